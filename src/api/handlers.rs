@@ -1,10 +1,120 @@
 use actix_web::{HttpResponse, web};
+use serde::Deserialize;
 use serde_json::json;
 use std::sync::Mutex;
 
+use crate::transaction::mempool::Mempool;
 use crate::transaction::tx::Transaction;
+use crate::blockchain::emission;
+use crate::blockchain::snapshot::{SnapshotCache, StateSnapshot};
+use crate::blockchain::template::{TemplateStore, WorkSubmission};
+use crate::blockchain::ChainError;
+use crate::consensus::finality::{CheckpointVote, FinalityCheckpoint};
+use crate::consensus::ConsensusKind;
+use crate::consensus::pos::MIN_STAKE;
 use crate::Blockchain;
 
+/// Maps a failed block lookup to a response: `410 Gone` with rule `pruned` if the
+/// node deleted the data, `404 Not Found` otherwise.
+fn lookup_error(e: ChainError) -> HttpResponse {
+    let body = json!({
+        "error": e.to_string(),
+        "rule": e.rule(),
+        "block_index": e.block_index()
+    });
+    match e {
+        ChainError::Pruned { .. } => HttpResponse::Gone().json(body),
+        _ => HttpResponse::NotFound().json(body),
+    }
+}
+
+/// GET /chain/block/{index}
+pub async fn get_block_by_index(
+    path: web::Path<u64>,
+    data: web::Data<Mutex<Blockchain>>,
+) -> HttpResponse {
+    let index = path.into_inner();
+    let blockchain = data.lock().unwrap();
+
+    match blockchain.block(index) {
+        Ok(block) => HttpResponse::Ok().json(block),
+        Err(e) => lookup_error(e),
+    }
+}
+
+/// Default page size of paginated explorer queries.
+const DEFAULT_PAGE_SIZE: usize = 25;
+
+/// Largest page size a client may request.
+const MAX_PAGE_SIZE: usize = 100;
+
+/// Query parameters for paginated explorer queries.
+#[derive(Debug, Deserialize)]
+pub struct PageQuery {
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+/// GET /chain/block/hash/{hash}
+pub async fn get_block_by_hash(
+    path: web::Path<String>,
+    data: web::Data<Mutex<Blockchain>>,
+) -> HttpResponse {
+    let hash = path.into_inner();
+    let blockchain = data.lock().unwrap();
+
+    match blockchain.height_of(&hash) {
+        Ok(Some(height)) => match blockchain.block(height) {
+            Ok(block) => HttpResponse::Ok().json(block),
+            Err(e) => lookup_error(e),
+        },
+        Ok(None) => HttpResponse::NotFound().json(json!({
+            "error": format!("Block {} is not on the active chain", hash)
+        })),
+        Err(e) => lookup_error(e),
+    }
+}
+
+/// GET /tx/{hash}
+pub async fn get_transaction(
+    path: web::Path<String>,
+    data: web::Data<Mutex<Blockchain>>,
+) -> HttpResponse {
+    let tx_hash = path.into_inner();
+    let blockchain = data.lock().unwrap();
+
+    match blockchain.find_transaction(&tx_hash) {
+        Ok(Some((location, tx))) => HttpResponse::Ok().json(json!({
+            "block_hash": location.block_hash,
+            "block_index": location.height,
+            "position": location.position,
+            "confirmations": blockchain.latest_block().index - location.height + 1,
+            "transaction": tx
+        })),
+        Ok(None) => HttpResponse::NotFound().json(json!({
+            "error": format!("Transaction {} is not on the active chain", tx_hash)
+        })),
+        Err(e) => lookup_error(e),
+    }
+}
+
+/// GET /address/{address}/transactions?offset={offset}&limit={limit}
+pub async fn get_address_transactions(
+    path: web::Path<String>,
+    query: web::Query<PageQuery>,
+    data: web::Data<Mutex<Blockchain>>,
+) -> HttpResponse {
+    let address = path.into_inner();
+    let blockchain = data.lock().unwrap();
+    let offset = query.offset.unwrap_or(0);
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+    match blockchain.address_history(&address, offset, limit) {
+        Ok(history) => HttpResponse::Ok().json(history),
+        Err(e) => HttpResponse::ServiceUnavailable().json(json!({ "error": e.to_string() })),
+    }
+}
+
 /// GET /chain
 pub async fn get_chain(data: web::Data<Mutex<Blockchain>>) -> HttpResponse {
     let blockchain = data.lock().unwrap();
@@ -44,13 +154,337 @@ pub async fn mine_block(data: web::Data<Mutex<Blockchain>>) -> HttpResponse {
     }))
 }
 
-/// GET /validate
-pub async fn validate_chain(data: web::Data<Mutex<Blockchain>>) -> HttpResponse {
+/// GET /balance/{address}
+pub async fn get_balance(
+    path: web::Path<String>,
+    data: web::Data<Mutex<Blockchain>>,
+) -> HttpResponse {
+    let address = path.into_inner();
     let blockchain = data.lock().unwrap();
-    let is_valid = blockchain.is_valid_chain();
+    let account = blockchain.state.account(&address);
 
     HttpResponse::Ok().json(json!({
-        "valid": is_valid,
-        "length": blockchain.chain.len()
+        "address": address,
+        "balance": account.balance,
+        "nonce": account.nonce,
+        "staked": account.staked,
+        "unbonding": account.unbonding,
+        "missed_proposals": account.missed_proposals,
+        "tombstoned": account.tombstoned
     }))
 }
+
+/// GET /balance/{address}/proof
+///
+/// Returns the account at the chain tip with a sparse Merkle proof against the
+/// tip header's state root, so a light client can check it without the state.
+pub async fn get_account_proof(
+    path: web::Path<String>,
+    data: web::Data<Mutex<Blockchain>>,
+) -> HttpResponse {
+    let address = path.into_inner();
+    let blockchain = data.lock().unwrap();
+    let tip = blockchain.latest_block();
+    let proof = blockchain.account_proof(&address);
+
+    HttpResponse::Ok().json(json!({
+        "block_index": tip.index,
+        "block_hash": tip.hash,
+        "state_root": tip.state_root,
+        "address": address,
+        "account": proof.account,
+        "proof": {
+            "siblings": proof.proof.siblings.iter().map(hex::encode).collect::<Vec<_>>(),
+            "leaf": proof.proof.leaf.map(|(key, value)| json!({
+                "key": hex::encode(key),
+                "value_hash": hex::encode(value)
+            }))
+        }
+    }))
+}
+
+/// Query parameters for GET /supply
+#[derive(Debug, Deserialize)]
+pub struct SupplyQuery {
+    pub height: Option<u64>,
+}
+
+/// GET /supply?height={height}
+pub async fn get_supply(
+    query: web::Query<SupplyQuery>,
+    data: web::Data<Mutex<Blockchain>>,
+) -> HttpResponse {
+    let blockchain = data.lock().unwrap();
+    let height = query.height.unwrap_or(blockchain.latest_block().index);
+
+    match blockchain.issued_supply(height) {
+        Ok(issued) => HttpResponse::Ok().json(json!({
+            "height": height,
+            "issued": issued,
+            "scheduled": emission::supply_at(&blockchain.spec, height),
+            "max_supply": blockchain.spec.consensus.max_supply
+        })),
+        Err(e) => lookup_error(e),
+    }
+}
+
+/// GET /chain/block/{index}/proof/{tx_hash}
+pub async fn get_merkle_proof(
+    path: web::Path<(u64, String)>,
+    data: web::Data<Mutex<Blockchain>>,
+) -> HttpResponse {
+    let (index, tx_hash) = path.into_inner();
+    let blockchain = data.lock().unwrap();
+
+    let block = match blockchain.block(index) {
+        Ok(block) => block,
+        Err(e) => return lookup_error(e),
+    };
+
+    match block.merkle_proof(&tx_hash) {
+        Some(proof) => HttpResponse::Ok().json(json!({
+            "block_index": block.index,
+            "block_hash": block.hash,
+            "merkle_root": block.merkle_root,
+            "tx_hash": tx_hash,
+            "proof": {
+                "index": proof.index,
+                "leaf_count": proof.leaf_count,
+                "siblings": proof.siblings.iter().map(hex::encode).collect::<Vec<_>>()
+            }
+        })),
+        None => HttpResponse::NotFound().json(json!({
+            "error": format!("Transaction {} is not in block {}", tx_hash, index)
+        })),
+    }
+}
+
+/// Query parameters for GET /mining/template
+#[derive(Debug, Deserialize)]
+pub struct TemplateQuery {
+    /// Address the coinbase pays to.
+    pub address: String,
+}
+
+/// GET /mining/template?address={address}
+pub async fn get_block_template(
+    query: web::Query<TemplateQuery>,
+    data: web::Data<Mutex<Blockchain>>,
+    mempool: web::Data<Mutex<Mempool>>,
+    templates: web::Data<Mutex<TemplateStore>>,
+) -> HttpResponse {
+    let blockchain = data.lock().unwrap();
+    let engine = blockchain.engine().kind();
+    if !matches!(engine, ConsensusKind::Pow | ConsensusKind::Hybrid) {
+        return HttpResponse::BadRequest().json(json!({
+            "error": format!("Block templates are for proof-of-work mining; this network runs {}.", engine)
+        }));
+    }
+    let candidates = mempool.lock().unwrap().all();
+
+    match blockchain.block_template(&candidates, &query.address) {
+        Ok(template) => {
+            let mut templates = templates.lock().unwrap();
+            templates.retain_tip(&template.previous_hash);
+            templates.insert(template.clone());
+            HttpResponse::Ok().json(template)
+        }
+        Err(e) => HttpResponse::InternalServerError().json(json!({ "error": e.to_string() })),
+    }
+}
+
+/// POST /mining/submit
+pub async fn submit_work(
+    submission: web::Json<WorkSubmission>,
+    data: web::Data<Mutex<Blockchain>>,
+    mempool: web::Data<Mutex<Mempool>>,
+    templates: web::Data<Mutex<TemplateStore>>,
+) -> HttpResponse {
+    let mut blockchain = data.lock().unwrap();
+    let mut mempool = mempool.lock().unwrap();
+    let mut templates = templates.lock().unwrap();
+
+    let block = match templates.solve(&submission) {
+        Ok(block) => block,
+        Err(e) => return HttpResponse::BadRequest().json(json!({ "error": e })),
+    };
+    let (index, hash) = (block.index, block.hash.clone());
+
+    match blockchain.accept_block(block, &mut mempool) {
+        Ok(connected) => {
+            templates.retain_tip(&blockchain.latest_block().hash);
+            HttpResponse::Ok().json(json!({
+                "status": if connected { "Block connected" } else { "Block stored on a side branch" },
+                "block_index": index,
+                "hash": hash
+            }))
+        }
+        Err(e) => HttpResponse::BadRequest().json(json!({
+            "error": e.to_string(),
+            "rule": e.rule(),
+            "block_index": e.block_index()
+        })),
+    }
+}
+
+/// GET /staking/validators
+///
+/// Returns the validators at the chain tip (accounts staking at least the
+/// minimum stake) with their stakes, and the unbonding period.
+pub async fn get_validators(data: web::Data<Mutex<Blockchain>>) -> HttpResponse {
+    let blockchain = data.lock().unwrap();
+    let pool = blockchain.staking_pool();
+    let validators: Vec<_> = pool
+        .get_validators()
+        .into_iter()
+        .map(|address| {
+            json!({
+                "address": address,
+                "stake": pool.get_stake(&address),
+                "missed_proposals": blockchain.state.account(&address).missed_proposals
+            })
+        })
+        .collect();
+
+    HttpResponse::Ok().json(json!({
+        "block_index": blockchain.latest_block().index,
+        "total_stake": pool.total_stake(),
+        "min_stake": MIN_STAKE,
+        "unbonding_period": blockchain.spec.consensus.unbonding_period,
+        "validators": validators
+    }))
+}
+
+/// GET /chain/finality
+///
+/// Returns the latest finalized checkpoint with its votes (`null` if none).
+pub async fn get_finality(data: web::Data<Mutex<Blockchain>>) -> HttpResponse {
+    let blockchain = data.lock().unwrap();
+    HttpResponse::Ok().json(json!({
+        "engine": blockchain.engine().name(),
+        "finality_interval": blockchain.spec.consensus.finality_interval,
+        "finalized": blockchain.finalized()
+    }))
+}
+
+/// POST /chain/finality/vote
+///
+/// Records a validator's signed vote for a checkpoint block.
+pub async fn submit_finality_vote(
+    vote: web::Json<CheckpointVote>,
+    data: web::Data<Mutex<Blockchain>>,
+) -> HttpResponse {
+    let mut blockchain = data.lock().unwrap();
+    let height = vote.height;
+
+    match blockchain.submit_finality_vote(vote.into_inner()) {
+        Ok(finalized) => HttpResponse::Ok().json(json!({
+            "status": if finalized { "Block finalized" } else { "Vote recorded" },
+            "block_index": height,
+            "finalized_height": blockchain.finalized_height()
+        })),
+        Err(e) => HttpResponse::BadRequest().json(json!({
+            "error": e.to_string(),
+            "rule": e.rule(),
+            "block_index": e.block_index()
+        })),
+    }
+}
+
+/// POST /chain/finality
+///
+/// Accepts a checkpoint finalized by another node, with all of its votes.
+pub async fn submit_finality_checkpoint(
+    checkpoint: web::Json<FinalityCheckpoint>,
+    data: web::Data<Mutex<Blockchain>>,
+) -> HttpResponse {
+    let mut blockchain = data.lock().unwrap();
+
+    match blockchain.accept_finality_checkpoint(checkpoint.into_inner()) {
+        Ok(finalized) => HttpResponse::Ok().json(json!({
+            "status": if finalized { "Block finalized" } else { "Already finalized" },
+            "finalized_height": blockchain.finalized_height()
+        })),
+        Err(e) => HttpResponse::BadRequest().json(json!({
+            "error": e.to_string(),
+            "rule": e.rule(),
+            "block_index": e.block_index()
+        })),
+    }
+}
+
+/// GET /validate
+pub async fn validate_chain(data: web::Data<Mutex<Blockchain>>) -> HttpResponse {
+    let blockchain = data.lock().unwrap();
+
+    match blockchain.validate_chain() {
+        Ok(()) => HttpResponse::Ok().json(json!({
+            "valid": true,
+            "length": blockchain.length()
+        })),
+        Err(e) => HttpResponse::Ok().json(json!({
+            "valid": false,
+            "length": blockchain.length(),
+            "error": e.to_string(),
+            "rule": e.rule(),
+            "block_index": e.block_index()
+        })),
+    }
+}
+
+/// Returns the snapshot at `height`, from the cache if it was built before for the
+/// block now at that height.
+fn cached_snapshot<'a>(
+    blockchain: &Blockchain,
+    snapshots: &'a mut SnapshotCache,
+    height: u64,
+) -> Result<&'a StateSnapshot, ChainError> {
+    let anchor = blockchain.block_at(height).map(|block| block.hash.clone());
+    if let Some(hash) = anchor.as_deref().filter(|hash| snapshots.get(hash).is_some()) {
+        return Ok(snapshots.get(hash).expect("Snapshot is cached"));
+    }
+    let snapshot = blockchain.snapshot(height)?;
+    let hash = snapshot.manifest.block_hash.clone();
+    snapshots.insert(snapshot);
+    Ok(snapshots.get(&hash).expect("Snapshot was just cached"))
+}
+
+/// GET /snapshot/{height}
+///
+/// Returns the manifest of a state snapshot at the given active-chain height.
+pub async fn get_snapshot_manifest(
+    path: web::Path<u64>,
+    data: web::Data<Mutex<Blockchain>>,
+    snapshots: web::Data<Mutex<SnapshotCache>>,
+) -> HttpResponse {
+    let height = path.into_inner();
+    let blockchain = data.lock().unwrap();
+    let mut snapshots = snapshots.lock().unwrap();
+
+    match cached_snapshot(&blockchain, &mut snapshots, height) {
+        Ok(snapshot) => HttpResponse::Ok().json(&snapshot.manifest),
+        Err(e) => lookup_error(e),
+    }
+}
+
+/// GET /snapshot/{height}/chunk/{index}
+pub async fn get_snapshot_chunk(
+    path: web::Path<(u64, u32)>,
+    data: web::Data<Mutex<Blockchain>>,
+    snapshots: web::Data<Mutex<SnapshotCache>>,
+) -> HttpResponse {
+    let (height, index) = path.into_inner();
+    let blockchain = data.lock().unwrap();
+    let mut snapshots = snapshots.lock().unwrap();
+
+    let snapshot = match cached_snapshot(&blockchain, &mut snapshots, height) {
+        Ok(snapshot) => snapshot,
+        Err(e) => return lookup_error(e),
+    };
+    match snapshot.chunks.iter().find(|chunk| chunk.index == index) {
+        Some(chunk) => HttpResponse::Ok().json(chunk),
+        None => HttpResponse::NotFound().json(json!({
+            "error": format!("Snapshot at height {} has no chunk {}", height, index)
+        })),
+    }
+}
@@ -6,8 +6,23 @@ use super::handlers::{
     mine_block,
     validate_chain,
     get_block_by_index,
+    get_block_by_hash,
     get_latest_block,
+    get_merkle_proof,
+    get_validators,
+    get_finality,
+    submit_finality_vote,
+    submit_finality_checkpoint,
     get_transaction_pool,
+    get_balance,
+    get_account_proof,
+    get_supply,
+    get_block_template,
+    submit_work,
+    get_snapshot_manifest,
+    get_snapshot_chunk,
+    get_transaction,
+    get_address_transactions,
     health_check,
 };
 
@@ -24,14 +39,39 @@ pub fn configure_routes(cfg: &mut ServiceConfig) {
         .route("/chain", web::get().to(get_chain))
         .route("/chain/latest", web::get().to(get_latest_block))
         .route("/chain/block/{index}", web::get().to(get_block_by_index))
+        .route("/chain/block/{index}/proof/{tx_hash}", web::get().to(get_merkle_proof))
+        .route("/chain/block/hash/{hash}", web::get().to(get_block_by_hash))
         .route("/validate", web::get().to(validate_chain))
 
         // --- Transaction Handling ---
         .route("/transactions", web::post().to(add_transaction))
         .route("/transactions/pool", web::get().to(get_transaction_pool))
 
+        // --- Account State ---
+        .route("/balance/{address}", web::get().to(get_balance))
+        .route("/balance/{address}/proof", web::get().to(get_account_proof))
+        .route("/supply", web::get().to(get_supply))
+
         // --- Mining Operations ---
         .route("/mine", web::post().to(mine_block))
+        .route("/mining/template", web::get().to(get_block_template))
+        .route("/mining/submit", web::post().to(submit_work))
+
+        // --- Staking ---
+        .route("/staking/validators", web::get().to(get_validators))
+
+        // --- Finality (hybrid PoW + PoS) ---
+        .route("/chain/finality", web::get().to(get_finality))
+        .route("/chain/finality", web::post().to(submit_finality_checkpoint))
+        .route("/chain/finality/vote", web::post().to(submit_finality_vote))
+
+        // --- State Snapshots (fast sync) ---
+        .route("/snapshot/{height}", web::get().to(get_snapshot_manifest))
+        .route("/snapshot/{height}/chunk/{index}", web::get().to(get_snapshot_chunk))
+
+        // --- Explorer Indexes ---
+        .route("/tx/{hash}", web::get().to(get_transaction))
+        .route("/address/{address}/transactions", web::get().to(get_address_transactions))
 
         // --- Node Utilities ---
         .route("/health", web::get().to(health_check));
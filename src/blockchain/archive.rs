@@ -0,0 +1,180 @@
+//! # Chain Archives
+//! A streaming binary format for exporting the active chain and importing it
+//! into another node (bootstrap, archival, disaster recovery).
+//!
+//! Layout (all integers little-endian):
+//!
+//! | Field        | Size     | Content                                    |
+//! |--------------|----------|--------------------------------------------|
+//! | magic        | 8 bytes  | [`ARCHIVE_MAGIC`]                          |
+//! | version      | `u32`    | [`ARCHIVE_VERSION`]                        |
+//! | network id   | `u32`    | `network_id` of the [`ChainSpec`](crate::blockchain::spec::ChainSpec) |
+//! | genesis hash | 32 bytes | hash of the network's genesis block        |
+//! | blocks       | repeated | `u32` length, then the bincode-encoded [`Block`] |
+//!
+//! Blocks follow in height order and the archive ends at end of input. Both
+//! sides handle one block at a time, so an archive of any size is written and
+//! read in constant memory. The reader only checks framing and the header; the
+//! blocks themselves are validated by [`Blockchain::import_archive`](crate::blockchain::Blockchain::import_archive).
+
+use std::io::{ErrorKind, Read, Write};
+
+use crate::blockchain::block::Block;
+
+/// Marks the start of a chain archive.
+pub const ARCHIVE_MAGIC: [u8; 8] = *b"OCOSARCH";
+
+/// Current archive format version.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// Largest encoded block an archive reader accepts, so a corrupt length prefix
+/// cannot make it allocate without bound.
+pub const MAX_ARCHIVE_BLOCK_BYTES: u32 = 32 * 1024 * 1024;
+
+/// Outcome of importing an archive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// Blocks validated and added to the chain.
+    pub imported: u64,
+
+    /// Blocks the chain already had.
+    pub skipped: u64,
+}
+
+/// Writes blocks to an archive.
+pub struct ArchiveWriter<W: Write> {
+    writer: W,
+    blocks: u64,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    /// Writes the archive header for the network with the given id and genesis block hash.
+    pub fn new(mut writer: W, network_id: u32, genesis_hash: &str) -> Result<Self, String> {
+        let genesis_hash = hash_bytes(genesis_hash)?;
+        let mut header = Vec::with_capacity(48);
+        header.extend_from_slice(&ARCHIVE_MAGIC);
+        header.extend_from_slice(&ARCHIVE_VERSION.to_le_bytes());
+        header.extend_from_slice(&network_id.to_le_bytes());
+        header.extend_from_slice(&genesis_hash);
+        writer.write_all(&header).map_err(write_error)?;
+        Ok(Self { writer, blocks: 0 })
+    }
+
+    /// Appends one block.
+    pub fn write_block(&mut self, block: &Block) -> Result<(), String> {
+        let bytes = bincode::serialize(block).map_err(|e| format!("Serialization error: {}", e))?;
+        let length = u32::try_from(bytes.len())
+            .ok()
+            .filter(|length| *length <= MAX_ARCHIVE_BLOCK_BYTES)
+            .ok_or_else(|| format!("Block {} is too large for an archive.", block.index))?;
+        self.writer.write_all(&length.to_le_bytes()).map_err(write_error)?;
+        self.writer.write_all(&bytes).map_err(write_error)?;
+        self.blocks += 1;
+        Ok(())
+    }
+
+    /// Number of blocks written so far.
+    pub fn blocks_written(&self) -> u64 {
+        self.blocks
+    }
+
+    /// Flushes the output and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, String> {
+        self.writer.flush().map_err(write_error)?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads blocks from an archive, one at a time.
+///
+/// Iterating yields each block in archive order; a truncated or malformed
+/// record yields an error and ends the iteration.
+pub struct ArchiveReader<R: Read> {
+    reader: R,
+    failed: bool,
+}
+
+impl<R: Read> ArchiveReader<R> {
+    /// Reads the archive header and checks it against the network with the given
+    /// id and genesis block hash.
+    pub fn new(mut reader: R, network_id: u32, genesis_hash: &str) -> Result<Self, String> {
+        let mut header = [0u8; 48];
+        reader
+            .read_exact(&mut header)
+            .map_err(|e| format!("Cannot read archive header: {}", e))?;
+        if header[..8] != ARCHIVE_MAGIC {
+            return Err("Not a chain archive (bad magic).".into());
+        }
+        let version = u32::from_le_bytes(header[8..12].try_into().expect("4-byte slice"));
+        if version != ARCHIVE_VERSION {
+            return Err(format!(
+                "Unsupported archive version {} (expected {}).",
+                version, ARCHIVE_VERSION
+            ));
+        }
+        let archive_network = u32::from_le_bytes(header[12..16].try_into().expect("4-byte slice"));
+        if archive_network != network_id {
+            return Err(format!(
+                "Archive belongs to network {}, this node runs network {}.",
+                archive_network, network_id
+            ));
+        }
+        if header[16..] != hash_bytes(genesis_hash)?[..] {
+            return Err("Archive was exported from a chain with a different genesis block.".into());
+        }
+        Ok(Self { reader, failed: false })
+    }
+
+    /// Reads the next block; `Ok(None)` at a clean end of the archive.
+    pub fn read_block(&mut self) -> Result<Option<Block>, String> {
+        // End of input is only clean before the first byte of a length prefix.
+        let mut length = [0u8; 4];
+        let mut filled = 0;
+        while filled < length.len() {
+            match self.reader.read(&mut length[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err("Truncated archive record length.".into()),
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(format!("Archive read error: {}", e)),
+            }
+        }
+        let length = u32::from_le_bytes(length);
+        if length > MAX_ARCHIVE_BLOCK_BYTES {
+            return Err(format!("Archive record of {} bytes exceeds the block size limit.", length));
+        }
+        let mut bytes = vec![0u8; length as usize];
+        self.reader
+            .read_exact(&mut bytes)
+            .map_err(|e| format!("Truncated archive record: {}", e))?;
+        bincode::deserialize(&bytes)
+            .map(Some)
+            .map_err(|e| format!("Invalid archive record: {}", e))
+    }
+}
+
+impl<R: Read> Iterator for ArchiveReader<R> {
+    type Item = Result<Block, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let next = self.read_block().transpose();
+        if matches!(next, Some(Err(_))) {
+            self.failed = true;
+        }
+        next
+    }
+}
+
+fn hash_bytes(hash: &str) -> Result<Vec<u8>, String> {
+    hex::decode(hash)
+        .ok()
+        .filter(|bytes| bytes.len() == 32)
+        .ok_or_else(|| format!("Block hash {} is not 32 bytes of hex.", hash))
+}
+
+fn write_error(e: std::io::Error) -> String {
+    format!("Archive write error: {}", e)
+}
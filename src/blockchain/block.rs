@@ -3,23 +3,198 @@
 //! This module contains the core definition of a `Block`,
 //! including its structure, constructor, and hash calculation.
 //!
+//! The block hash is computed over a [`BlockHeader`]: a versioned, fixed-width
+//! binary encoding of the header fields. Every component that hashes, mines or
+//! validates a block goes through [`BlockHeader::to_bytes`], so there is exactly
+//! one canonical preimage per block.
+//!
+//! Since header version 2 the header also commits to the root of the world
+//! state after the block is applied (see
+//! [`WorldState::state_root`](crate::blockchain::state::WorldState::state_root)),
+//! so a node can adopt a state snapshot verified against one trusted header.
+//! Since version 3 it also carries the leader seed of a proof-of-stake block
+//! (see [`crate::consensus::pos`]); other blocks leave it zero.
+//!
+//! A proof-of-stake block also carries its proposer's [`BlockSignature`] over
+//! the encoded header and the network id (so a signature made for one network
+//! proves nothing on another), and the VRF proof of its seed. Neither is part
+//! of the header, so they do not change the block hash.
+//!
 //! Inspired by Bitcoin and adapted for modular, future-proof Rust-based chains.
 
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
 use hex::encode;
 
+use crate::consensus::difficulty::hash_meets_target;
+use crate::consensus::miner::{CancelHandle, Miner};
+use crate::crypto::hash::{double_sha256, merkle_proof, merkle_root, verify_merkle_proof, MerkleProof};
+use crate::crypto::keypair::{PrivateKey, PublicKey};
+use crate::crypto::signature::{sign_message, verify_signature, Signature};
 use crate::transaction::tx::Transaction;
+use crate::wallet::wallet::derive_address;
+
+/// Hex-encoded double-SHA256 hash of a block header.
+pub type BlockHash = String;
+
+/// Current block header format version.
+pub const BLOCK_VERSION: u32 = 3;
+
+/// Size in bytes of an encoded [`BlockHeader`].
+pub const HEADER_SIZE: usize = 4 + 8 + 8 + 32 + 32 + 32 + 32 + 4 + 8;
+
+/// Largest [`Block::encoded_size`], in bytes, a valid block may have.
+pub const MAX_BLOCK_SIZE: u64 = 1_000_000;
+
+/// Most transactions, coinbase included, a valid block may hold.
+pub const MAX_BLOCK_TRANSACTIONS: usize = 1_000;
+
+/// Domain separator of the proposer's signed message.
+const PROPOSAL_DOMAIN: &[u8] = b"OCOS-PROPOSAL";
+
+/// The consensus-critical part of a block, hashed to produce the block hash.
+///
+/// Encoding (all integers little-endian, fixed width):
+///
+/// | Field           | Bytes |
+/// |-----------------|-------|
+/// | `version`       | 4     |
+/// | `index`         | 8     |
+/// | `timestamp`     | 8     |
+/// | `previous_hash` | 32    |
+/// | `merkle_root`   | 32    |
+/// | `state_root`    | 32    |
+/// | `seed`          | 32    |
+/// | `bits`          | 4     |
+/// | `nonce`         | 8     |
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub version: u32,
+    pub index: u64,
+    /// Block time in seconds since the UNIX epoch.
+    pub timestamp: i64,
+    pub previous_hash: [u8; 32],
+    pub merkle_root: [u8; 32],
+    /// Root of the world state after the block is applied.
+    pub state_root: [u8; 32],
+    /// Leader seed of a proof-of-stake block (zero otherwise).
+    pub seed: [u8; 32],
+    /// Compact proof-of-work target (see `consensus::difficulty`).
+    pub bits: u32,
+    pub nonce: u64,
+}
+
+impl BlockHeader {
+    /// Serializes the header into its canonical fixed-width binary form.
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut out = [0u8; HEADER_SIZE];
+        out[0..4].copy_from_slice(&self.version.to_le_bytes());
+        out[4..12].copy_from_slice(&self.index.to_le_bytes());
+        out[12..20].copy_from_slice(&self.timestamp.to_le_bytes());
+        out[20..52].copy_from_slice(&self.previous_hash);
+        out[52..84].copy_from_slice(&self.merkle_root);
+        out[84..116].copy_from_slice(&self.state_root);
+        out[116..148].copy_from_slice(&self.seed);
+        out[148..152].copy_from_slice(&self.bits.to_le_bytes());
+        out[152..160].copy_from_slice(&self.nonce.to_le_bytes());
+        out
+    }
+
+    /// Decodes a header from its canonical binary form.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() != HEADER_SIZE {
+            return Err(format!(
+                "Invalid header length: expected {} bytes, got {}.",
+                HEADER_SIZE,
+                bytes.len()
+            ));
+        }
+
+        let mut previous_hash = [0u8; 32];
+        previous_hash.copy_from_slice(&bytes[20..52]);
+        let mut merkle_root = [0u8; 32];
+        merkle_root.copy_from_slice(&bytes[52..84]);
+        let mut state_root = [0u8; 32];
+        state_root.copy_from_slice(&bytes[84..116]);
+        let mut seed = [0u8; 32];
+        seed.copy_from_slice(&bytes[116..148]);
+
+        Ok(Self {
+            version: u32::from_le_bytes(bytes[0..4].try_into().unwrap()),
+            index: u64::from_le_bytes(bytes[4..12].try_into().unwrap()),
+            timestamp: i64::from_le_bytes(bytes[12..20].try_into().unwrap()),
+            previous_hash,
+            merkle_root,
+            state_root,
+            seed,
+            bits: u32::from_le_bytes(bytes[148..152].try_into().unwrap()),
+            nonce: u64::from_le_bytes(bytes[152..160].try_into().unwrap()),
+        })
+    }
+
+    /// Computes the block hash: double SHA-256 over the encoded header.
+    pub fn hash(&self) -> BlockHash {
+        encode(double_sha256(&self.to_bytes()))
+    }
+}
+
+/// A block producer's signature over an encoded [`BlockHeader`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockSignature {
+    /// Hex-encoded uncompressed public key of the signer.
+    pub public_key: String,
+
+    /// Hex-encoded DER signature over [`BlockSignature::message`].
+    pub signature: String,
+}
+
+impl BlockSignature {
+    /// The bytes a proposer signs: domain, network id and encoded header.
+    pub fn message(network_id: u32, header: &BlockHeader) -> Vec<u8> {
+        let mut message = PROPOSAL_DOMAIN.to_vec();
+        message.extend_from_slice(&network_id.to_le_bytes());
+        message.extend_from_slice(&header.to_bytes());
+        message
+    }
+
+    /// Signs `header` for the network `network_id` with `private_key`.
+    pub fn sign(network_id: u32, header: &BlockHeader, private_key: &PrivateKey) -> Self {
+        Self {
+            public_key: encode(private_key.public_key().to_bytes()),
+            signature: encode(sign_message(&Self::message(network_id, header), private_key).to_bytes()),
+        }
+    }
+
+    /// Checks the signature over `header` for the network `network_id` and
+    /// returns the signer's address.
+    pub fn verify(&self, network_id: u32, header: &BlockHeader) -> Result<String, String> {
+        let public_key = hex::decode(&self.public_key)
+            .ok()
+            .and_then(|bytes| PublicKey::from_bytes(&bytes))
+            .ok_or_else(|| "block signature carries an invalid public key".to_string())?;
+        let signature = hex::decode(&self.signature)
+            .ok()
+            .and_then(|bytes| Signature::from_bytes(&bytes))
+            .ok_or_else(|| "block signature is malformed".to_string())?;
+        if !verify_signature(&Self::message(network_id, header), &signature, &public_key) {
+            return Err("block signature does not match the header".into());
+        }
+        Ok(derive_address(&public_key))
+    }
+}
 
 /// Represents a single block in the OCOS blockchain.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Block {
+    /// Header format version.
+    #[serde(default = "default_version")]
+    pub version: u32,
+
     /// Position of the block in the chain.
     pub index: u64,
 
-    /// UTC timestamp when the block was created.
-    pub timestamp: String,
+    /// Block time in seconds since the UNIX epoch (UTC).
+    pub timestamp: i64,
 
     /// Hash of the previous block in the chain.
     pub previous_hash: String,
@@ -27,61 +202,148 @@ pub struct Block {
     /// Merkle root of all transactions in this block.
     pub merkle_root: String,
 
-    /// SHA256 hash of the current block (after mining).
+    /// Root of the world state after applying this block.
+    pub state_root: String,
+
+    /// Leader seed of a proof-of-stake block, all zeros for other blocks.
+    pub seed: String,
+
+    /// Double-SHA256 hash of the block header (after mining).
     pub hash: String,
 
     /// Nonce used to mine the block.
     pub nonce: u64,
 
-    /// Difficulty level used during mining (number of leading zeros).
-    pub difficulty: usize,
+    /// Compact proof-of-work target the block hash must meet.
+    pub bits: u32,
 
     /// All transactions included in the block.
     pub transactions: Vec<Transaction>,
+
+    /// Proposer's signature over the header (proof-of-stake blocks only).
+    #[serde(default)]
+    pub signature: Option<BlockSignature>,
+
+    /// Hex-encoded VRF proof that `seed` is the proposer's (proof-of-stake blocks only).
+    #[serde(default)]
+    pub seed_proof: Option<String>,
+}
+
+fn default_version() -> u32 {
+    BLOCK_VERSION
+}
+
+/// Decodes a 64-character hex hash; anything else maps to `None`.
+fn decode_hash32(hex_str: &str) -> Option<[u8; 32]> {
+    let bytes = hex::decode(hex_str).ok()?;
+    bytes.try_into().ok()
 }
 
 impl Block {
     /// Creates a new (unmined) block.
+    ///
+    /// The state root starts out as all zeros; block producers set it with
+    /// [`Block::with_state_root`] before mining.
     pub fn new(
         index: u64,
         previous_hash: String,
         transactions: Vec<Transaction>,
-        difficulty: usize,
+        bits: u32,
     ) -> Self {
-        let timestamp: DateTime<Utc> = Utc::now();
-        let timestamp_str = timestamp.to_rfc3339();
         let merkle_root = Block::calculate_merkle_root(&transactions);
 
         Self {
+            version: BLOCK_VERSION,
             index,
-            timestamp: timestamp_str,
+            timestamp: Utc::now().timestamp(),
             previous_hash,
             merkle_root,
+            state_root: "0".repeat(64),
+            seed: "0".repeat(64),
             hash: String::new(), // Will be computed after mining
             nonce: 0,
-            difficulty,
+            bits,
             transactions,
+            signature: None,
+            seed_proof: None,
         }
     }
 
-    /// Returns the block header string (used for hashing).
-    pub fn header_string(&self) -> String {
-        format!(
-            "{}{}{}{}{}{}",
-            self.index,
-            self.timestamp,
-            self.previous_hash,
-            self.merkle_root,
-            self.nonce,
-            self.difficulty
-        )
+    /// Sets the root of the world state after this block.
+    pub fn with_state_root(mut self, state_root: String) -> Self {
+        self.state_root = state_root;
+        self
     }
 
-    /// Calculates the hash of the block using SHA256.
+    /// Rebuilds a body-less block from a header: the fields and hash are those of the
+    /// original block, but it carries no transactions. Used for the ancestors of a
+    /// snapshot, which are only needed for their headers.
+    pub fn from_header(header: &BlockHeader) -> Self {
+        Self {
+            version: header.version,
+            index: header.index,
+            timestamp: header.timestamp,
+            previous_hash: encode(header.previous_hash),
+            merkle_root: encode(header.merkle_root),
+            state_root: encode(header.state_root),
+            seed: encode(header.seed),
+            hash: header.hash(),
+            nonce: header.nonce,
+            bits: header.bits,
+            transactions: Vec::new(),
+            signature: None,
+            seed_proof: None,
+        }
+    }
+
+    /// Builds the binary header for this block.
+    ///
+    /// Malformed fields encode as zeros; [`Block::has_canonical_fields`] rejects such blocks.
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            version: self.version,
+            index: self.index,
+            timestamp: self.timestamp,
+            previous_hash: decode_hash32(&self.previous_hash).unwrap_or([0u8; 32]),
+            merkle_root: decode_hash32(&self.merkle_root).unwrap_or([0u8; 32]),
+            state_root: decode_hash32(&self.state_root).unwrap_or([0u8; 32]),
+            seed: decode_hash32(&self.seed).unwrap_or([0u8; 32]),
+            bits: self.bits,
+            nonce: self.nonce,
+        }
+    }
+
+    /// Checks that the textual fields map one-to-one onto the binary header,
+    /// so no two distinct blocks share a header encoding.
+    pub fn has_canonical_fields(&self) -> bool {
+        let previous_ok = decode_hash32(&self.previous_hash)
+            .is_some_and(|h| encode(h) == self.previous_hash);
+        let merkle_ok = decode_hash32(&self.merkle_root)
+            .is_some_and(|h| encode(h) == self.merkle_root);
+        let state_ok = decode_hash32(&self.state_root)
+            .is_some_and(|h| encode(h) == self.state_root);
+        let seed_ok = decode_hash32(&self.seed)
+            .is_some_and(|h| encode(h) == self.seed);
+
+        self.version == BLOCK_VERSION && previous_ok && merkle_ok && state_ok && seed_ok
+    }
+
+    /// Calculates the hash of the block from its canonical header encoding.
     pub fn calculate_hash(&self) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(self.header_string());
-        encode(hasher.finalize())
+        self.header().hash()
+    }
+
+    /// Size of the block as stored and sent to peers (its bincode encoding), in bytes.
+    pub fn encoded_size(&self) -> u64 {
+        bincode::serialized_size(self).unwrap_or(u64::MAX)
+    }
+
+    /// Merkle leaves of a transaction list: the raw bytes of each transaction hash.
+    fn merkle_leaves(transactions: &[Transaction]) -> Vec<Vec<u8>> {
+        transactions
+            .iter()
+            .map(|tx| hex::decode(tx.compute_hash()).unwrap_or_default())
+            .collect()
     }
 
     /// Calculates the Merkle root from the transactions.
@@ -90,47 +352,97 @@ impl Block {
             return "0".repeat(64);
         }
 
-        let mut hashes: Vec<String> = transactions
+        encode(merkle_root(&Self::merkle_leaves(transactions)))
+    }
+
+    /// Builds the Merkle inclusion proof for the transaction with the given hash.
+    pub fn merkle_proof(&self, tx_hash: &str) -> Option<MerkleProof> {
+        let index = self
+            .transactions
             .iter()
-            .map(|tx| tx.calculate_hash())
-            .collect();
-
-        while hashes.len() > 1 {
-            let mut new_hashes = Vec::new();
-            for i in (0..hashes.len()).step_by(2) {
-                let a = &hashes[i];
-                let b = if i + 1 < hashes.len() {
-                    &hashes[i + 1]
-                } else {
-                    a // duplicate the last hash if odd number
-                };
-
-                let mut hasher = Sha256::new();
-                hasher.update(a.as_bytes());
-                hasher.update(b.as_bytes());
-                new_hashes.push(encode(hasher.finalize()));
-            }
-            hashes = new_hashes;
-        }
+            .position(|tx| tx.compute_hash() == tx_hash)?;
+        merkle_proof(&Self::merkle_leaves(&self.transactions), index)
+    }
 
-        hashes[0].clone()
+    /// Checks a Merkle inclusion proof for `tx_hash` against a block's `merkle_root`.
+    ///
+    /// Only needs the header field, so light clients can verify without the block body.
+    pub fn verify_merkle_proof(tx_hash: &str, proof: &MerkleProof, merkle_root: &str) -> bool {
+        match (hex::decode(tx_hash), hex::decode(merkle_root)) {
+            (Ok(leaf), Ok(root)) => verify_merkle_proof(&leaf, proof, &root),
+            _ => false,
+        }
     }
 
-    /// Starts mining by iterating nonces until the hash meets the difficulty target.
+    /// Mines the block on the calling thread, searching nonces upward from zero.
+    ///
+    /// Deterministic and uncancellable; use [`Miner`] for parallel or interruptible mining.
     pub fn mine(&mut self) {
-        loop {
-            let hash = self.calculate_hash();
-            if hash.starts_with(&"0".repeat(self.difficulty)) {
-                self.hash = hash;
-                break;
-            } else {
-                self.nonce += 1;
-            }
-        }
+        Miner::default().mine(self, &CancelHandle::new());
+    }
+
+    /// Validates the block hash against the target the chain expects at this height.
+    ///
+    /// `expected_bits` comes from the retargeting rules, never from the block itself,
+    /// so a miner cannot lower its own difficulty.
+    pub fn is_valid(&self, expected_bits: u32) -> bool {
+        self.bits == expected_bits
+            && self.has_canonical_fields()
+            && self.hash == self.calculate_hash()
+            && hash_meets_target(&self.hash, self.bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Block {
+        let mut block = Block::new(7, "ab".repeat(32), vec![Transaction::new("a", "b", 1, "")], 0x1d00ffff);
+        block.timestamp = 1_700_000_000;
+        block.nonce = 42;
+        block
+    }
+
+    #[test]
+    fn header_round_trips_through_bytes() {
+        let header = sample().header();
+        let bytes = header.to_bytes();
+        assert_eq!(bytes.len(), HEADER_SIZE);
+        assert_eq!(BlockHeader::from_bytes(&bytes), Ok(header.clone()));
+        assert_eq!(header.timestamp, 1_700_000_000);
+        assert_eq!(header.previous_hash, [0xab; 32]);
+        assert!(BlockHeader::from_bytes(&bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn hash_covers_every_header_field() {
+        let block = sample();
+        let hash = block.calculate_hash();
+
+        let mut other = block.clone();
+        other.nonce += 1;
+        assert_ne!(other.calculate_hash(), hash);
+
+        let mut other = block.clone();
+        other.timestamp += 1;
+        assert_ne!(other.calculate_hash(), hash);
+
+        let mut other = block;
+        other.bits += 1;
+        assert_ne!(other.calculate_hash(), hash);
     }
 
-    /// Validates the current block hash against expected difficulty.
-    pub fn is_valid(&self) -> bool {
-        self.hash == self.calculate_hash() && self.hash.starts_with(&"0".repeat(self.difficulty))
+    #[test]
+    fn rejects_fields_without_a_canonical_encoding() {
+        assert!(sample().has_canonical_fields());
+
+        let mut block = sample();
+        block.previous_hash = "AB".repeat(32);
+        assert!(!block.has_canonical_fields());
+
+        let mut block = sample();
+        block.merkle_root = "00".to_string();
+        assert!(!block.has_canonical_fields());
     }
 }
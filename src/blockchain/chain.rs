@@ -1,24 +1,423 @@
 //! # Chain Module
 //! This module manages the blockchain structure, including block sequencing,
 //! hash validation, block addition logic, and chain integrity checks.
+//!
+//! A [`Blockchain`] keeps the active chain, the competing branches of its
+//! [`BlockTree`] and the [`WorldState`] after the tip. Every block goes through
+//! the validation pipeline (see [`validation`](crate::blockchain::validation))
+//! and the consensus engine the chain spec selects; rejections are reported as a
+//! [`ChainError`] naming the failed rule and the block index.
+//!
+//! The chain writes through to a [`ChainStore`], one atomic batch per change,
+//! with an undo journal ([`BlockUndo`]) for every connected block, and
+//! [`Blockchain::open`] rebuilds it from the store at startup.
 
-use chrono::prelude::*;
-use crate::blockchain::block::Block;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::blockchain::archive::{ArchiveReader, ArchiveWriter, ImportSummary};
+use crate::blockchain::block::{Block, BLOCK_VERSION, MAX_BLOCK_SIZE, MAX_BLOCK_TRANSACTIONS};
+use crate::blockchain::emission;
+use crate::blockchain::genesis::create_genesis_block;
+use crate::blockchain::snapshot::StateSnapshot;
+use crate::blockchain::spec::ChainSpec;
+use crate::blockchain::state::{AccountProof, WorldState};
+use crate::blockchain::template::BlockTemplate;
+use crate::blockchain::tree::BlockTree;
+use crate::blockchain::undo::BlockUndo;
+use crate::blockchain::validation;
+use crate::consensus::finality::{CheckpointVote, FinalityCheckpoint};
+use crate::consensus::miner::CancelHandle;
+use crate::consensus::pos::StakingPool;
+use crate::consensus::{self, difficulty, timestamp, ConsensusEngine, ConsensusKind};
+use crate::storage::{AddressTx, ChainStore, TxLocation, WriteBatch};
+use crate::transaction::mempool::Mempool;
 use crate::transaction::tx::Transaction;
+use crate::transaction::validator::TransactionValidator;
+
+pub use crate::blockchain::error::{ChainError, ChainResult};
+
+/// A pruned chain moves its base only once this many blocks have accumulated
+/// beyond the prune depth, so the base snapshot is rewritten in steps rather
+/// than on every block.
+pub const PRUNE_INTERVAL: u64 = 128;
+
+/// One page of an address's transaction history, newest first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AddressHistory {
+    pub address: String,
+
+    /// Number of transactions in the whole history.
+    pub total: usize,
+
+    /// Number of (newer) transactions skipped before this page.
+    pub offset: usize,
+
+    pub transactions: Vec<AddressTx>,
+}
+
+/// Votes collected for a checkpoint block that is not final yet, with the
+/// validators of the state after it, rebuilt once per block rather than per vote.
+#[derive(Debug, Clone)]
+struct PendingCheckpoint {
+    checkpoint: FinalityCheckpoint,
+    validators: StakingPool,
+}
 
+/// The chain of a node. Cloning shares the underlying store.
 #[derive(Debug, Clone)]
 pub struct Blockchain {
+    pub spec: ChainSpec,
+    /// The active (best) chain, from genesis or, for a chain started from a
+    /// snapshot or pruned, from its base block ([`Blockchain::base_height`]).
     pub blocks: Vec<Block>,
-    pub difficulty: usize,
+    /// Balances, nonces and stakes after the active tip.
+    pub state: WorldState,
+    /// Every known block, active or on a competing branch.
+    pub tree: BlockTree,
+    store: ChainStore,
+    /// Consensus engine selected by the spec; seals and validates blocks.
+    engine: Arc<dyn ConsensusEngine>,
+    /// Searches on the current tip, cancelled when the tip changes.
+    tip_watchers: Vec<CancelHandle>,
+    /// Height of the tip a half-applied store was repaired back to when opened.
+    repaired_tip: Option<u64>,
+    /// State after the first active block, for a chain started from a snapshot or pruned.
+    base_state: Option<WorldState>,
+    /// Number of recent blocks whose bodies a pruned node keeps; `None` keeps all.
+    prune_depth: Option<u64>,
+    /// Whether the store keeps the address index.
+    address_index: bool,
+    /// Latest finalized checkpoint; blocks up to it are never disconnected.
+    finalized: Option<FinalityCheckpoint>,
+    /// Votes for checkpoint blocks that are not final yet, by height.
+    finality_votes: BTreeMap<u64, PendingCheckpoint>,
 }
 
 impl Blockchain {
-    /// Initializes a new blockchain instance with the Genesis block.
-    pub fn new(difficulty: usize) -> Self {
-        let genesis_block = Block::genesis(difficulty);
-        Blockchain {
+    /// Initializes a new, in-memory blockchain instance with the Genesis block of the given network.
+    pub fn new(spec: ChainSpec) -> Self {
+        Self::open(spec, ChainStore::memory()).expect("Genesis block must initialize an empty store")
+    }
+
+    /// Opens the chain persisted in `store`, initializing it with the genesis block if empty.
+    ///
+    /// Every change is committed in one atomic batch before the in-memory chain
+    /// changes, so memory never runs ahead of disk. A half-applied tip, left by an
+    /// interrupted non-atomic write, is rolled back to the last consistent block
+    /// (see [`Blockchain::repaired_tip`]).
+    ///
+    /// Fails if the store was initialized for a network with a different genesis block.
+    pub fn open(spec: ChainSpec, store: ChainStore) -> ChainResult<Self> {
+        let genesis_block = create_genesis_block(&spec);
+        match store.genesis_hash().map_err(ChainError::Storage)? {
+            None => Self::initialize(spec, genesis_block, store),
+            Some(hash) if hash != genesis_block.hash => Err(ChainError::Storage(format!(
+                "Store belongs to a different network (genesis {}, expected {}).",
+                hash, genesis_block.hash
+            ))),
+            Some(_) => Self::load(spec, genesis_block, store),
+        }
+    }
+
+    /// Writes the genesis block and its allocations (and genesis validator stakes)
+    /// into an empty store.
+    fn initialize(spec: ChainSpec, genesis_block: Block, store: ChainStore) -> ChainResult<Self> {
+        let engine = consensus::engine_for(&spec);
+        let mut state = WorldState::new();
+        state.start_journal();
+        apply_checked(&mut state, &genesis_block, None, &spec, engine.as_ref())?;
+        let journal = state.take_journal();
+
+        let chain = Blockchain {
+            engine,
+            tip_watchers: Vec::new(),
+            repaired_tip: None,
+            spec,
+            tree: BlockTree::new(&genesis_block),
             blocks: vec![genesis_block],
-            difficulty,
+            state,
+            store,
+            base_state: None,
+            prune_depth: None,
+            address_index: false,
+            finalized: None,
+            finality_votes: BTreeMap::new(),
+        };
+        let genesis = chain.latest_block();
+        let mut batch = WriteBatch::new();
+        chain.store.put_block(&mut batch, genesis).map_err(ChainError::Storage)?;
+        chain.store.set_genesis_hash(&mut batch, &genesis.hash);
+        chain.store.replace_state(&mut batch, &chain.state).map_err(ChainError::Storage)?;
+        let undo = BlockUndo::new(genesis, journal, 0);
+        chain.persist_switch(batch, &[], &BTreeSet::new(), &chain.blocks, &[undo], &chain.state)?;
+        Ok(chain)
+    }
+
+    /// Starts a chain from a state snapshot instead of replaying every block (fast sync).
+    ///
+    /// `trusted_hash` is the anchor block hash vouched for by the operator or a
+    /// checkpoint; the snapshot headers and every chunk are verified against it.
+    /// The store must be empty. The chain then continues with the blocks after the
+    /// anchor, received through [`Blockchain::accept_block`].
+    pub fn from_snapshot(
+        spec: ChainSpec,
+        store: ChainStore,
+        snapshot: StateSnapshot,
+        trusted_hash: &str,
+    ) -> ChainResult<Self> {
+        if store.genesis_hash().map_err(ChainError::Storage)?.is_some() {
+            return Err(ChainError::Storage("Cannot restore a snapshot into a non-empty store.".into()));
+        }
+        if snapshot.manifest.block_hash != trusted_hash {
+            return Err(ChainError::Other(format!(
+                "Snapshot is anchored at {}, expected {}.",
+                snapshot.manifest.block_hash, trusted_hash
+            )));
+        }
+        let state = snapshot.restore()?;
+
+        let genesis_block = create_genesis_block(&spec);
+        let engine = consensus::engine_for(&spec);
+        let tree = snapshot_tree(&snapshot, &genesis_block, engine.as_ref())?;
+        let anchor = tree
+            .get(trusted_hash)
+            .map(|entry| entry.block.clone())
+            .ok_or_else(|| ChainError::Other("Snapshot has no anchor header.".into()))?;
+
+        let chain = Blockchain {
+            spec,
+            blocks: vec![anchor],
+            engine,
+            tip_watchers: Vec::new(),
+            repaired_tip: None,
+            state: state.clone(),
+            tree,
+            store,
+            base_state: Some(state),
+            prune_depth: None,
+            address_index: false,
+            finalized: None,
+            finality_votes: BTreeMap::new(),
+        };
+        let anchor = chain.latest_block();
+        let mut batch = WriteBatch::new();
+        chain.store.set_genesis_hash(&mut batch, &genesis_block.hash);
+        chain.store.put_snapshot(&mut batch, &snapshot).map_err(ChainError::Storage)?;
+        chain.store.put_block(&mut batch, anchor).map_err(ChainError::Storage)?;
+        chain.store.replace_state(&mut batch, &chain.state).map_err(ChainError::Storage)?;
+        chain.persist_switch(batch, &[], &BTreeSet::new(), &chain.blocks, &[], &chain.state)?;
+        Ok(chain)
+    }
+
+    /// Rebuilds the active chain, the block tree and the world state from the store,
+    /// repairing a half-applied tip first (see [`Blockchain::recover`]).
+    fn load(spec: ChainSpec, genesis_block: Block, store: ChainStore) -> ChainResult<Self> {
+        let tip_hash = store
+            .tip_hash()
+            .map_err(ChainError::Storage)?
+            .ok_or_else(|| ChainError::Storage("Store is missing the tip pointer.".into()))?;
+
+        // A chain restored from a snapshot starts at the snapshot anchor.
+        let snapshot = match store.base_hash().map_err(ChainError::Storage)? {
+            Some(_) => Some(
+                store
+                    .snapshot()
+                    .map_err(ChainError::Storage)?
+                    .ok_or_else(|| ChainError::Storage("Store is missing its base snapshot.".into()))?,
+            ),
+            None => None,
+        };
+        let engine = consensus::engine_for(&spec);
+        let (base_hash, base_state, mut tree) = match &snapshot {
+            Some(snapshot) => (
+                snapshot.manifest.block_hash.clone(),
+                Some(snapshot.restore()?),
+                snapshot_tree(snapshot, &genesis_block, engine.as_ref())?,
+            ),
+            None => (genesis_block.hash.clone(), None, BlockTree::new(&genesis_block)),
+        };
+
+        // Walk the height index up to the tip, stopping at the first height whose
+        // block is missing or does not link to the one below it.
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut height = snapshot.as_ref().map_or(0, |s| s.manifest.height);
+        while let Some(hash) = store.hash_at(height).map_err(ChainError::Storage)? {
+            let block = match store.get_block(&hash).map_err(ChainError::Storage)? {
+                Some(block) => block,
+                None => break,
+            };
+            let links = match blocks.last() {
+                Some(parent) => block.index == height && block.previous_hash == parent.hash,
+                None => block.hash == base_hash,
+            };
+            if !links {
+                break;
+            }
+            let at_tip = block.hash == tip_hash;
+            blocks.push(block);
+            if at_tip {
+                break;
+            }
+            height += 1;
+        }
+        if blocks.is_empty() {
+            return Err(ChainError::Storage("Store is missing the base block.".into()));
+        }
+
+        let tip = blocks.last().expect("Walk keeps at least the base block");
+        let consistent = tip.hash == tip_hash
+            && store.state_tip_hash().map_err(ChainError::Storage)?.as_ref() == Some(&tip_hash)
+            && store.max_height().map_err(ChainError::Storage)? == Some(tip.index);
+        let (state, repaired_tip) = if consistent {
+            (store.load_state().map_err(ChainError::Storage)?, None)
+        } else {
+            let state = Self::recover(&store, &blocks, base_state.as_ref(), &spec, engine.as_ref())?;
+            (state, Some(tip.index))
+        };
+
+        // Parents before children, so every stored branch reattaches to the tree.
+        let mut stored = store.blocks().map_err(ChainError::Storage)?;
+        stored.sort_by_key(|b| b.index);
+        for block in stored {
+            if !tree.contains(&block.hash) && tree.contains(&block.previous_hash) {
+                tree.insert(block, engine.as_ref())?;
+            }
+        }
+
+        let prune_depth = store.prune_depth().map_err(ChainError::Storage)?;
+        let address_index = store.address_index().map_err(ChainError::Storage)?;
+        let finalized = store.finalized().map_err(ChainError::Storage)?;
+        Ok(Blockchain {
+            spec,
+            blocks,
+            state,
+            tree,
+            store,
+            engine,
+            tip_watchers: Vec::new(),
+            repaired_tip,
+            base_state,
+            prune_depth,
+            address_index,
+            finalized,
+            finality_votes: BTreeMap::new(),
+        })
+    }
+
+    /// Makes the store agree with `blocks`, the longest consistent prefix of the
+    /// stored active chain: height and transaction index entries above it are
+    /// dropped, the index of the kept blocks is rewritten, the state is rebuilt by
+    /// replaying them, and the tip is reset to the last of them. Returns the state.
+    fn recover(
+        store: &ChainStore,
+        blocks: &[Block],
+        base_state: Option<&WorldState>,
+        spec: &ChainSpec,
+        engine: &dyn ConsensusEngine,
+    ) -> ChainResult<WorldState> {
+        let tip = blocks.last().expect("Recovery needs at least the base block");
+        let state = match base_state {
+            Some(base_state) => Self::replay_state(base_state.clone(), blocks.first(), &blocks[1..], spec, engine)?,
+            None => Self::replay_state(WorldState::new(), None, blocks, spec, engine)?,
+        };
+
+        let mut batch = WriteBatch::new();
+        let address_index = store.address_index().map_err(ChainError::Storage)?;
+        let max_height = store.max_height().map_err(ChainError::Storage)?.unwrap_or(0);
+        for height in tip.index + 1..=max_height {
+            if let Some(hash) = store.hash_at(height).map_err(ChainError::Storage)? {
+                if let Some(block) = store.get_block(&hash).map_err(ChainError::Storage)? {
+                    store.disconnect(&mut batch, &block).map_err(ChainError::Storage)?;
+                    if address_index {
+                        store.unindex_addresses(&mut batch, &block);
+                    }
+                }
+            }
+            store.clear_height(&mut batch, height);
+        }
+        for block in blocks {
+            store.connect(&mut batch, block).map_err(ChainError::Storage)?;
+            if address_index {
+                store.index_addresses(&mut batch, block);
+            }
+        }
+        store.replace_state(&mut batch, &state).map_err(ChainError::Storage)?;
+        store.set_tip(&mut batch, &tip.hash);
+        store.commit(batch).map_err(ChainError::Storage)?;
+        Ok(state)
+    }
+
+    /// Returns the height the chain was rolled back to if [`Blockchain::open`] found
+    /// a half-applied tip and repaired it, or `None` if the store was consistent.
+    pub fn repaired_tip(&self) -> Option<u64> {
+        self.repaired_tip
+    }
+
+    /// Returns the store the chain persists to.
+    pub fn store(&self) -> &ChainStore {
+        &self.store
+    }
+
+    /// Returns the consensus engine the chain seals and validates blocks with.
+    pub fn engine(&self) -> &dyn ConsensusEngine {
+        self.engine.as_ref()
+    }
+
+    /// Returns a shared handle to the consensus engine, for sealing a block while
+    /// the chain is not borrowed.
+    pub fn shared_engine(&self) -> Arc<dyn ConsensusEngine> {
+        Arc::clone(&self.engine)
+    }
+
+    /// Cancels `cancel` as soon as the active tip changes, so a search on the
+    /// current tip stops once its block could no longer extend the chain.
+    pub fn cancel_on_tip_change(&mut self, cancel: CancelHandle) {
+        self.tip_watchers.retain(|watcher| !watcher.is_cancelled());
+        self.tip_watchers.push(cancel);
+    }
+
+    /// Cancels every search registered with [`Blockchain::cancel_on_tip_change`].
+    fn notify_tip_changed(&mut self) {
+        for watcher in self.tip_watchers.drain(..) {
+            watcher.cancel();
+        }
+    }
+
+    /// Height of the first block of the active chain: 0, or the anchor height for a
+    /// chain started from a snapshot.
+    pub fn base_height(&self) -> u64 {
+        self.blocks.first().map_or(0, |b| b.index)
+    }
+
+    /// Returns the active-chain block at `height`, if the chain holds it.
+    pub fn block_at(&self, height: u64) -> Option<&Block> {
+        let position = height.checked_sub(self.base_height())?;
+        self.blocks.get(usize::try_from(position).ok()?)
+    }
+
+    /// Returns the active-chain block at `height` with its body.
+    ///
+    /// Fails with [`ChainError::Pruned`] if the node no longer has the body (below
+    /// the base, or the body-less anchor of a snapshot).
+    pub fn block(&self, height: u64) -> ChainResult<&Block> {
+        let base = self.base_height();
+        let pruned = ChainError::Pruned { index: height, base };
+        if height < base {
+            return Err(pruned);
+        }
+        match self.block_at(height) {
+            Some(block) if block.transactions.is_empty() => Err(pruned),
+            Some(block) => Ok(block),
+            None => Err(ChainError::Other(format!(
+                "Height {} is above the chain tip {}.",
+                height,
+                self.latest_block().index
+            ))),
         }
     }
 
@@ -27,52 +426,1588 @@ impl Blockchain {
         self.blocks.last().expect("Blockchain must contain at least one block")
     }
 
-    /// Adds a new block to the chain using the given transactions.
-    pub fn add_block(&mut self, transactions: Vec<Transaction>) {
-        let previous_hash = self.latest_block().hash.clone();
-        let index = self.blocks.len() as u64;
-        let timestamp = Utc::now().to_rfc3339();
+    /// Builds a template for the next block, for mining outside the node.
+    ///
+    /// Candidates are taken in order (typically the mempool queue); a transaction that
+    /// fails validation or does not apply on top of the current state plus the ones
+    /// already selected is skipped, as is one that would take the block past
+    /// [`MAX_BLOCK_SIZE`]. Selection stops at [`MAX_BLOCK_TRANSACTIONS`]. The coinbase
+    /// pays the subsidy plus the selected fees to `miner_address`.
+    ///
+    /// Under proof of stake the block time decides which proposers missed their rounds,
+    /// and so the state root; the template then pins it (`min_time == max_time`).
+    pub fn block_template(&self, candidates: &[Transaction], miner_address: &str) -> ChainResult<BlockTemplate> {
+        let parent = self.latest_block();
+        let height = parent.index + 1;
+        let bits = self.expected_bits(&parent.hash)?;
+        let target = difficulty::bits_to_target(bits)
+            .ok_or_else(|| ChainError::Other(format!("Invalid target bits {:#010x}.", bits)))?;
 
-        let mut new_block = Block::new(
-            index,
-            timestamp,
-            previous_hash,
-            transactions,
-            self.difficulty,
+        let mut state = self.state.clone();
+        state
+            .release_unbonded(height)
+            .map_err(|reason| ChainError::StateRejected { index: height, reason })?;
+        // The coinbase amount is fixed-width, so an empty one sizes the final block.
+        let created = Utc::now().to_rfc3339();
+        let empty_coinbase = Transaction::coinbase(height, miner_address, 0, &created);
+        let mut size = Block::new(height, parent.hash.clone(), vec![empty_coinbase], bits).encoded_size();
+        let mut transactions = Vec::new();
+        let mut fees: u64 = 0;
+        for tx in candidates {
+            if transactions.len() + 1 >= MAX_BLOCK_TRANSACTIONS {
+                break;
+            }
+            let block_size = size.saturating_add(tx.encoded_size());
+            if block_size > MAX_BLOCK_SIZE {
+                continue;
+            }
+            let fee_total = match fees.checked_add(tx.fee) {
+                Some(total) => total,
+                None => continue,
+            };
+            let signed = TransactionValidator::validate(tx, self.spec.network_id).is_ok();
+            if signed && state.apply_transaction(tx, height, &self.spec).is_ok() {
+                fees = fee_total;
+                size = block_size;
+                transactions.push(tx.clone());
+            }
+        }
+
+        let now = Utc::now().timestamp();
+        let min_time = self.median_time_past(&parent.hash).saturating_add(1);
+        let coinbase_value = emission::block_subsidy(&self.spec, height).saturating_add(fees);
+        let coinbase = Transaction::coinbase(height, miner_address, coinbase_value, &created);
+        let mut block = Block::new(
+            height,
+            parent.hash.clone(),
+            std::iter::once(coinbase.clone()).chain(transactions.iter().cloned()).collect(),
+            bits,
         );
+        // Missed proposals depend on the block time; the state root assumes the template's.
+        block.timestamp = now.max(min_time);
+        let (min_time, max_time) = if self.engine.kind() == ConsensusKind::Pos {
+            (block.timestamp, block.timestamp)
+        } else {
+            (min_time, now.saturating_add(self.spec.consensus.max_future_drift).max(min_time))
+        };
+        let missed = self.engine.missed_proposers(&block, parent, &self.staking_pool());
+        let mut post_state = self.state.clone();
+        post_state
+            .apply_block(&block, &self.spec)
+            .map_err(|reason| ChainError::StateRejected { index: height, reason })?;
+        post_state.record_proposal(&block, &missed, &self.spec);
+        let merkle_root = block.merkle_root;
 
-        new_block.mine();
-        self.blocks.push(new_block);
+        Ok(BlockTemplate {
+            template_id: merkle_root.clone(),
+            version: BLOCK_VERSION,
+            height,
+            previous_hash: parent.hash.clone(),
+            bits,
+            target: hex::encode(target),
+            timestamp: block.timestamp,
+            min_time,
+            max_time,
+            merkle_root,
+            state_root: post_state.state_root(),
+            coinbase_value,
+            coinbase,
+            transactions,
+        })
     }
 
-    /// Validates the entire chain's integrity by checking hashes and links.
-    pub fn is_valid(&self) -> bool {
-        for i in 1..self.blocks.len() {
-            let current = &self.blocks[i];
-            let previous = &self.blocks[i - 1];
+    /// Accepts a block received from a peer (or an external miner).
+    ///
+    /// The block is stored in the block tree. If it extends the active tip it is
+    /// connected directly; if it makes a competing branch heavier than the active
+    /// chain, the chain reorganizes onto that branch. Transactions from blocks that
+    /// drop out of the active chain are returned to the mempool, and transactions
+    /// newly confirmed are removed from it.
+    ///
+    /// Blocks that conflict with a spec checkpoint, fork off the active chain below
+    /// a checkpoint it already passed, or do not descend from the finalized block
+    /// are rejected whatever their work.
+    ///
+    /// Returns `Ok(true)` if the active tip changed, in which case every search
+    /// registered with [`Blockchain::cancel_on_tip_change`] is cancelled.
+    pub fn accept_block(&mut self, block: Block, mempool: &mut Mempool) -> ChainResult<bool> {
+        if self.tree.contains(&block.hash) {
+            return Err(ChainError::DuplicateBlock {
+                index: block.index,
+                hash: block.hash,
+            });
+        }
+        if let Some((checkpoint, _)) = self.spec.last_checkpoint(self.latest_block().index) {
+            if block.index <= checkpoint {
+                return Err(ChainError::ForkBelowCheckpoint {
+                    index: block.index,
+                    checkpoint,
+                });
+            }
+        }
+        if let Some(finalized) = self.finalized_height() {
+            if block.index <= finalized {
+                return Err(ChainError::BelowFinalized {
+                    index: block.index,
+                    finalized,
+                });
+            }
+        }
+        self.validate_block(&block)?;
+
+        let hash = block.hash.clone();
+        let entry = self.tree.insert(block, self.engine.as_ref())?;
+        let total_work = entry.total_work;
+        let mut batch = WriteBatch::new();
+        let queued = self.store.put_block(&mut batch, &entry.block);
+        if let Err(e) = queued {
+            self.tree.remove_branch(&hash);
+            return Err(ChainError::Storage(e));
+        }
+        let tip_work = self
+            .tree
+            .get(&self.latest_block().hash)
+            .map(|e| e.total_work)
+            .unwrap_or(0);
+
+        if total_work <= tip_work {
+            // Side branch with no more work than the active chain: keep it for later.
+            if let Err(e) = self.store.commit(batch) {
+                self.tree.remove_branch(&hash);
+                return Err(ChainError::Storage(e));
+            }
+            return Ok(false);
+        }
+
+        if let Err(e) = self.reorganize_to(&hash, mempool, batch) {
+            // Nothing of the block was persisted; a no-op if the reorganization already dropped it.
+            self.tree.remove_branch(&hash);
+            return Err(e);
+        }
+        self.notify_tip_changed();
+        Ok(true)
+    }
+
+    /// Runs every validation stage that does not need the world state:
+    /// header, parent and height, checkpoints and finality, consensus seal, timestamp, merkle
+    /// root and transactions.
+    ///
+    /// Transaction signatures are not re-checked for an ancestor of the spec's
+    /// assume-valid block, once that block is in the tree.
+    pub fn validate_block(&self, block: &Block) -> ChainResult<()> {
+        self.validate_block_at(block, Utc::now().timestamp())
+    }
+
+    /// [`Blockchain::validate_block`] with an explicit local clock for the future-drift rule.
+    fn validate_block_at(&self, block: &Block, now: i64) -> ChainResult<()> {
+        validation::check_header(block)?;
+
+        let parent = self
+            .tree
+            .get(&block.previous_hash)
+            .ok_or_else(|| ChainError::UnknownParent {
+                index: block.index,
+                parent: block.previous_hash.clone(),
+            })?;
+        if block.index != parent.height + 1 {
+            return Err(ChainError::BadHeight {
+                index: block.index,
+                expected: parent.height + 1,
+            });
+        }
+        self.check_checkpoints(block)?;
+        self.check_finality(block)?;
+
+        validation::check_consensus(self.engine(), block, self.expected_bits(&block.previous_hash)?)?;
+        timestamp::check_block_time(
+            block.timestamp,
+            self.median_time_past(&block.previous_hash),
+            now,
+            self.spec.consensus.max_future_drift,
+        )
+        .map_err(|reason| ChainError::BadTimestamp {
+            index: block.index,
+            reason,
+        })?;
+
+        validation::check_merkle_root(block)?;
+        validation::check_transactions(block, self.spec.network_id, !self.skips_signatures(block))
+    }
+
+    /// Returns true if the transaction signatures of `block` are assumed valid:
+    /// the block is an ancestor of the spec's assume-valid block.
+    ///
+    /// The assume-valid block must already be in the tree, so a block received
+    /// before it (as in initial sync) is checked in full; re-validating a stored
+    /// chain skips the checks.
+    fn skips_signatures(&self, block: &Block) -> bool {
+        match &self.spec.assume_valid {
+            Some(av) if block.index < av.height => self
+                .tree
+                .ancestor(&av.hash, block.index)
+                .is_some_and(|ancestor| ancestor.block.hash == block.hash),
+            _ => false,
+        }
+    }
+
+    /// Rejects a block that conflicts with a checkpoint: the one at its own height,
+    /// or the last one below it, which the block's ancestor at that height must be.
+    fn check_checkpoints(&self, block: &Block) -> ChainResult<()> {
+        let mismatch = |height: u64, expected: &str, found: &str| ChainError::CheckpointMismatch {
+            index: block.index,
+            height,
+            expected: expected.to_string(),
+            found: found.to_string(),
+        };
+        if let Some(expected) = self.spec.checkpoint_at(block.index) {
+            if block.hash != expected {
+                return Err(mismatch(block.index, expected, &block.hash));
+            }
+        }
+
+        let (height, expected) = match block.index.checked_sub(1).and_then(|h| self.spec.last_checkpoint(h)) {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(()),
+        };
+        // Ancestors below a snapshot base may be unknown; nothing to compare then.
+        match self.ancestor_hash(block, height) {
+            Some(found) if found != expected => Err(mismatch(height, expected, found)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Rejects a block that is not the finalized block or a descendant of it.
+    fn check_finality(&self, block: &Block) -> ChainResult<()> {
+        let finalized = match &self.finalized {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(()),
+        };
+        // Only the active chain is compatible at or below the finalized height.
+        let conflicts = if block.index <= finalized.height {
+            self.block_at(block.index).is_none_or(|b| b.hash != block.hash)
+        } else {
+            self.ancestor_hash(block, finalized.height)
+                .is_some_and(|found| found != finalized.block_hash)
+        };
+        if conflicts {
+            return Err(ChainError::BelowFinalized {
+                index: block.index,
+                finalized: finalized.height,
+            });
+        }
+        Ok(())
+    }
+
+    /// Hash of the ancestor of `block` at `height` (below the block), taken from the
+    /// active chain when the block extends it and from the block tree otherwise.
+    fn ancestor_hash(&self, block: &Block, height: u64) -> Option<&str> {
+        let parent_is_active = self
+            .block_at(block.index.checked_sub(1)?)
+            .is_some_and(|parent| parent.hash == block.previous_hash);
+        if parent_is_active {
+            self.block_at(height).map(|b| b.hash.as_str())
+        } else {
+            self.tree
+                .ancestor(&block.previous_hash, height)
+                .map(|entry| entry.block.hash.as_str())
+        }
+    }
+
+    /// Latest finalized checkpoint, with the votes that finalized it.
+    pub fn finalized(&self) -> Option<&FinalityCheckpoint> {
+        self.finalized.as_ref()
+    }
+
+    /// Height of the latest finalized block.
+    pub fn finalized_height(&self) -> Option<u64> {
+        self.finalized.as_ref().map(|checkpoint| checkpoint.height)
+    }
+
+    /// Records a validator's vote for a finality checkpoint.
+    ///
+    /// The vote must be signed by a validator staking in the state after the
+    /// active-chain block at a finality height above the finalized one. Once the
+    /// block's votes hold two thirds of that stake, it becomes final. Returns
+    /// `Ok(true)` if this vote finalized it; a repeated vote changes nothing.
+    /// A finalized block is never disconnected.
+    pub fn submit_finality_vote(&mut self, vote: CheckpointVote) -> ChainResult<bool> {
+        let reject = |reason: String| ChainError::BadFinalityVote {
+            index: vote.height,
+            reason,
+        };
+        if !self.engine.has_finality() {
+            return Err(reject("the network has no finality checkpoints".into()));
+        }
+        self.check_finality_target(vote.height, &vote.block_hash)?;
+        vote.verify(self.spec.network_id).map_err(reject)?;
+
+        let known = self
+            .finality_votes
+            .get(&vote.height)
+            .is_some_and(|pending| pending.checkpoint.block_hash == vote.block_hash);
+        if !known {
+            // New height, or earlier votes are for a block a reorganization has since replaced.
+            let validators = self.validators_at(vote.height)?;
+            let checkpoint = FinalityCheckpoint::new(vote.height, &vote.block_hash);
+            self.finality_votes.insert(vote.height, PendingCheckpoint { checkpoint, validators });
+        }
+        let pending = self
+            .finality_votes
+            .get_mut(&vote.height)
+            .expect("Votes for the height were just set up");
+        if !pending.validators.is_validator(&vote.validator) {
+            return Err(reject(format!("{} is not a validator", vote.validator)));
+        }
+        if pending.checkpoint.has_vote_from(&vote.validator) {
+            return Ok(false);
+        }
+        pending.checkpoint.votes.push(vote);
+        if !pending.checkpoint.is_final(&pending.validators) {
+            return Ok(false);
+        }
+        let checkpoint = pending.checkpoint.clone();
+        self.finalize(checkpoint)?;
+        Ok(true)
+    }
+
+    /// Accepts a checkpoint finalized elsewhere (e.g. received from a peer), after
+    /// verifying every vote and the two-thirds stake threshold against the
+    /// validators of the state after the checkpoint block.
+    ///
+    /// Returns `Ok(false)` if the chain is already finalized at or above its height.
+    pub fn accept_finality_checkpoint(&mut self, checkpoint: FinalityCheckpoint) -> ChainResult<bool> {
+        if self.finalized_height().is_some_and(|height| height >= checkpoint.height) {
+            return Ok(false);
+        }
+        if !self.engine.has_finality() {
+            return Err(ChainError::BadFinalityVote {
+                index: checkpoint.height,
+                reason: "the network has no finality checkpoints".into(),
+            });
+        }
+        self.check_finality_target(checkpoint.height, &checkpoint.block_hash)?;
+        let pool = self.validators_at(checkpoint.height)?;
+        checkpoint
+            .verify(self.spec.network_id, &pool)
+            .map_err(|reason| ChainError::BadFinalityVote {
+                index: checkpoint.height,
+                reason,
+            })?;
+        self.finalize(checkpoint)?;
+        Ok(true)
+    }
+
+    /// Checks that `block_hash` is the active-chain block at `height`, a finality
+    /// height above the finalized one.
+    fn check_finality_target(&self, height: u64, block_hash: &str) -> ChainResult<()> {
+        let reject = |reason: String| ChainError::BadFinalityVote { index: height, reason };
+        if !self.spec.is_finality_height(height) {
+            return Err(reject("not a finality checkpoint height".into()));
+        }
+        if let Some(finalized) = self.finalized_height() {
+            if height <= finalized {
+                return Err(reject(format!("the chain is already finalized at height {}", finalized)));
+            }
+        }
+        match self.block_at(height) {
+            Some(block) if block.hash == block_hash => Ok(()),
+            _ => Err(reject(format!("block {} is not on the active chain", block_hash))),
+        }
+    }
+
+    /// Staking pool of the state after the active-chain block at `height`.
+    fn validators_at(&self, height: u64) -> ChainResult<StakingPool> {
+        self.state_at(height).map(|state| StakingPool::from_state(&state))
+    }
+
+    /// Persists `checkpoint` as finalized and drops the votes at or below it.
+    fn finalize(&mut self, checkpoint: FinalityCheckpoint) -> ChainResult<()> {
+        let mut batch = WriteBatch::new();
+        self.store
+            .set_finalized(&mut batch, &checkpoint)
+            .map_err(ChainError::Storage)?;
+        self.store.commit(batch).map_err(ChainError::Storage)?;
+        self.finality_votes = self.finality_votes.split_off(&(checkpoint.height + 1));
+        self.finalized = Some(checkpoint);
+        Ok(())
+    }
+
+    /// Switches the active chain to the branch ending at `new_tip`.
+    ///
+    /// The state is rewound to the fork point through the undo journals of the
+    /// disconnected blocks, then the new branch is applied; if any block of the
+    /// branch fails to apply, that block and its descendants are dropped from the
+    /// tree and the active chain is left unchanged.
+    ///
+    /// The switch is committed together with the writes already queued in `batch`.
+    fn reorganize_to(&mut self, new_tip: &str, mempool: &mut Mempool, batch: WriteBatch) -> ChainResult<()> {
+        let branch: Vec<Block> = self
+            .tree
+            .branch_until(new_tip, |entry| {
+                self.block_at(entry.height)
+                    .is_some_and(|b| b.hash == entry.block.hash)
+            })
+            .into_iter()
+            .map(|entry| entry.block.clone())
+            .collect();
+
+        let fork_height = branch
+            .first()
+            .map(|b| b.index)
+            .ok_or_else(|| ChainError::Other("Reorganization target is already the active tip.".into()))?;
+        if fork_height <= self.base_height() {
+            return Err(ChainError::Other(format!(
+                "Cannot reorganize below the chain base at height {}.",
+                self.base_height()
+            )));
+        }
+        if let Some(finalized) = self.finalized_height().filter(|height| fork_height <= *height) {
+            return Err(ChainError::BelowFinalized {
+                index: fork_height,
+                finalized,
+            });
+        }
+        let fork = (fork_height - self.base_height()) as usize;
 
-            // Validate hash
-            if current.hash != current.calculate_hash() {
-                eprintln!("Invalid hash at block {}", i);
-                return false;
+        let (mut next_state, rewound) = self.rewind_to(fork)?;
+        let mut undos = Vec::with_capacity(branch.len());
+        let mut parent = &self.blocks[fork - 1];
+        for block in &branch {
+            next_state.start_journal();
+            if let Err(e) = apply_checked(&mut next_state, block, Some(parent), &self.spec, self.engine()) {
+                // The branch is invalid from this block on; forget it so it is never retried.
+                let mut removal = WriteBatch::new();
+                for removed in self.tree.remove_branch(&block.hash) {
+                    self.store.delete_block(&mut removal, &removed);
+                }
+                self.store.commit(removal).map_err(ChainError::Storage)?;
+                return Err(e);
             }
+            undos.push(BlockUndo::new(block, next_state.take_journal(), next_state.burned()));
+            parent = block;
+        }
+
+        let disconnected: Vec<Block> = self.blocks[fork..].to_vec();
+        self.persist_switch(batch, &disconnected, &rewound, &branch, &undos, &next_state)?;
+
+        self.blocks.truncate(fork);
+        self.blocks.extend(branch);
+        self.state = next_state;
+
+        let connected = &self.blocks[fork..];
+        let orphaned: Vec<Transaction> = disconnected
+            .iter()
+            .flat_map(|b| b.transactions.iter())
+            .filter(|tx| !tx.is_coinbase())
+            .filter(|tx| !connected.iter().any(|b| b.transactions.iter().any(|c| c.hash == tx.hash)))
+            .cloned()
+            .collect();
+
+        for block in connected {
+            mempool.remove_included(&block.transactions);
+        }
+        mempool.reinsert(orphaned);
+        Ok(())
+    }
+
+    /// Commits a change of the active chain in one atomic batch, together with the
+    /// writes already queued in `batch`: heights and transaction index for the
+    /// disconnected and connected blocks, the undo journals of the connected ones,
+    /// the accounts the disconnected blocks changed (`rewound`) and those the
+    /// connected blocks changed, as found in `state`, the state at the new tip,
+    /// and the tip, which is the last connected block.
+    ///
+    /// Called before `self.blocks` and `self.state` change, so a failed commit leaves
+    /// both memory and disk at the old tip.
+    fn persist_switch(
+        &self,
+        mut batch: WriteBatch,
+        disconnected: &[Block],
+        rewound: &BTreeSet<String>,
+        connected: &[Block],
+        undos: &[BlockUndo],
+        state: &WorldState,
+    ) -> ChainResult<()> {
+        let store = &self.store;
+        let tip = connected
+            .last()
+            .ok_or_else(|| ChainError::Other("A chain switch must connect at least one block.".into()))?;
+        for block in disconnected.iter().rev() {
+            store.disconnect(&mut batch, block).map_err(ChainError::Storage)?;
+            if self.address_index {
+                store.unindex_addresses(&mut batch, block);
+            }
+        }
+        for block in connected {
+            store.connect(&mut batch, block).map_err(ChainError::Storage)?;
+            if self.address_index {
+                store.index_addresses(&mut batch, block);
+            }
+        }
+        for undo in undos {
+            store.put_undo(&mut batch, undo).map_err(ChainError::Storage)?;
+        }
+        let addresses: BTreeSet<&str> = rewound
+            .iter()
+            .map(String::as_str)
+            .chain(undos.iter().flat_map(BlockUndo::addresses))
+            .collect();
+        store
+            .write_accounts(&mut batch, state, addresses)
+            .map_err(ChainError::Storage)?;
+        store.set_tip(&mut batch, &tip.hash);
+        store.commit(batch).map_err(ChainError::Storage)
+    }
+
+    /// Returns the compact target required for a block whose parent is `parent_hash`.
+    ///
+    /// Computed from the parent's own branch, so blocks on competing forks are
+    /// checked against their fork's difficulty history.
+    pub fn expected_bits(&self, parent_hash: &str) -> ChainResult<u32> {
+        let parent = self
+            .tree
+            .get(parent_hash)
+            .ok_or_else(|| ChainError::Other(format!("Unknown parent block {}.", parent_hash)))?;
+        let height = parent.height + 1;
+
+        if !difficulty::is_retarget_height(&self.spec.consensus, height) {
+            return Ok(parent.block.bits);
+        }
+
+        let start_height = height.saturating_sub(self.spec.consensus.retarget_interval);
+        let start = self
+            .tree
+            .ancestor(parent_hash, start_height)
+            .ok_or_else(|| {
+                ChainError::Other(format!("Missing ancestor at height {} of {}.", start_height, parent_hash))
+            })?;
+
+        Ok(difficulty::next_bits(
+            &self.spec.consensus,
+            height,
+            parent.block.bits,
+            parent.block.timestamp,
+            start.block.timestamp,
+        ))
+    }
+
+    /// Returns the median timestamp of `parent_hash` and its ancestors
+    /// (up to `median_time_span` blocks), following the parent's own branch.
+    pub fn median_time_past(&self, parent_hash: &str) -> i64 {
+        let span = self.spec.consensus.median_time_span;
+        let mut times = Vec::with_capacity(span);
+        let mut cursor = self.tree.get(parent_hash);
+        while let Some(entry) = cursor {
+            times.push(entry.block.timestamp);
+            if times.len() == span {
+                break;
+            }
+            cursor = self.tree.get(&entry.block.previous_hash);
+        }
+        times.reverse();
+        timestamp::median_time_past(&times)
+    }
+
+    /// Returns the compact target the next block on the active chain must meet.
+    pub fn next_bits(&self) -> u32 {
+        self.expected_bits(&self.latest_block().hash)
+            .expect("Active tip is always in the block tree")
+    }
+
+    /// Applies the given blocks in order on top of `state`, the state `parent`
+    /// left (`None` before genesis), checking each state root.
+    fn replay_state<'a>(
+        mut state: WorldState,
+        mut parent: Option<&'a Block>,
+        blocks: &'a [Block],
+        spec: &ChainSpec,
+        engine: &dyn ConsensusEngine,
+    ) -> ChainResult<WorldState> {
+        for block in blocks {
+            apply_checked(&mut state, block, parent, spec, engine)?;
+            parent = Some(block);
+        }
+        Ok(state)
+    }
 
-            // Validate previous hash linkage
-            if current.previous_hash != previous.hash {
-                eprintln!("Invalid previous hash link at block {}", i);
-                return false;
+    /// Rebuilds the state after the first `end` active blocks, from genesis or from
+    /// the snapshot the chain started at.
+    fn replay_to(&self, end: usize) -> ChainResult<WorldState> {
+        match &self.base_state {
+            Some(base_state) => {
+                Self::replay_state(base_state.clone(), self.blocks.first(), &self.blocks[1..end], &self.spec, self.engine())
             }
+            None => Self::replay_state(WorldState::new(), None, &self.blocks[..end], &self.spec, self.engine()),
+        }
+    }
+
+    /// Returns the state after the first `end` active blocks by reverting the blocks
+    /// above them with their undo journals, starting from the current state, and
+    /// the addresses whose accounts the reverted blocks changed.
+    ///
+    /// Falls back to [`Blockchain::replay_to`] if a journal is missing (a store
+    /// written before journals existed) or the result does not match the state
+    /// root of the new tip; the changed addresses are then found by comparing
+    /// the whole states.
+    fn rewind_to(&self, end: usize) -> ChainResult<(WorldState, BTreeSet<String>)> {
+        let mut state = self.state.clone();
+        let mut rewound = BTreeSet::new();
+        if end == self.blocks.len() {
+            return Ok((state, rewound));
+        }
+        let replay = || {
+            let state = self.replay_to(end)?;
+            let changed = state.changed_addresses(&self.state).into_iter().map(str::to_string).collect();
+            Ok((state, changed))
+        };
+        for block in self.blocks[end..].iter().rev() {
+            match self.store.get_undo(&block.hash).map_err(ChainError::Storage)? {
+                Some(undo) if undo.block_hash == block.hash => {
+                    undo.revert(&mut state);
+                    rewound.extend(undo.addresses().map(str::to_string));
+                }
+                _ => return replay(),
+            }
+        }
+        match end.checked_sub(1).map(|last| &self.blocks[last]) {
+            Some(tip) if state.state_root() == tip.state_root => Ok((state, rewound)),
+            _ => replay(),
         }
-        true
     }
 
-    /// Returns the number of blocks in the chain.
+    /// Disconnects the active tip, restoring the state before it from its undo
+    /// journal, and returns the block.
+    ///
+    /// The block's transactions (coinbase aside) go back to the mempool. The block
+    /// itself stays in the block tree and the store as a side branch. The chain
+    /// base (genesis, or the snapshot anchor of a fast-synced or pruned chain)
+    /// and finalized blocks cannot be disconnected.
+    pub fn disconnect_block(&mut self, mempool: &mut Mempool) -> ChainResult<Block> {
+        if self.blocks.len() < 2 {
+            return Err(ChainError::Other(format!(
+                "Cannot disconnect the chain base at height {}.",
+                self.base_height()
+            )));
+        }
+        let index = self.latest_block().index;
+        if let Some(finalized) = self.finalized_height().filter(|height| index <= *height) {
+            return Err(ChainError::BelowFinalized { index, finalized });
+        }
+        let end = self.blocks.len() - 1;
+        let (state, rewound) = self.rewind_to(end)?;
+        let tip = self.latest_block();
+        let parent = &self.blocks[end - 1];
+
+        let mut batch = WriteBatch::new();
+        self.store.disconnect(&mut batch, tip).map_err(ChainError::Storage)?;
+        if self.address_index {
+            self.store.unindex_addresses(&mut batch, tip);
+        }
+        self.store
+            .write_accounts(&mut batch, &state, rewound.iter().map(String::as_str))
+            .map_err(ChainError::Storage)?;
+        self.store.set_tip(&mut batch, &parent.hash);
+        self.store.commit(batch).map_err(ChainError::Storage)?;
+
+        let block = self.blocks.pop().expect("Chain holds more than the base block");
+        self.state = state;
+        self.notify_tip_changed();
+        mempool.reinsert(block.transactions.iter().filter(|tx| !tx.is_coinbase()).cloned().collect());
+        Ok(block)
+    }
+
+    /// Disconnects blocks from the tip until the block at `height` is the tip, and
+    /// returns the disconnected blocks, tip first.
+    ///
+    /// Each block is committed on its own, so an interrupted rollback leaves a
+    /// consistent chain somewhere between the old tip and `height`.
+    pub fn rollback_to_height(&mut self, height: u64, mempool: &mut Mempool) -> ChainResult<Vec<Block>> {
+        let tip = self.latest_block().index;
+        if height > tip {
+            return Err(ChainError::Other(format!(
+                "Height {} is above the chain tip {}.",
+                height, tip
+            )));
+        }
+        if height < self.base_height() {
+            return Err(ChainError::Pruned {
+                index: height,
+                base: self.base_height(),
+            });
+        }
+        if let Some(finalized) = self.finalized_height().filter(|finalized| height < *finalized) {
+            return Err(ChainError::BelowFinalized { index: height, finalized });
+        }
+
+        let mut disconnected = Vec::with_capacity((tip - height) as usize);
+        while self.latest_block().index > height {
+            disconnected.push(self.disconnect_block(mempool)?);
+        }
+        Ok(disconnected)
+    }
+
+    /// Returns the state after the active-chain block at `height`, rewound from the
+    /// current state through the undo journals.
+    fn state_at(&self, height: u64) -> ChainResult<WorldState> {
+        if height < self.base_height() {
+            return Err(ChainError::Pruned {
+                index: height,
+                base: self.base_height(),
+            });
+        }
+        if self.block_at(height).is_none() {
+            return Err(ChainError::Other(format!("Height {} is not on the active chain.", height)));
+        }
+        self.rewind_to((height - self.base_height()) as usize + 1)
+            .map(|(state, _)| state)
+    }
+
+    /// Exports the state after the active-chain block at `height` as a [`StateSnapshot`].
+    ///
+    /// The manifest carries the block's header and enough ancestor headers for the
+    /// retargeting and median-time-past rules to validate the blocks that follow.
+    pub fn snapshot(&self, height: u64) -> ChainResult<StateSnapshot> {
+        self.snapshot_with_state(height).map(|(snapshot, _)| snapshot)
+    }
+
+    /// [`Blockchain::snapshot`], also returning the exported state.
+    fn snapshot_with_state(&self, height: u64) -> ChainResult<(StateSnapshot, WorldState)> {
+        let state = self.state_at(height)?;
+        let anchor = self
+            .block_at(height)
+            .ok_or_else(|| ChainError::Other(format!("Height {} is not on the active chain.", height)))?;
+
+        let context = self
+            .spec
+            .consensus
+            .retarget_interval
+            .max(self.spec.consensus.median_time_span as u64);
+        let mut headers = Vec::new();
+        let mut cursor = self.tree.get(&anchor.hash);
+        while let Some(entry) = cursor {
+            headers.push(entry.block.header());
+            if headers.len() as u64 > context {
+                break;
+            }
+            cursor = self.tree.get(&entry.block.previous_hash);
+        }
+        headers.reverse();
+
+        let snapshot = StateSnapshot::create(&state, headers)?;
+        Ok((snapshot, state))
+    }
+
+    /// Number of recent blocks whose bodies are kept; `None` if the node is not pruned.
+    pub fn prune_depth(&self) -> Option<u64> {
+        self.prune_depth
+    }
+
+    /// Turns pruning on (keeping the bodies of the last `depth` blocks) or off, and
+    /// prunes right away if the chain is already long enough.
+    ///
+    /// The setting is stored, so the chain stays pruned when reopened. Turning
+    /// pruning off does not bring deleted bodies back. Reorganizations deeper than
+    /// `depth` blocks become impossible.
+    pub fn set_prune_depth(&mut self, depth: Option<u64>) -> ChainResult<()> {
+        if depth == Some(0) {
+            return Err(ChainError::Other("Prune depth must be at least one block.".into()));
+        }
+        let mut batch = WriteBatch::new();
+        self.store.set_prune_depth(&mut batch, depth);
+        self.store.commit(batch).map_err(ChainError::Storage)?;
+        self.prune_depth = depth;
+        self.prune()
+    }
+
+    /// Moves the base up to `prune_depth` blocks below the tip once at least
+    /// [`PRUNE_INTERVAL`] blocks can be pruned.
+    ///
+    /// Connecting a block does not prune; the node calls this after it connects
+    /// blocks. A failure leaves the chain as it was, and the next call retries.
+    pub fn prune(&mut self) -> ChainResult<()> {
+        let depth = match self.prune_depth {
+            Some(depth) => depth,
+            None => return Ok(()),
+        };
+        let target = self.latest_block().index.saturating_sub(depth);
+        if target < self.base_height().saturating_add(PRUNE_INTERVAL) {
+            return Ok(());
+        }
+        self.prune_to(target)
+    }
+
+    /// Makes the active block at `height` the new base: its post-state is stored as
+    /// the base snapshot, bodies of older active blocks are deleted (headers and the
+    /// height index stay), and branches forking below it are dropped.
+    fn prune_to(&mut self, height: u64) -> ChainResult<()> {
+        let (snapshot, state) = self.snapshot_with_state(height)?;
+        let position = (height - self.base_height()) as usize;
+        let base_hash = self.blocks[position].hash.clone();
+
+        let mut batch = WriteBatch::new();
+        self.store.put_snapshot(&mut batch, &snapshot).map_err(ChainError::Storage)?;
+        for block in &self.blocks[..position] {
+            self.store.delete_body(&mut batch, &block.hash);
+        }
+        for removed in self.tree.prune_below(&base_hash) {
+            self.store.delete_block(&mut batch, &removed);
+        }
+        self.store.commit(batch).map_err(ChainError::Storage)?;
+
+        self.blocks.drain(..position);
+        self.base_state = Some(state);
+        Ok(())
+    }
+
+    /// Whether the store keeps the address index.
+    pub fn address_index(&self) -> bool {
+        self.address_index
+    }
+
+    /// Turns the address index on or off. Turning it on indexes every active
+    /// block whose body the node still has, in one batch.
+    pub fn set_address_index(&mut self, enabled: bool) -> ChainResult<()> {
+        if enabled == self.address_index {
+            return Ok(());
+        }
+        let mut batch = WriteBatch::new();
+        self.store
+            .set_address_index(&mut batch, enabled)
+            .map_err(ChainError::Storage)?;
+        if enabled {
+            for block in &self.blocks {
+                self.store.index_addresses(&mut batch, block);
+            }
+        }
+        self.store.commit(batch).map_err(ChainError::Storage)?;
+        self.address_index = enabled;
+        Ok(())
+    }
+
+    /// Looks up an active-chain transaction by hash, with where it was included.
+    ///
+    /// Fails with [`ChainError::Pruned`] if the including block's body was pruned.
+    pub fn find_transaction(&self, tx_hash: &str) -> ChainResult<Option<(TxLocation, Transaction)>> {
+        let location = match self.store.tx_location(tx_hash).map_err(ChainError::Storage)? {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        let tx = self
+            .block(location.height)?
+            .transactions
+            .get(location.position as usize)
+            .filter(|tx| tx.hash == tx_hash)
+            .cloned()
+            .ok_or_else(|| {
+                ChainError::Storage(format!("Transaction index is stale for {}.", tx_hash))
+            })?;
+        Ok(Some((location, tx)))
+    }
+
+    /// Height of a block on the active chain, by hash; `None` if the block is
+    /// unknown or on a side branch.
+    pub fn height_of(&self, block_hash: &str) -> ChainResult<Option<u64>> {
+        self.store.height_of(block_hash).map_err(ChainError::Storage)
+    }
+
+    /// Returns up to `limit` transactions of an address's history, newest first,
+    /// after skipping the `offset` newest ones. Needs the address index.
+    pub fn address_history(&self, address: &str, offset: usize, limit: usize) -> ChainResult<AddressHistory> {
+        if !self.address_index {
+            return Err(ChainError::Other("The address index is disabled on this node.".into()));
+        }
+        let history = self.store.address_txs(address).map_err(ChainError::Storage)?;
+        Ok(AddressHistory {
+            address: address.to_string(),
+            total: history.len(),
+            offset,
+            transactions: history.into_iter().rev().skip(offset).take(limit).collect(),
+        })
+    }
+
+    /// Returns the number of coins in existence on the active chain up to `height`.
+    ///
+    /// This is the genesis supply (every genesis allocation and validator stake) plus,
+    /// for every later block, what its coinbase claimed minus the fees it collected
+    /// (fees are transfers, not new coins) and the stake its slashing burned (taken
+    /// from the block's undo journal). It can be lower than [`emission::supply_at`]
+    /// when producers claim less than allowed or validators are slashed.
+    ///
+    /// For a chain started from a snapshot, the supply at the anchor is the sum of all
+    /// balances, stakes and unbonding funds. Heights below the base fail with
+    /// [`ChainError::Pruned`].
+    pub fn issued_supply(&self, height: u64) -> ChainResult<u64> {
+        let base = self.base_height();
+        if height < base {
+            return Err(ChainError::Pruned { index: height, base });
+        }
+        let tip = self.latest_block().index;
+        if height > tip {
+            return Err(ChainError::Other(format!("Height {} is above the chain tip {}.", height, tip)));
+        }
+
+        let mut supply: i128 = match &self.base_state {
+            Some(base_state) => base_state.accounts().map(|(_, a)| a.holdings() as i128).sum(),
+            None => self.spec.genesis_supply() as i128,
+        };
+        for block in &self.blocks[1..=(height - base) as usize] {
+            let claimed = block.transactions.first().filter(|tx| tx.is_coinbase()).map_or(0, |tx| tx.amount);
+            let fees = block
+                .transactions
+                .iter()
+                .skip(1)
+                .try_fold(0u64, |total, tx| total.checked_add(tx.fee))
+                .ok_or_else(|| ChainError::Other(format!("Fees of block {} overflow.", block.index)))?;
+            let burned = self
+                .store
+                .get_undo(&block.hash)
+                .map_err(ChainError::Storage)?
+                .ok_or_else(|| ChainError::Storage(format!("Missing undo journal of block {}.", block.hash)))?
+                .burned;
+            supply += claimed as i128 - fees as i128 - burned as i128;
+        }
+        Ok(supply.max(0) as u64)
+    }
+
+    /// Returns the spendable balance of an address at the chain tip.
+    pub fn balance_of(&self, address: &str) -> u64 {
+        self.state.balance(address)
+    }
+
+    /// Returns the next expected nonce of an address at the chain tip.
+    pub fn nonce_of(&self, address: &str) -> u64 {
+        self.state.nonce(address)
+    }
+
+    /// Validators at the chain tip: the staking pool of the current state, which
+    /// the next block is checked against.
+    pub fn staking_pool(&self) -> StakingPool {
+        StakingPool::from_state(&self.state)
+    }
+
+    /// Returns true if the consensus engine lets `address` produce the next block.
+    pub fn may_propose(&self, address: &str) -> bool {
+        self.engine
+            .may_propose(self.latest_block(), Utc::now().timestamp(), address, &self.staking_pool())
+    }
+
+    /// Proves the account of an address at the chain tip, against the tip's `state_root`.
+    pub fn account_proof(&self, address: &str) -> AccountProof {
+        self.state.prove_account(address)
+    }
+
+    /// Re-validates the entire active chain from genesis.
+    ///
+    /// Every block goes through the full pipeline (the future-drift rule excepted,
+    /// since it depends on the clock at acceptance time) and is replayed onto a
+    /// fresh world state, which must end up equal to the chain's own state.
+    ///
+    /// A chain started from a snapshot is validated from its anchor on, against
+    /// the restored snapshot state.
+    pub fn validate_chain(&self) -> ChainResult<()> {
+        let base = self
+            .blocks
+            .first()
+            .ok_or_else(|| ChainError::Other("Chain has no genesis block.".into()))?;
+        match &self.base_state {
+            Some(base_state) => {
+                validation::check_header(base)?;
+                let root = base_state.state_root();
+                if root != base.state_root {
+                    return Err(ChainError::BadStateRoot {
+                        index: base.index,
+                        expected: root,
+                        found: base.state_root.clone(),
+                    });
+                }
+            }
+            None => {
+                if base.index != 0 {
+                    return Err(ChainError::BadHeight {
+                        index: base.index,
+                        expected: 0,
+                    });
+                }
+                validation::check_block(base, self.spec.network_id)?;
+                validation::check_proof_of_work(base, self.spec.genesis.bits)?;
+            }
+        }
+
+        for pair in self.blocks.windows(2) {
+            let (previous, block) = (&pair[0], &pair[1]);
+            if block.previous_hash != previous.hash {
+                return Err(ChainError::UnknownParent {
+                    index: block.index,
+                    parent: block.previous_hash.clone(),
+                });
+            }
+            // Already accepted blocks are not held to the local clock.
+            self.validate_block_at(block, i64::MAX)?;
+        }
+
+        let state = self.replay_to(self.blocks.len())?;
+        if state != self.state {
+            return Err(ChainError::Other("World state does not match the replayed chain.".into()));
+        }
+        Ok(())
+    }
+
+    /// Returns true if [`Blockchain::validate_chain`] finds no problem.
+    pub fn is_valid(&self) -> bool {
+        self.validate_chain().is_ok()
+    }
+
+    /// Returns the number of blocks in the chain, including any before the snapshot it started from.
     pub fn length(&self) -> usize {
-        self.blocks.len()
+        self.latest_block().index as usize + 1
     }
 
     /// Exports the full chain as a JSON string (for explorer or archive).
     pub fn export_json(&self) -> String {
         serde_json::to_string_pretty(&self.blocks).unwrap_or_else(|_| "[]".to_string())
     }
+
+    /// Streams the active chain, genesis to tip, into `writer` as a binary
+    /// [archive](crate::blockchain::archive). Returns the number of blocks written.
+    ///
+    /// Fails with [`ChainError::Pruned`] if the chain started from a snapshot or
+    /// was pruned, since it no longer has every block body.
+    pub fn export_archive<W: Write>(&self, writer: W) -> ChainResult<u64> {
+        if self.base_state.is_some() {
+            return Err(ChainError::Pruned {
+                index: 0,
+                base: self.base_height(),
+            });
+        }
+        let mut archive = ArchiveWriter::new(writer, self.spec.network_id, &self.blocks[0].hash)?;
+        for block in &self.blocks {
+            archive.write_block(block)?;
+        }
+        let written = archive.blocks_written();
+        archive.finish()?;
+        Ok(written)
+    }
+
+    /// Imports a binary archive made by [`Blockchain::export_archive`], one block
+    /// at a time.
+    ///
+    /// The archive must come from this network. Blocks the chain already knows,
+    /// or at or below its base, are skipped; every other block goes through
+    /// [`Blockchain::accept_block`] and its full validation pipeline. Import stops
+    /// at the first invalid or unreadable block and returns its error; the blocks
+    /// before it stay imported, so an interrupted import can simply be rerun.
+    pub fn import_archive<R: Read>(&mut self, reader: R, mempool: &mut Mempool) -> ChainResult<ImportSummary> {
+        let genesis_hash = self
+            .store
+            .genesis_hash()
+            .map_err(ChainError::Storage)?
+            .ok_or_else(|| ChainError::Storage("Store is missing the genesis hash.".into()))?;
+        let archive = ArchiveReader::new(reader, self.spec.network_id, &genesis_hash)?;
+
+        let mut summary = ImportSummary::default();
+        for block in archive {
+            let block = block?;
+            if block.index <= self.base_height() || self.tree.contains(&block.hash) {
+                summary.skipped += 1;
+                continue;
+            }
+            self.accept_block(block, mempool)?;
+            self.prune()?;
+            summary.imported += 1;
+        }
+        Ok(summary)
+    }
+}
+
+impl Default for Blockchain {
+    fn default() -> Self {
+        Self::new(ChainSpec::default())
+    }
+}
+
+/// Checks the block's proposer against the staking pool of `state`, the state
+/// its parent left, then applies the block, records the proposals it shows were
+/// missed, and checks the state root committed in its header. `parent` is `None`
+/// only for the genesis block.
+///
+/// On error `state` may be partly updated; callers apply to a copy.
+fn apply_checked(
+    state: &mut WorldState,
+    block: &Block,
+    parent: Option<&Block>,
+    spec: &ChainSpec,
+    engine: &dyn ConsensusEngine,
+) -> ChainResult<()> {
+    let mut missed = Vec::new();
+    if let Some(parent) = parent {
+        let validators = StakingPool::from_state(state);
+        validation::check_proposer(engine, block, parent, &validators)?;
+        missed = engine.missed_proposers(block, parent, &validators);
+    }
+    state
+        .apply_block(block, spec)
+        .map_err(|reason| ChainError::StateRejected {
+            index: block.index,
+            reason,
+        })?;
+    if parent.is_some() {
+        state.record_proposal(block, &missed, spec);
+    }
+    let root = state.state_root();
+    if root != block.state_root {
+        return Err(ChainError::BadStateRoot {
+            index: block.index,
+            expected: root,
+            found: block.state_root.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// Block tree rooted at the oldest header of a snapshot, holding every snapshot header.
+/// Each header's seal is checked by `engine` as far as a header shows it.
+///
+/// Headers reaching back to height 0 must start at this network's genesis block.
+fn snapshot_tree(snapshot: &StateSnapshot, genesis_block: &Block, engine: &dyn ConsensusEngine) -> ChainResult<BlockTree> {
+    snapshot.manifest.verify()?;
+    for header in &snapshot.manifest.headers {
+        engine
+            .validate_header(header)
+            .into_result()
+            .map_err(|reason| ChainError::ConsensusRejected {
+                index: header.index,
+                reason,
+            })?;
+    }
+    let mut headers = snapshot.manifest.headers.iter().map(Block::from_header);
+    let root = headers
+        .next()
+        .ok_or_else(|| ChainError::Other("Snapshot manifest has no headers.".into()))?;
+    if root.index == 0 && root.hash != genesis_block.hash {
+        return Err(ChainError::Other("Snapshot belongs to a different network.".into()));
+    }
+
+    let mut tree = BlockTree::new(&root);
+    for block in headers {
+        tree.insert(block, engine)?;
+    }
+    Ok(tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::OnceLock;
+
+    use crate::blockchain::emission::COIN;
+    use crate::blockchain::spec::{AssumeValid, GenesisAllocation};
+    use crate::blockchain::template::{TemplateStore, WorkSubmission};
+    use crate::consensus::pos::MIN_STAKE;
+    use crate::crypto::keypair::PrivateKey;
+    use crate::storage::RocksStore;
+    use crate::wallet::wallet::derive_address;
+
+    /// Mines a child of `parent`, a block of `chain`'s tree, committing to its post-state.
+    fn mined(chain: &Blockchain, parent: &Block, transfers: Vec<Transaction>) -> Block {
+        let coinbase = Transaction::coinbase(parent.index + 1, "miner", 1, "");
+        let transactions = std::iter::once(coinbase).chain(transfers).collect();
+        let mut block = Block::new(parent.index + 1, parent.hash.clone(), transactions, parent.bits);
+        block.timestamp = parent.timestamp + 47;
+
+        let mut branch = vec![];
+        let mut cursor = chain.tree.get(&parent.hash);
+        while let Some(entry) = cursor {
+            branch.push(entry.block.clone());
+            cursor = chain.tree.get(&entry.block.previous_hash);
+        }
+        let mut state = WorldState::new();
+        for ancestor in branch.iter().rev() {
+            state.apply_block(ancestor, &chain.spec).unwrap();
+        }
+        // A block that does not apply keeps the all-zero root; it is rejected either way.
+        if state.apply_block(&block, &chain.spec).is_ok() {
+            block.state_root = state.state_root();
+        }
+
+        block.mine();
+        block
+    }
+
+    /// Mines `transactions` on the active tip from a block template.
+    fn mine(chain: &mut Blockchain, transactions: Vec<Transaction>) -> Block {
+        let template = chain.block_template(&transactions, "miner").unwrap();
+        let mut block = template.to_block(template.coinbase.nonce, template.timestamp, 0);
+        let parent = chain.latest_block().clone();
+        chain.engine().seal_block(&mut block, &parent, None, &CancelHandle::new()).unwrap();
+        assert!(chain.accept_block(block.clone(), &mut Mempool::new()).unwrap());
+        block
+    }
+
+    /// The key holding the genesis allocation of [`spec`].
+    fn sender() -> &'static PrivateKey {
+        static KEY: OnceLock<PrivateKey> = OnceLock::new();
+        KEY.get_or_init(PrivateKey::generate)
+    }
+
+    fn sender_address() -> String {
+        derive_address(&sender().public_key())
+    }
+
+    fn spec() -> ChainSpec {
+        let mut spec = ChainSpec::devnet();
+        spec.genesis.allocations.push(GenesisAllocation { address: sender_address(), amount: 1_000_000 * COIN });
+        spec
+    }
+
+    fn signed(mut tx: Transaction) -> Transaction {
+        tx.sign(ChainSpec::devnet().network_id, sender());
+        tx
+    }
+
+    fn transfer(to: &str, amount: u64, nonce: u64) -> Transaction {
+        signed(Transaction::new(&sender_address(), to, amount, "").with_nonce(nonce))
+    }
+
+    #[test]
+    fn follows_the_branch_with_most_work() {
+        let mut chain = Blockchain::new(spec());
+        let mut mempool = Mempool::new();
+        let genesis = chain.latest_block().clone();
+
+        let a1 = mined(&chain, &genesis, vec![transfer("a", 1, 0)]);
+        assert!(chain.accept_block(a1.clone(), &mut mempool).unwrap());
+
+        // Equal work: the first-seen tip stays active.
+        let b1 = mined(&chain, &genesis, vec![transfer("b", 1, 0)]);
+        assert!(!chain.accept_block(b1.clone(), &mut mempool).unwrap());
+        assert_eq!(chain.latest_block().hash, a1.hash);
+
+        let b2 = mined(&chain, &b1, vec![transfer("b", 1, 1)]);
+        assert!(chain.accept_block(b2.clone(), &mut mempool).unwrap());
+        assert_eq!(chain.latest_block().hash, b2.hash);
+        assert_eq!(chain.length(), 3);
+        assert_eq!(chain.balance_of("a"), 0);
+        assert_eq!(chain.balance_of("b"), 2);
+        assert!(chain.is_valid());
+    }
+
+    #[test]
+    fn keeps_the_active_chain_when_a_heavier_branch_is_invalid() {
+        let mut chain = Blockchain::new(spec());
+        let mut mempool = Mempool::new();
+        let genesis = chain.latest_block().clone();
+
+        let a1 = mined(&chain, &genesis, vec![]);
+        chain.accept_block(a1.clone(), &mut mempool).unwrap();
+
+        let b1 = mined(&chain, &genesis, vec![transfer("b", 1, 0)]);
+        chain.accept_block(b1.clone(), &mut mempool).unwrap();
+        let b2 = mined(&chain, &b1, vec![transfer("b", u64::MAX, 1)]);
+        assert!(chain.accept_block(b2.clone(), &mut mempool).is_err());
+
+        assert_eq!(chain.latest_block().hash, a1.hash);
+        assert!(!chain.tree.contains(&b2.hash));
+    }
+
+    #[test]
+    fn checks_signatures_off_the_assume_valid_branch() {
+        let mut source = Blockchain::new(spec());
+        let a1 = mine(&mut source, vec![transfer("bob", 10, 0)]);
+        let a2 = mine(&mut source, vec![]);
+        let mut trusting = spec();
+        trusting.assume_valid = Some(AssumeValid { height: 2, hash: a2.hash.clone() });
+
+        let mut forged = Transaction::new(&sender_address(), "mallory", 50, "");
+        forged.sign(trusting.network_id, &PrivateKey::generate());
+
+        // Before the assume-valid block is known, nothing shows a block is its ancestor.
+        let mut syncing = Blockchain::new(trusting.clone());
+        let genesis = syncing.latest_block().clone();
+        let early = mined(&syncing, &genesis, vec![forged.clone()]);
+        assert!(syncing.accept_block(early, &mut Mempool::new()).is_err());
+
+        let mut synced = Blockchain::new(trusting);
+        for block in [a1, a2.clone()] {
+            assert!(synced.accept_block(block, &mut Mempool::new()).unwrap());
+        }
+        let side = mined(&synced, &genesis, vec![forged]);
+        assert!(synced.accept_block(side.clone(), &mut Mempool::new()).is_err());
+        assert!(!synced.tree.contains(&side.hash));
+        assert_eq!(synced.latest_block().hash, a2.hash);
+        assert_eq!(synced.validate_chain(), Ok(()));
+    }
+
+    #[test]
+    fn pins_the_block_time_of_proof_of_stake_templates() {
+        let key = PrivateKey::generate();
+        let proposer = derive_address(&key.public_key());
+        let mut spec = spec();
+        spec.consensus.engine = ConsensusKind::Pos;
+        spec.consensus.max_future_drift = 4;
+        spec.genesis.validators.push(GenesisAllocation { address: proposer.clone(), amount: MIN_STAKE });
+        let mut chain = Blockchain::new(spec);
+
+        let template = chain.block_template(&[], &proposer).unwrap();
+        assert_eq!((template.min_time, template.max_time), (template.timestamp, template.timestamp));
+        let mut store = TemplateStore::new();
+        store.insert(template.clone());
+        let moved = WorkSubmission {
+            template_id: template.template_id.clone(),
+            nonce: 0,
+            extra_nonce: template.coinbase.nonce,
+            timestamp: Some(template.timestamp + 1),
+        };
+        assert!(store.solve(&moved).is_err());
+
+        let mut block = template.to_block(template.coinbase.nonce, template.timestamp, 0);
+        let parent = chain.latest_block().clone();
+        chain.engine().seal_block(&mut block, &parent, Some(&key), &CancelHandle::new()).unwrap();
+        assert!(chain.accept_block(block, &mut Mempool::new()).unwrap());
+    }
+
+    #[test]
+    fn keeps_templates_within_the_block_size() {
+        let mut chain = Blockchain::new(spec());
+        let oversized = transfer(&"b".repeat(MAX_BLOCK_SIZE as usize), 10, 0);
+        let small = transfer("bob", 10, 0);
+
+        let template = chain.block_template(&[oversized, small.clone()], "miner").unwrap();
+        assert_eq!(template.transactions, vec![small.clone()]);
+        mine(&mut chain, vec![small]);
+        assert_eq!(chain.balance_of("bob"), 10);
+    }
+
+    #[test]
+    fn returns_reorged_out_transactions_to_the_mempool() {
+        let mut chain = Blockchain::new(spec());
+        let mut mempool = Mempool::new();
+        let genesis = chain.latest_block().clone();
+
+        let tx = transfer("bob", 10, 0);
+        mempool.add_transaction(tx.clone()).unwrap();
+        let a1 = mined(&chain, &genesis, vec![tx.clone()]);
+        chain.accept_block(a1, &mut mempool).unwrap();
+        assert!(!mempool.contains(&tx.hash));
+        assert_eq!(chain.balance_of("bob"), 10);
+
+        let b1 = mined(&chain, &genesis, vec![]);
+        chain.accept_block(b1.clone(), &mut mempool).unwrap();
+        let b2 = mined(&chain, &b1, vec![]);
+        chain.accept_block(b2, &mut mempool).unwrap();
+
+        assert!(mempool.contains(&tx.hash));
+        assert_eq!(chain.balance_of("bob"), 0);
+    }
+
+    #[test]
+    fn tracks_issued_supply_against_the_schedule() {
+        let mut chain = Blockchain::new(spec());
+        mine(&mut chain, vec![signed(Transaction::new(&sender_address(), "bob", 5, "").with_fee(1))]);
+        mine(&mut chain, vec![]);
+
+        assert_eq!(chain.issued_supply(2), Ok(emission::supply_at(&chain.spec, 2)));
+        assert_eq!(chain.balance_of("miner"), emission::supply_at(&chain.spec, 2) - chain.spec.genesis_supply() + 1);
+        assert!(chain.issued_supply(3).is_err());
+    }
+
+    #[test]
+    fn reloads_the_chain_from_its_store() {
+        let path = std::env::temp_dir().join(format!("ocos-chain-test-{}", std::process::id()));
+        let open = || {
+            let store = ChainStore::new(std::sync::Arc::new(RocksStore::open(&path).unwrap()));
+            Blockchain::open(spec(), store).unwrap()
+        };
+
+        let (tip, side) = {
+            let mut chain = open();
+            let mut mempool = Mempool::new();
+            let genesis = chain.latest_block().clone();
+            let side = mined(&chain, &genesis, vec![]);
+            chain.accept_block(side.clone(), &mut mempool).unwrap();
+            let a1 = mined(&chain, &genesis, vec![transfer("bob", 10, 0)]);
+            chain.accept_block(a1.clone(), &mut mempool).unwrap();
+            let a2 = mined(&chain, &a1, vec![]);
+            chain.accept_block(a2.clone(), &mut mempool).unwrap();
+            (a2, side)
+        };
+
+        let chain = open();
+        assert_eq!(chain.latest_block().hash, tip.hash);
+        assert_eq!(chain.length(), 3);
+        assert_eq!(chain.balance_of("bob"), 10);
+        assert!(chain.tree.contains(&side.hash));
+        assert!(chain.validate_chain().is_ok());
+
+        // A store initialized for one network cannot be opened as another.
+        assert!(Blockchain::open(ChainSpec::testnet(), chain.store().clone()).is_err());
+        drop(chain);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn recovers_a_half_applied_tip() {
+        let mut chain = Blockchain::new(spec());
+        let mut mempool = Mempool::new();
+        let a1 = mined(&chain, chain.latest_block(), vec![]);
+        chain.accept_block(a1.clone(), &mut mempool).unwrap();
+        let parent = mined(&chain, &a1, vec![transfer("bob", 10, 0)]);
+        chain.accept_block(parent.clone(), &mut mempool).unwrap();
+        let parent_state = chain.state.clone();
+        let tip = mined(&chain, &parent, vec![transfer("bob", 5, 1)]);
+        chain.accept_block(tip.clone(), &mut mempool).unwrap();
+
+        // The block and its height entry were written, the tip pointer and state were not.
+        let store = chain.store().clone();
+        let mut batch = WriteBatch::new();
+        store.set_tip(&mut batch, &parent.hash);
+        store.replace_state(&mut batch, &parent_state).unwrap();
+        store.commit(batch).unwrap();
+        assert_eq!(store.max_height().unwrap(), Some(tip.index));
+
+        let recovered = Blockchain::open(spec(), store.clone()).unwrap();
+        assert_eq!(recovered.repaired_tip(), Some(parent.index));
+        assert_eq!(recovered.latest_block().hash, parent.hash);
+        assert_eq!(recovered.state, parent_state);
+        assert_eq!(recovered.balance_of("bob"), 10);
+        assert_eq!(store.max_height().unwrap(), Some(parent.index));
+        assert_eq!(store.hash_at(tip.index).unwrap(), None);
+        assert_eq!(store.tx_block(&tip.transactions[1].hash).unwrap(), None);
+        assert!(recovered.tree.contains(&tip.hash));
+
+        // The repair was committed: the store now opens as consistent.
+        let reopened = Blockchain::open(spec(), store).unwrap();
+        assert_eq!(reopened.latest_block().hash, parent.hash);
+        assert_eq!(reopened.repaired_tip(), None);
+        assert_eq!(reopened.state, parent_state);
+    }
+
+    #[test]
+    fn starts_from_a_verified_snapshot() {
+        let mut chain = Blockchain::new(spec());
+        mine(&mut chain, vec![transfer("bob", 10, 0)]);
+        mine(&mut chain, vec![]);
+        mine(&mut chain, vec![transfer("bob", 5, 1)]);
+        let anchor = chain.block_at(2).unwrap().clone();
+        let snapshot = chain.snapshot(2).unwrap();
+
+        let wrong_anchor = chain.block_at(1).unwrap().hash.clone();
+        assert!(Blockchain::from_snapshot(spec(), ChainStore::memory(), snapshot.clone(), &wrong_anchor).is_err());
+        let mut tampered = snapshot.clone();
+        tampered.chunks[0].accounts[0].1.balance += 1;
+        assert!(Blockchain::from_snapshot(spec(), ChainStore::memory(), tampered, &anchor.hash).is_err());
+
+        let mut restored =
+            Blockchain::from_snapshot(spec(), ChainStore::memory(), snapshot, &anchor.hash).unwrap();
+        assert_eq!(restored.latest_block().hash, anchor.hash);
+        assert_eq!(restored.base_height(), 2);
+        assert_eq!(restored.balance_of("bob"), 10);
+
+        let next = chain.block_at(3).unwrap().clone();
+        assert!(restored.accept_block(next, &mut Mempool::new()).unwrap());
+        assert_eq!(restored.state, chain.state);
+        assert_eq!(restored.balance_of("bob"), 15);
+    }
+
+    #[test]
+    fn prunes_bodies_below_the_depth() {
+        let store = ChainStore::memory();
+        let mut chain = Blockchain::open(spec(), store.clone()).unwrap();
+        assert!(chain.set_prune_depth(Some(0)).is_err());
+        chain.set_prune_depth(Some(10)).unwrap();
+
+        mine(&mut chain, vec![transfer("bob", 10, 0)]);
+        while chain.latest_block().index < PRUNE_INTERVAL + 10 {
+            assert_eq!(chain.base_height(), 0);
+            mine(&mut chain, vec![]);
+            chain.prune().unwrap();
+        }
+        let tip = chain.latest_block().index;
+        assert_eq!(chain.base_height(), tip - 10);
+        assert!(matches!(chain.block(1), Err(ChainError::Pruned { index: 1, .. })));
+        assert!(chain.block(tip - 10).is_ok());
+        assert_eq!(chain.balance_of("bob"), 10);
+        assert_eq!(chain.validate_chain(), Ok(()));
+
+        let mut reopened = Blockchain::open(spec(), store).unwrap();
+        assert_eq!(reopened.prune_depth(), Some(10));
+        assert_eq!(reopened.base_height(), tip - 10);
+        assert_eq!(reopened.state, chain.state);
+        mine(&mut reopened, vec![transfer("bob", 5, 1)]);
+        assert_eq!(reopened.balance_of("bob"), 15);
+    }
+
+    #[test]
+    fn indexes_follow_the_active_chain() {
+        let mut chain = Blockchain::new(spec());
+        let mut mempool = Mempool::new();
+        let genesis = chain.latest_block().clone();
+        assert!(chain.address_history("bob", 0, 10).is_err());
+        chain.set_address_index(true).unwrap();
+
+        let tx = transfer("bob", 10, 0);
+        let a1 = mined(&chain, &genesis, vec![tx.clone()]);
+        chain.accept_block(a1.clone(), &mut mempool).unwrap();
+
+        let (location, found) = chain.find_transaction(&tx.hash).unwrap().unwrap();
+        assert_eq!(found, tx);
+        assert_eq!((location.block_hash.as_str(), location.height, location.position), (a1.hash.as_str(), 1, 1));
+        assert_eq!(chain.height_of(&a1.hash).unwrap(), Some(1));
+        let history = chain.address_history(&sender_address(), 0, 10).unwrap();
+        assert_eq!(history.total, 2);
+        assert_eq!(history.transactions[0].tx_hash, tx.hash);
+        assert_eq!(chain.address_history(&sender_address(), 1, 10).unwrap().transactions[0].height, 0);
+
+        // Reorganizing away from a1 drops its entries.
+        let b1 = mined(&chain, &genesis, vec![]);
+        chain.accept_block(b1.clone(), &mut mempool).unwrap();
+        let b2 = mined(&chain, &b1, vec![]);
+        chain.accept_block(b2.clone(), &mut mempool).unwrap();
+
+        assert_eq!(chain.find_transaction(&tx.hash).unwrap(), None);
+        assert_eq!(chain.height_of(&a1.hash).unwrap(), None);
+        assert_eq!(chain.height_of(&b2.hash).unwrap(), Some(2));
+        assert_eq!(chain.address_history("bob", 0, 10).unwrap().total, 0);
+    }
+
+    #[test]
+    fn imports_an_exported_archive() {
+        let mut source = Blockchain::new(spec());
+        mine(&mut source, vec![transfer("bob", 10, 0)]);
+        mine(&mut source, vec![]);
+        mine(&mut source, vec![]);
+        let mut archive = Vec::new();
+        assert_eq!(source.export_archive(&mut archive).unwrap(), 4);
+
+        let mut copy = Blockchain::new(spec());
+        let summary = copy.import_archive(archive.as_slice(), &mut Mempool::new()).unwrap();
+        assert_eq!(summary, ImportSummary { imported: 3, skipped: 1 });
+        assert_eq!(copy.latest_block().hash, source.latest_block().hash);
+        assert_eq!(copy.state, source.state);
+
+        // Rerunning an import skips what is already there.
+        let summary = copy.import_archive(archive.as_slice(), &mut Mempool::new()).unwrap();
+        assert_eq!(summary, ImportSummary { imported: 0, skipped: 4 });
+    }
+
+    #[test]
+    fn rejects_a_bad_archive() {
+        let mut source = Blockchain::new(spec());
+        mine(&mut source, vec![transfer("bob", 10, 0)]);
+        mine(&mut source, vec![]);
+        let mut archive = Vec::new();
+        source.export_archive(&mut archive).unwrap();
+
+        let mut other_network = Blockchain::new(ChainSpec::testnet());
+        assert!(other_network.import_archive(archive.as_slice(), &mut Mempool::new()).is_err());
+
+        // The last byte belongs to the tip's coinbase hash; the blocks before it stay imported.
+        let mut tampered = archive.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        let mut copy = Blockchain::new(spec());
+        assert!(copy.import_archive(tampered.as_slice(), &mut Mempool::new()).is_err());
+        assert_eq!(copy.latest_block().index, 1);
+
+        let truncated = &archive[..archive.len() - 4];
+        assert!(Blockchain::new(spec()).import_archive(truncated, &mut Mempool::new()).is_err());
+    }
 }
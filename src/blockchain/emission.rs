@@ -0,0 +1,111 @@
+//! # Emission Module
+//! The OCOS coin issuance schedule.
+//!
+//! New coins enter circulation only through the coinbase transaction of each
+//! block. The block subsidy starts at [`INITIAL_SUBSIDY`] and halves every
+//! [`HALVING_INTERVAL`] blocks (~4 years at the 47-second block time). Total
+//! issuance, including the genesis allocation, can never exceed [`MAX_SUPPLY`]
+//! (21,000,000 OCOS, see `docs/vision/S470SHI_Chain.md`).
+//!
+//! The constants are the mainnet values; every function reads the actual
+//! parameters from the network's [`ChainSpec`].
+
+use crate::blockchain::spec::{ChainSpec, ConsensusParams};
+
+/// Number of base units in one OCOS.
+pub const COIN: u64 = 100_000_000;
+
+/// Hard cap on the number of base units that will ever exist.
+pub const MAX_SUPPLY: u64 = 21_000_000 * COIN;
+
+/// Subsidy of the first block after genesis (3.9 OCOS).
+pub const INITIAL_SUBSIDY: u64 = 390_000_000;
+
+/// Number of blocks between subsidy halvings.
+pub const HALVING_INTERVAL: u64 = 2_680_000;
+
+/// Subsidy paid by the emission schedule at `height`, before applying the supply cap.
+fn scheduled_subsidy(params: &ConsensusParams, height: u64) -> u64 {
+    if height == 0 {
+        return 0;
+    }
+    let halvings = (height - 1) / params.halving_interval;
+    if halvings >= 64 {
+        0
+    } else {
+        params.initial_subsidy >> halvings
+    }
+}
+
+/// Sum of the scheduled subsidies of blocks `1..=height`.
+fn scheduled_issuance(params: &ConsensusParams, height: u64) -> u64 {
+    let mut total: u64 = 0;
+    let mut era_start: u64 = 1;
+    while era_start <= height {
+        let subsidy = scheduled_subsidy(params, era_start);
+        if subsidy == 0 {
+            break;
+        }
+        let era_end = era_start.saturating_add(params.halving_interval - 1).min(height);
+        total = total.saturating_add(subsidy.saturating_mul(era_end - era_start + 1));
+        era_start = era_start.saturating_add(params.halving_interval);
+    }
+    total
+}
+
+/// Maximum number of new coins the coinbase of the block at `height` may create.
+///
+/// Genesis (height 0) issues only the spec's genesis allocations.
+pub fn block_subsidy(spec: &ChainSpec, height: u64) -> u64 {
+    if height == 0 {
+        return 0;
+    }
+    let issued_before = supply_at(spec, height - 1);
+    scheduled_subsidy(&spec.consensus, height).min(spec.consensus.max_supply.saturating_sub(issued_before))
+}
+
+/// Total coins issued by the schedule up to and including `height`, genesis included.
+pub fn supply_at(spec: &ChainSpec, height: u64) -> u64 {
+    spec.genesis_supply()
+        .saturating_add(scheduled_issuance(&spec.consensus, height))
+        .min(spec.consensus.max_supply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn halves_the_subsidy_every_interval() {
+        let spec = ChainSpec::mainnet();
+        assert_eq!(block_subsidy(&spec, 0), 0);
+        assert_eq!(block_subsidy(&spec, 1), INITIAL_SUBSIDY);
+        assert_eq!(block_subsidy(&spec, HALVING_INTERVAL), INITIAL_SUBSIDY);
+        assert_eq!(block_subsidy(&spec, HALVING_INTERVAL + 1), INITIAL_SUBSIDY / 2);
+        assert_eq!(block_subsidy(&spec, 3 * HALVING_INTERVAL + 1), INITIAL_SUBSIDY / 8);
+        assert_eq!(block_subsidy(&spec, 64 * HALVING_INTERVAL + 1), 0);
+    }
+
+    #[test]
+    fn supply_matches_the_sum_of_subsidies() {
+        let spec = ChainSpec::mainnet();
+        assert_eq!(supply_at(&spec, 0), spec.genesis_supply());
+        assert_eq!(supply_at(&spec, 1), spec.genesis_supply() + INITIAL_SUBSIDY);
+        let era = HALVING_INTERVAL;
+        assert_eq!(
+            supply_at(&spec, 2 * era),
+            spec.genesis_supply() + INITIAL_SUBSIDY * era + INITIAL_SUBSIDY / 2 * era
+        );
+    }
+
+    #[test]
+    fn never_issues_more_than_the_cap() {
+        let spec = ChainSpec::mainnet();
+        let total = supply_at(&spec, u64::MAX);
+        assert!(total <= spec.consensus.max_supply);
+        assert_eq!(total, supply_at(&spec, 64 * HALVING_INTERVAL));
+        for height in [1, HALVING_INTERVAL, 40 * HALVING_INTERVAL, u64::MAX] {
+            assert!(supply_at(&spec, height - 1) + block_subsidy(&spec, height) <= spec.consensus.max_supply);
+        }
+    }
+}
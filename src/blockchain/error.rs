@@ -0,0 +1,228 @@
+//! # Chain Errors
+//! Typed reasons for rejecting a block or failing a chain operation.
+//!
+//! Every block rejection names the rule that failed and the index of the
+//! offending block, so callers (the API, peers, import tools) can react to the
+//! reason instead of parsing messages.
+
+use std::fmt;
+
+use crate::blockchain::block::BlockHash;
+
+/// Result type of fallible [`Blockchain`](crate::blockchain::Blockchain) operations.
+pub type ChainResult<T> = Result<T, ChainError>;
+
+/// Why a block was rejected or a chain operation failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainError {
+    /// The block is already in the block tree.
+    DuplicateBlock { index: u64, hash: BlockHash },
+
+    /// The block's parent is not known.
+    UnknownParent { index: u64, parent: BlockHash },
+
+    /// The block index is not its parent's height plus one.
+    BadHeight { index: u64, expected: u64 },
+
+    /// Unsupported version or header fields that do not map one-to-one onto the binary header.
+    NonCanonicalHeader { index: u64 },
+
+    /// The stored hash is not the hash of the block header.
+    BadHash { index: u64 },
+
+    /// The block carries a different target than the retargeting rules require.
+    BadBits { index: u64, expected: u32, found: u32 },
+
+    /// The block hash does not meet its target.
+    InsufficientWork { index: u64 },
+
+    /// The timestamp violates the median-time-past or future-drift rule.
+    BadTimestamp { index: u64, reason: String },
+
+    /// The header's merkle root does not commit to the block's transactions.
+    BadMerkleRoot { index: u64, expected: String, found: String },
+
+    /// The block holds more transactions or encoded bytes than a block may.
+    OversizedBlock { index: u64, reason: String },
+
+    /// The block does not start with exactly one coinbase transaction, or its
+    /// coinbase does not commit to the block height.
+    BadCoinbase { index: u64, reason: String },
+
+    /// A transaction failed stateless validation.
+    BadTransaction { index: u64, tx_hash: String, reason: String },
+
+    /// Applying the block to the world state failed (overdraw, nonce, excessive coinbase).
+    StateRejected { index: u64, reason: String },
+
+    /// The state after the block does not match the state root in its header.
+    BadStateRoot { index: u64, expected: String, found: String },
+
+    /// The consensus engine rejected the block.
+    ConsensusRejected { index: u64, reason: String },
+
+    /// The block (or its ancestor at `height`) is not the checkpointed block for that height.
+    CheckpointMismatch { index: u64, height: u64, expected: BlockHash, found: BlockHash },
+
+    /// The block forks off the active chain at or below a checkpoint it already passed.
+    ForkBelowCheckpoint { index: u64, checkpoint: u64 },
+
+    /// The block is at or below the finalized block at height `finalized`, or
+    /// does not descend from it.
+    BelowFinalized { index: u64, finalized: u64 },
+
+    /// A finality vote or checkpoint for the block at `index` was rejected.
+    BadFinalityVote { index: u64, reason: String },
+
+    /// The block body is no longer kept: the node is pruned (or started from a
+    /// snapshot) and only holds bodies from height `base` on.
+    Pruned { index: u64, base: u64 },
+
+    /// The storage backend failed or holds inconsistent data.
+    Storage(String),
+
+    /// Any other failure that is not tied to a block rule.
+    Other(String),
+}
+
+impl ChainError {
+    /// Index of the offending block, if the error concerns one.
+    pub fn block_index(&self) -> Option<u64> {
+        match self {
+            ChainError::DuplicateBlock { index, .. }
+            | ChainError::UnknownParent { index, .. }
+            | ChainError::BadHeight { index, .. }
+            | ChainError::NonCanonicalHeader { index }
+            | ChainError::BadHash { index }
+            | ChainError::BadBits { index, .. }
+            | ChainError::InsufficientWork { index }
+            | ChainError::BadTimestamp { index, .. }
+            | ChainError::BadMerkleRoot { index, .. }
+            | ChainError::OversizedBlock { index, .. }
+            | ChainError::BadCoinbase { index, .. }
+            | ChainError::BadTransaction { index, .. }
+            | ChainError::StateRejected { index, .. }
+            | ChainError::BadStateRoot { index, .. }
+            | ChainError::ConsensusRejected { index, .. }
+            | ChainError::CheckpointMismatch { index, .. }
+            | ChainError::ForkBelowCheckpoint { index, .. }
+            | ChainError::BelowFinalized { index, .. }
+            | ChainError::BadFinalityVote { index, .. }
+            | ChainError::Pruned { index, .. } => Some(*index),
+            ChainError::Storage(_) | ChainError::Other(_) => None,
+        }
+    }
+
+    /// Short, stable name of the violated rule (e.g. for API responses and metrics).
+    pub fn rule(&self) -> &'static str {
+        match self {
+            ChainError::DuplicateBlock { .. } => "duplicate-block",
+            ChainError::UnknownParent { .. } => "unknown-parent",
+            ChainError::BadHeight { .. } => "bad-height",
+            ChainError::NonCanonicalHeader { .. } => "non-canonical-header",
+            ChainError::BadHash { .. } => "bad-hash",
+            ChainError::BadBits { .. } => "bad-bits",
+            ChainError::InsufficientWork { .. } => "insufficient-work",
+            ChainError::BadTimestamp { .. } => "bad-timestamp",
+            ChainError::BadMerkleRoot { .. } => "bad-merkle-root",
+            ChainError::OversizedBlock { .. } => "oversized-block",
+            ChainError::BadCoinbase { .. } => "bad-coinbase",
+            ChainError::BadTransaction { .. } => "bad-transaction",
+            ChainError::StateRejected { .. } => "state-rejected",
+            ChainError::BadStateRoot { .. } => "bad-state-root",
+            ChainError::ConsensusRejected { .. } => "consensus-rejected",
+            ChainError::CheckpointMismatch { .. } => "checkpoint-mismatch",
+            ChainError::ForkBelowCheckpoint { .. } => "fork-below-checkpoint",
+            ChainError::BelowFinalized { .. } => "below-finalized",
+            ChainError::BadFinalityVote { .. } => "bad-finality-vote",
+            ChainError::Pruned { .. } => "pruned",
+            ChainError::Storage(_) => "storage",
+            ChainError::Other(_) => "other",
+        }
+    }
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::DuplicateBlock { index, hash } => {
+                write!(f, "Block {} ({}) is already known.", index, hash)
+            }
+            ChainError::UnknownParent { index, parent } => {
+                write!(f, "Block {}: unknown parent {}.", index, parent)
+            }
+            ChainError::BadHeight { index, expected } => {
+                write!(f, "Block {}: expected index {}.", index, expected)
+            }
+            ChainError::NonCanonicalHeader { index } => {
+                write!(f, "Block {}: unsupported version or non-canonical header fields.", index)
+            }
+            ChainError::BadHash { index } => {
+                write!(f, "Block {}: hash does not match the header.", index)
+            }
+            ChainError::BadBits { index, expected, found } => write!(
+                f,
+                "Block {}: target bits {:#010x}, expected {:#010x}.",
+                index, found, expected
+            ),
+            ChainError::InsufficientWork { index } => {
+                write!(f, "Block {}: hash does not meet the target.", index)
+            }
+            ChainError::BadTimestamp { index, reason } => write!(f, "Block {}: {}", index, reason),
+            ChainError::BadMerkleRoot { index, expected, found } => write!(
+                f,
+                "Block {}: merkle root {} does not match transactions ({}).",
+                index, found, expected
+            ),
+            ChainError::OversizedBlock { index, reason } => write!(f, "Block {}: {}", index, reason),
+            ChainError::BadCoinbase { index, reason } => write!(f, "Block {}: {}", index, reason),
+            ChainError::BadTransaction { index, tx_hash, reason } => {
+                write!(f, "Block {}: transaction {} is invalid: {}", index, tx_hash, reason)
+            }
+            ChainError::StateRejected { index, reason } => {
+                write!(f, "Block {}: rejected by state: {}", index, reason)
+            }
+            ChainError::BadStateRoot { index, expected, found } => write!(
+                f,
+                "Block {}: state root {} does not match the resulting state ({}).",
+                index, found, expected
+            ),
+            ChainError::ConsensusRejected { index, reason } => {
+                write!(f, "Block {}: rejected by consensus: {}", index, reason)
+            }
+            ChainError::CheckpointMismatch { index, height, expected, found } => write!(
+                f,
+                "Block {}: block {} at checkpoint height {} does not match checkpoint {}.",
+                index, found, height, expected
+            ),
+            ChainError::ForkBelowCheckpoint { index, checkpoint } => write!(
+                f,
+                "Block {}: forks off the active chain below the checkpoint at height {}.",
+                index, checkpoint
+            ),
+            ChainError::BelowFinalized { index, finalized } => write!(
+                f,
+                "Block {}: conflicts with the finalized block at height {}.",
+                index, finalized
+            ),
+            ChainError::BadFinalityVote { index, reason } => {
+                write!(f, "Block {}: finality vote rejected: {}", index, reason)
+            }
+            ChainError::Pruned { index, base } => write!(
+                f,
+                "Block {}: pruned; this node keeps block bodies from height {} on.",
+                index, base
+            ),
+            ChainError::Storage(message) => write!(f, "Storage error: {}", message),
+            ChainError::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+impl From<String> for ChainError {
+    fn from(message: String) -> Self {
+        ChainError::Other(message)
+    }
+}
@@ -6,63 +6,85 @@
 //! Genesis blocks are immutable and serve as the trust anchor for all future blocks.
 //! © OCOS Blockchain, 2025 — "Not your keys, not your mind."
 
-use chrono::{DateTime, Utc, TimeZone};
-use crate::blockchain::block::Block;
-use crate::transaction::tx::Transaction;
-use crate::crypto::hash::calculate_merkle_root;
+use chrono::{SecondsFormat, TimeZone, Utc};
+use crate::blockchain::block::{Block, BLOCK_VERSION};
+use crate::blockchain::spec::ChainSpec;
+use crate::blockchain::state::WorldState;
+use crate::transaction::tx::{Transaction, COINBASE_SENDER};
 
 /// The default timestamp for OCOS Genesis Block
 pub const GENESIS_TIMESTAMP: &str = "2025-07-11T00:47:00Z";
 
-/// The default difficulty for the genesis block (can be updated)
-pub const GENESIS_DIFFICULTY: usize = 4;
+/// [`GENESIS_TIMESTAMP`] in UNIX seconds
+pub const GENESIS_TIME: i64 = 1_752_194_820;
 
-/// The symbolic OCOS Genesis reward
-pub const GENESIS_REWARD: u64 = 47;
-
-/// The initial recipient of the Genesis Block reward
-pub const GENESIS_RECEIVER: &str = "OCOS_PUBLIC_KEY";
+/// The compact proof-of-work target of the genesis block (0x0000ffff…)
+pub const GENESIS_BITS: u32 = 0x1f00ffff;
 
 /// A unique, philosophical message encoded into the genesis block.
 pub const GENESIS_MESSAGE: &str =
     "11 July 2025 — OCOS Genesis Block. The New Era of Decentralization. Not your keys, not your mind.";
 
-/// Creates the Genesis Block with pre-defined metadata, transaction, and hardcoded timestamp.
+/// Creates the Genesis Block described by a [`ChainSpec`].
+///
+/// The block opens with a zero-value coinbase whose message embeds the spec's
+/// network name and id, so each network has its own genesis hash; each genesis
+/// allocation then becomes one more coinbase transaction.
 ///
 /// # Returns
 /// - [`Block`] — A fully mined and immutable genesis block.
 ///
 /// # Example
 /// ```
-/// let genesis = create_genesis_block();
+/// let genesis = create_genesis_block(&ChainSpec::mainnet());
 /// println!("Genesis block hash: {}", genesis.hash);
 /// ```
-pub fn create_genesis_block() -> Block {
-    // Create the genesis transaction
-    let tx = Transaction {
-        from: String::from("0x0"),  // No sender
-        to: GENESIS_RECEIVER.to_string(),
-        amount: GENESIS_REWARD,
-        message: GENESIS_MESSAGE.to_string(),
-    };
+pub fn create_genesis_block(spec: &ChainSpec) -> Block {
+    // `ChainSpec::validate` rejects timestamps chrono cannot represent.
+    let tx_timestamp = Utc
+        .timestamp_opt(spec.genesis.timestamp, 0)
+        .single()
+        .map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .unwrap_or_default();
 
-    let transactions = vec![tx.clone()];
-    let merkle_root = calculate_merkle_root(&transactions);
+    // Create the genesis coinbase transactions
+    let genesis_coinbase = |to: &str, amount: u64, message: String| {
+        let mut tx = Transaction::coinbase(0, to, amount, &tx_timestamp);
+        tx.message = message;
+        tx.hash = tx.compute_hash();
+        tx
+    };
+    let identity = format!("{} | network {} ({})", spec.genesis.message, spec.name, spec.network_id);
+    let transactions: Vec<Transaction> = std::iter::once(genesis_coinbase(COINBASE_SENDER, 0, identity.clone()))
+        .chain(spec.genesis.allocations.iter().enumerate().map(|(i, allocation)| {
+            genesis_coinbase(&allocation.address, allocation.amount, format!("{} | allocation {}", identity, i))
+        }))
+        .collect();
 
-    let timestamp: DateTime<Utc> = Utc.datetime_from_str(GENESIS_TIMESTAMP, "%Y-%m-%dT%H:%M:%SZ")
-        .expect("Invalid GENESIS_TIMESTAMP format");
+    let merkle_root = Block::calculate_merkle_root(&transactions);
 
     let mut block = Block {
+        version: BLOCK_VERSION,
         index: 0,
-        timestamp: timestamp.to_rfc3339(),
+        timestamp: spec.genesis.timestamp,
         previous_hash: "0".repeat(64),
         hash: String::new(),
         merkle_root,
+        state_root: "0".repeat(64),
+        seed: "0".repeat(64),
         nonce: 0,
-        difficulty: GENESIS_DIFFICULTY,
+        bits: spec.genesis.bits,
         transactions,
+        signature: None,
+        seed_proof: None,
     };
 
+    // Commit to the allocations; an invalid spec is caught when the chain applies the block.
+    let mut state = WorldState::new();
+    if state.apply_block(&block, spec).is_ok() {
+        block.state_root = state.state_root();
+    }
+
     block.mine(); // Perform Proof-of-Work
     block
 }
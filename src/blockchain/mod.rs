@@ -4,14 +4,41 @@
 //! including blocks, the chain itself, and genesis initialization.
 //!
 //! Modules:
+//! - `archive`: Streaming binary chain export and import
 //! - `block`: Defines the Block structure, Merkle tree, and hashing logic
 //! - `chain`: Manages the blockchain state, validation, and block insertion
+//! - `emission`: Block subsidy, halving schedule and the 21M OCOS supply cap
+//! - `error`: `ChainError`, the typed reasons a block or chain operation fails
 //! - `genesis`: Builds and returns the initial genesis block
+//! - `snapshot`: Chunked state snapshots verified against a header's state root
+//! - `spec`: Per-network chain specification (genesis, consensus parameters, forks)
+//! - `state`: Account balances and nonces, updated as blocks are applied
+//! - `template`: Block templates and work submission for external miners
+//! - `tree`: All known blocks and fork choice by cumulative (engine-weighed) work
+//! - `undo`: Per-block undo journals for disconnecting blocks
+//! - `validation`: Stateless stages of the block acceptance pipeline
 
+pub mod archive;
 pub mod block;
 pub mod chain;
+pub mod emission;
+pub mod error;
 pub mod genesis;
+pub mod snapshot;
+pub mod spec;
+pub mod state;
+pub mod template;
+pub mod tree;
+pub mod undo;
+pub mod validation;
 
+pub use archive::{ArchiveReader, ArchiveWriter, ImportSummary};
 pub use block::{Block, BlockHeader, BlockHash};
-pub use chain::{Blockchain, ChainError, ChainResult};
+pub use chain::{AddressHistory, Blockchain, ChainError, ChainResult};
 pub use genesis::create_genesis_block;
+pub use snapshot::{SnapshotCache, SnapshotChunk, SnapshotManifest, SnapshotRestorer, StateSnapshot};
+pub use spec::ChainSpec;
+pub use state::{Account, AccountProof, WorldState};
+pub use template::{BlockTemplate, TemplateStore, WorkSubmission};
+pub use tree::{BlockTree, TreeEntry};
+pub use undo::BlockUndo;
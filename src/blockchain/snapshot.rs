@@ -0,0 +1,281 @@
+//! # State Snapshots
+//! Chunked exports of the world state at a given height, for fast sync.
+//!
+//! A snapshot is a [`SnapshotManifest`] plus a list of [`SnapshotChunk`]s. The
+//! manifest names the anchor block (the block whose post-state was exported),
+//! carries its header and enough ancestor headers for the consensus rules
+//! (retargeting, median time past) to work on the blocks that follow, and lists
+//! the hash of every chunk. Each chunk holds a run of accounts in ascending
+//! address order.
+//!
+//! A [`SnapshotRestorer`] accepts chunks one at a time (in any order, e.g. from
+//! different peers), rejects any chunk whose hash is not in the manifest, and
+//! finally rebuilds the state and checks it against the `state_root` committed
+//! in the anchor header. Only the anchor hash has to be trusted; everything else
+//! is verified against it.
+//!
+//! A node serving snapshots keeps the ones it built in a [`SnapshotCache`], so
+//! a peer fetching chunk after chunk does not make it export the state each time.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::block::{BlockHash, BlockHeader};
+use crate::blockchain::state::{account_leaf, state_root_of, Account, WorldState};
+use crate::crypto::hash::double_sha256;
+
+/// Number of accounts per snapshot chunk.
+pub const SNAPSHOT_CHUNK_ACCOUNTS: usize = 4_096;
+
+/// Number of recently served snapshots a [`SnapshotCache`] keeps.
+pub const MAX_CACHED_SNAPSHOTS: usize = 4;
+
+/// Describes a snapshot and commits to each of its chunks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    /// Height of the anchor block.
+    pub height: u64,
+
+    /// Hash of the anchor block.
+    pub block_hash: BlockHash,
+
+    /// State root committed in the anchor header.
+    pub state_root: String,
+
+    /// Total number of accounts across all chunks.
+    pub account_count: u64,
+
+    /// Hash of every chunk, by chunk index.
+    pub chunk_hashes: Vec<String>,
+
+    /// Consecutive headers ending with the anchor, oldest first.
+    pub headers: Vec<BlockHeader>,
+}
+
+impl SnapshotManifest {
+    /// Returns the anchor block header.
+    pub fn anchor(&self) -> Option<&BlockHeader> {
+        self.headers.last()
+    }
+
+    /// Checks the manifest's internal consistency: the headers link up and end at
+    /// the anchor, whose state root is the manifest's. Header seals depend on the
+    /// network's consensus engine and are checked when the chain is built from the
+    /// snapshot.
+    pub fn verify(&self) -> Result<(), String> {
+        let anchor = self
+            .anchor()
+            .ok_or_else(|| "Snapshot manifest has no headers.".to_string())?;
+        if anchor.hash() != self.block_hash || anchor.index != self.height {
+            return Err(format!(
+                "Snapshot anchor does not match block {} at height {}.",
+                self.block_hash, self.height
+            ));
+        }
+        if hex::encode(anchor.state_root) != self.state_root {
+            return Err("Snapshot state root does not match the anchor header.".into());
+        }
+
+        for pair in self.headers.windows(2) {
+            let (parent, child) = (&pair[0], &pair[1]);
+            if child.index != parent.index + 1 || hex::encode(child.previous_hash) != parent.hash() {
+                return Err(format!("Snapshot header {} does not link to its parent.", child.index));
+            }
+        }
+
+        let expected_chunks = (self.account_count as usize).div_ceil(SNAPSHOT_CHUNK_ACCOUNTS);
+        if self.chunk_hashes.len() != expected_chunks {
+            return Err(format!(
+                "Snapshot lists {} chunks for {} accounts, expected {}.",
+                self.chunk_hashes.len(),
+                self.account_count,
+                expected_chunks
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A run of accounts, in ascending address order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotChunk {
+    /// Position of the chunk in the snapshot.
+    pub index: u32,
+    pub accounts: Vec<(String, Account)>,
+}
+
+impl SnapshotChunk {
+    /// Hash of the chunk: double SHA-256 over its index and account leaves.
+    pub fn hash(&self) -> String {
+        let mut bytes = self.index.to_le_bytes().to_vec();
+        for (address, account) in &self.accounts {
+            bytes.extend_from_slice(&account_leaf(address, account));
+        }
+        hex::encode(double_sha256(&bytes))
+    }
+}
+
+/// A complete snapshot: the manifest and every chunk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub manifest: SnapshotManifest,
+    pub chunks: Vec<SnapshotChunk>,
+}
+
+impl StateSnapshot {
+    /// Exports `state`, the state after the last of `headers` (the anchor).
+    pub fn create(state: &WorldState, headers: Vec<BlockHeader>) -> Result<Self, String> {
+        let anchor = headers
+            .last()
+            .ok_or_else(|| "A snapshot needs at least the anchor header.".to_string())?;
+        let state_root = state.state_root();
+        if hex::encode(anchor.state_root) != state_root {
+            return Err(format!("State does not match the state root of block {}.", anchor.index));
+        }
+
+        let accounts = state.sorted_accounts();
+        let chunks: Vec<SnapshotChunk> = accounts
+            .chunks(SNAPSHOT_CHUNK_ACCOUNTS)
+            .enumerate()
+            .map(|(index, run)| SnapshotChunk {
+                index: index as u32,
+                accounts: run
+                    .iter()
+                    .map(|(address, account)| (address.to_string(), (*account).clone()))
+                    .collect(),
+            })
+            .collect();
+
+        let manifest = SnapshotManifest {
+            height: anchor.index,
+            block_hash: anchor.hash(),
+            state_root,
+            account_count: accounts.len() as u64,
+            chunk_hashes: chunks.iter().map(SnapshotChunk::hash).collect(),
+            headers,
+        };
+        Ok(Self { manifest, chunks })
+    }
+
+    /// Verifies every chunk against the manifest and rebuilds the state.
+    pub fn restore(&self) -> Result<WorldState, String> {
+        let mut restorer = SnapshotRestorer::new(self.manifest.clone())?;
+        for chunk in &self.chunks {
+            restorer.add_chunk(chunk.clone())?;
+        }
+        restorer.finish()
+    }
+}
+
+/// Collects the chunks of a snapshot and rebuilds the state once all are in.
+#[derive(Debug, Clone)]
+pub struct SnapshotRestorer {
+    manifest: SnapshotManifest,
+    chunks: BTreeMap<u32, SnapshotChunk>,
+}
+
+impl SnapshotRestorer {
+    /// Starts a restore; fails if the manifest is inconsistent (see [`SnapshotManifest::verify`]).
+    pub fn new(manifest: SnapshotManifest) -> Result<Self, String> {
+        manifest.verify()?;
+        Ok(Self {
+            manifest,
+            chunks: BTreeMap::new(),
+        })
+    }
+
+    /// Returns the manifest being restored.
+    pub fn manifest(&self) -> &SnapshotManifest {
+        &self.manifest
+    }
+
+    /// Adds a chunk after checking its hash against the manifest.
+    pub fn add_chunk(&mut self, chunk: SnapshotChunk) -> Result<(), String> {
+        let expected = self
+            .manifest
+            .chunk_hashes
+            .get(chunk.index as usize)
+            .ok_or_else(|| format!("Snapshot has no chunk {}.", chunk.index))?;
+        if chunk.hash() != *expected {
+            return Err(format!("Snapshot chunk {} does not match the manifest.", chunk.index));
+        }
+        self.chunks.insert(chunk.index, chunk);
+        Ok(())
+    }
+
+    /// Indexes of the chunks not received yet.
+    pub fn missing_chunks(&self) -> Vec<u32> {
+        (0..self.manifest.chunk_hashes.len() as u32)
+            .filter(|index| !self.chunks.contains_key(index))
+            .collect()
+    }
+
+    /// Returns true once every chunk has been received.
+    pub fn is_complete(&self) -> bool {
+        self.chunks.len() == self.manifest.chunk_hashes.len()
+    }
+
+    /// Rebuilds the state and checks it against the anchor's state root.
+    pub fn finish(self) -> Result<WorldState, String> {
+        if !self.is_complete() {
+            return Err(format!("Snapshot is missing chunks {:?}.", self.missing_chunks()));
+        }
+
+        let accounts: Vec<(&str, &Account)> = self
+            .chunks
+            .values()
+            .flat_map(|chunk| chunk.accounts.iter())
+            .map(|(address, account)| (address.as_str(), account))
+            .collect();
+        if accounts.len() as u64 != self.manifest.account_count {
+            return Err(format!(
+                "Snapshot holds {} accounts, the manifest lists {}.",
+                accounts.len(),
+                self.manifest.account_count
+            ));
+        }
+        if accounts.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err("Snapshot accounts are not in strictly ascending address order.".into());
+        }
+        if state_root_of(accounts.iter().copied()) != self.manifest.state_root {
+            return Err("Restored state does not match the snapshot state root.".into());
+        }
+
+        let mut state = WorldState::new();
+        for (address, account) in accounts {
+            state.set_account(address, account.clone());
+        }
+        Ok(state)
+    }
+}
+
+/// Recently built snapshots by anchor hash, oldest evicted first. Keying by hash
+/// means a snapshot of a block that was reorganized away is never served again.
+#[derive(Debug, Default)]
+pub struct SnapshotCache {
+    snapshots: VecDeque<StateSnapshot>,
+}
+
+impl SnapshotCache {
+    /// Creates an empty snapshot cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the cached snapshot anchored at `block_hash`, if any.
+    pub fn get(&self, block_hash: &str) -> Option<&StateSnapshot> {
+        self.snapshots.iter().find(|s| s.manifest.block_hash == block_hash)
+    }
+
+    /// Caches a snapshot, evicting the oldest one when full.
+    pub fn insert(&mut self, snapshot: StateSnapshot) {
+        if self.get(&snapshot.manifest.block_hash).is_some() {
+            return;
+        }
+        while self.snapshots.len() >= MAX_CACHED_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+}
@@ -0,0 +1,469 @@
+//! # Chain Specification
+//! A [`ChainSpec`] describes one OCOS network: its identity, genesis block,
+//! consensus parameters, scheduled fork heights and pinned blocks.
+//!
+//! Built-in presets exist for `mainnet`, `testnet` and `devnet`; private networks
+//! can be described in a JSON file and loaded with [`ChainSpec::from_json_file`].
+//!
+//! # Example (JSON)
+//! ```json
+//! {
+//!   "name": "acme-devnet",
+//!   "network_id": 90210,
+//!   "genesis": {
+//!     "timestamp": 1752194820,
+//!     "bits": 545259519,
+//!     "message": "ACME private devnet",
+//!     "allocations": [{ "address": "OCOS...", "amount": 100000000000 }]
+//!   },
+//!   "consensus": { "engine": "pow", "target_block_time": 5, "retarget_interval": 20 },
+//!   "forks": { "example_fork": 1000 },
+//!   "checkpoints": { "5000": "00000a3f...c1" },
+//!   "assume_valid": { "height": 9000, "hash": "000001b2...7e" }
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::emission::{HALVING_INTERVAL, INITIAL_SUBSIDY, MAX_SUPPLY};
+use crate::blockchain::genesis::{GENESIS_BITS, GENESIS_MESSAGE, GENESIS_TIME};
+use crate::consensus::ConsensusKind;
+use crate::consensus::finality::FINALITY_INTERVAL;
+use crate::consensus::difficulty::{bits_to_target, MAX_ADJUSTMENT_FACTOR, POW_LIMIT_BITS, RETARGET_INTERVAL, TARGET_BLOCK_TIME};
+use crate::consensus::pos::{MIN_STAKE, UNBONDING_PERIOD};
+use crate::consensus::slashing::{DOWNTIME_SLASH_PERCENT, DOWNTIME_THRESHOLD, EQUIVOCATION_SLASH_PERCENT};
+use crate::consensus::timestamp::{MAX_FUTURE_DRIFT, MEDIAN_TIME_SPAN};
+
+/// An initial balance created by the genesis block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisAllocation {
+    pub address: String,
+    pub amount: u64,
+}
+
+/// Contents of the genesis block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisSpec {
+    /// Genesis block time, in UNIX seconds.
+    pub timestamp: i64,
+
+    /// Compact proof-of-work target of the genesis block.
+    pub bits: u32,
+
+    /// Message embedded in the genesis coinbase.
+    pub message: String,
+
+    /// Initial balances; each becomes one coinbase output of the genesis block.
+    #[serde(default)]
+    pub allocations: Vec<GenesisAllocation>,
+
+    /// Initial validators and their stakes, for a `pos` or `hybrid` network; the
+    /// stakes are locked in the genesis state and count toward the genesis supply.
+    #[serde(default)]
+    pub validators: Vec<GenesisAllocation>,
+}
+
+/// Consensus parameters. Fields omitted from a JSON spec take mainnet values.
+///
+/// Proof-of-stake proposal rounds follow block time, so a `pos` or `hybrid`
+/// network must keep `max_future_drift` below one `target_block_time`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConsensusParams {
+    /// Consensus engine the network runs (see [`crate::consensus`]).
+    pub engine: ConsensusKind,
+
+    /// Target spacing between blocks, in seconds.
+    pub target_block_time: i64,
+
+    /// Number of blocks between difficulty adjustments.
+    pub retarget_interval: u64,
+
+    /// Largest factor by which the target may change in one adjustment.
+    pub max_adjustment_factor: i64,
+
+    /// Easiest compact target any block may use.
+    pub pow_limit_bits: u32,
+
+    /// Subsidy of the first block after genesis, in base units.
+    pub initial_subsidy: u64,
+
+    /// Number of blocks between subsidy halvings.
+    pub halving_interval: u64,
+
+    /// Hard cap on total issuance (genesis allocations included), in base units.
+    pub max_supply: u64,
+
+    /// Number of ancestors used for the median-time-past rule.
+    pub median_time_span: usize,
+
+    /// Maximum seconds a block may be ahead of local time.
+    pub max_future_drift: i64,
+
+    /// Number of blocks between finality checkpoints (hybrid engine only).
+    pub finality_interval: u64,
+
+    /// Number of blocks unstaked funds stay locked before they are spendable;
+    /// equivocation evidence is admissible for as long.
+    pub unbonding_period: u64,
+
+    /// Share of stake and unbonding funds burned for double-signing, in percent
+    /// (see [`crate::consensus::slashing`]).
+    pub equivocation_slash_percent: u64,
+
+    /// Number of proposals in a row a validator may miss before it is penalized
+    /// (zero disables the penalty).
+    pub downtime_threshold: u64,
+
+    /// Share of stake burned for downtime, in percent.
+    pub downtime_slash_percent: u64,
+}
+
+impl Default for ConsensusParams {
+    fn default() -> Self {
+        Self {
+            engine: ConsensusKind::Pow,
+            target_block_time: TARGET_BLOCK_TIME,
+            retarget_interval: RETARGET_INTERVAL,
+            max_adjustment_factor: MAX_ADJUSTMENT_FACTOR,
+            pow_limit_bits: POW_LIMIT_BITS,
+            initial_subsidy: INITIAL_SUBSIDY,
+            halving_interval: HALVING_INTERVAL,
+            max_supply: MAX_SUPPLY,
+            median_time_span: MEDIAN_TIME_SPAN,
+            max_future_drift: MAX_FUTURE_DRIFT,
+            finality_interval: FINALITY_INTERVAL,
+            unbonding_period: UNBONDING_PERIOD,
+            equivocation_slash_percent: EQUIVOCATION_SLASH_PERCENT,
+            downtime_threshold: DOWNTIME_THRESHOLD,
+            downtime_slash_percent: DOWNTIME_SLASH_PERCENT,
+        }
+    }
+}
+
+/// A block whose ancestors are trusted to carry valid transaction signatures.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssumeValid {
+    pub height: u64,
+    pub hash: String,
+}
+
+/// Full description of an OCOS network.
+///
+/// The name and network id are written into the genesis coinbase, so two specs
+/// that differ only in identity still produce different genesis hashes (and hence
+/// entirely separate chains).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainSpec {
+    /// Human-readable network name (e.g. "mainnet").
+    pub name: String,
+
+    /// Numeric network identifier, unique per network.
+    pub network_id: u32,
+
+    pub genesis: GenesisSpec,
+
+    #[serde(default)]
+    pub consensus: ConsensusParams,
+
+    /// Activation heights of named protocol upgrades.
+    #[serde(default)]
+    pub forks: BTreeMap<String, u64>,
+
+    /// Known-good block hashes by height; branches that conflict are rejected.
+    #[serde(default)]
+    pub checkpoints: BTreeMap<u64, String>,
+
+    /// Block whose ancestors' transaction signatures are not re-checked (see
+    /// [`Blockchain::validate_block`](crate::blockchain::Blockchain::validate_block)).
+    #[serde(default)]
+    pub assume_valid: Option<AssumeValid>,
+}
+
+impl ChainSpec {
+    /// The OCOS main network.
+    pub fn mainnet() -> Self {
+        Self {
+            name: "mainnet".to_string(),
+            network_id: 47,
+            genesis: GenesisSpec {
+                timestamp: GENESIS_TIME,
+                bits: GENESIS_BITS,
+                message: GENESIS_MESSAGE.to_string(),
+                allocations: Vec::new(),
+                validators: Vec::new(),
+            },
+            consensus: ConsensusParams::default(),
+            forks: BTreeMap::new(),
+            checkpoints: BTreeMap::new(),
+            assume_valid: None,
+        }
+    }
+
+    /// The public test network: same rules as mainnet, easier genesis target.
+    pub fn testnet() -> Self {
+        let mut spec = Self::mainnet();
+        spec.name = "testnet".to_string();
+        spec.network_id = 4747;
+        spec.genesis.bits = POW_LIMIT_BITS;
+        spec.genesis.message = "OCOS Testnet Genesis Block.".to_string();
+        spec
+    }
+
+    /// A local development network with near-instant mining and fast retargeting.
+    pub fn devnet() -> Self {
+        let mut spec = Self::mainnet();
+        spec.name = "devnet".to_string();
+        spec.network_id = 474747;
+        spec.genesis.bits = 0x207fffff;
+        spec.genesis.message = "OCOS Devnet Genesis Block.".to_string();
+        spec.consensus.pow_limit_bits = 0x207fffff;
+        spec.consensus.target_block_time = 5;
+        spec.consensus.retarget_interval = 20;
+        // Near-instant mining runs timestamps ahead of the clock through the MTP rule.
+        spec.consensus.max_future_drift = 10 * 60;
+        spec
+    }
+
+    /// Returns the built-in preset with the given name, if any.
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "mainnet" => Some(Self::mainnet()),
+            "testnet" => Some(Self::testnet()),
+            "devnet" => Some(Self::devnet()),
+            _ => None,
+        }
+    }
+
+    /// Parses and validates a chain spec from JSON.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let spec: ChainSpec =
+            serde_json::from_str(json).map_err(|e| format!("Invalid chain spec: {}", e))?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Loads and validates a chain spec from a JSON file.
+    pub fn from_json_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read chain spec {}: {}", path.display(), e))?;
+        Self::from_json(&json)
+    }
+
+    /// Resolves a preset name or, failing that, a path to a JSON spec file.
+    pub fn load(name_or_path: &str) -> Result<Self, String> {
+        match Self::preset(name_or_path) {
+            Some(spec) => Ok(spec),
+            None => Self::from_json_file(name_or_path),
+        }
+    }
+
+    /// Serializes the spec as pretty-printed JSON (e.g. to bootstrap a new devnet file).
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Total coins created by the genesis block, genesis validator stakes included.
+    pub fn genesis_supply(&self) -> u64 {
+        self.genesis
+            .allocations
+            .iter()
+            .chain(&self.genesis.validators)
+            .fold(0u64, |acc, a| acc.saturating_add(a.amount))
+    }
+
+    /// Returns true if the named fork is active at `height`.
+    pub fn is_fork_active(&self, fork: &str, height: u64) -> bool {
+        self.forks.get(fork).is_some_and(|activation| height >= *activation)
+    }
+
+    /// Hash a block at `height` must have: a checkpoint, or the assume-valid block
+    /// (which is enforced like a checkpoint).
+    pub fn checkpoint_at(&self, height: u64) -> Option<&str> {
+        match &self.assume_valid {
+            Some(av) if av.height == height => Some(av.hash.as_str()),
+            _ => self.checkpoints.get(&height).map(String::as_str),
+        }
+    }
+
+    /// Highest checkpoint (assume-valid block included) at or below `height`.
+    pub fn last_checkpoint(&self, height: u64) -> Option<(u64, &str)> {
+        let checkpoint = self
+            .checkpoints
+            .range(..=height)
+            .next_back()
+            .map(|(h, hash)| (*h, hash.as_str()));
+        let assume_valid = self
+            .assume_valid
+            .as_ref()
+            .filter(|av| av.height <= height)
+            .map(|av| (av.height, av.hash.as_str()));
+        checkpoint.into_iter().chain(assume_valid).max_by_key(|(h, _)| *h)
+    }
+
+    /// Returns true if the block at `height` is a finality checkpoint (every
+    /// `finality_interval` blocks after genesis).
+    pub fn is_finality_height(&self, height: u64) -> bool {
+        let interval = self.consensus.finality_interval;
+        height > 0 && interval > 0 && height.is_multiple_of(interval)
+    }
+
+    /// Checks that the parameters are internally consistent.
+    pub fn validate(&self) -> Result<(), String> {
+        let c = &self.consensus;
+        if self.name.trim().is_empty() {
+            return Err("Chain spec name is empty.".into());
+        }
+        if Utc.timestamp_opt(self.genesis.timestamp, 0).single().is_none() {
+            return Err("Genesis timestamp is out of range.".into());
+        }
+        if self.genesis_supply() > c.max_supply {
+            return Err("Genesis allocations exceed the maximum supply.".into());
+        }
+        if c.target_block_time <= 0 || c.max_future_drift < 0 {
+            return Err("Block time parameters must be positive.".into());
+        }
+        if c.retarget_interval < 2 || c.max_adjustment_factor < 1 {
+            return Err("Retarget interval must be at least 2 and adjustment factor at least 1.".into());
+        }
+        if c.halving_interval == 0 || c.median_time_span == 0 {
+            return Err("Halving interval and median time span must be non-zero.".into());
+        }
+        match (bits_to_target(self.genesis.bits), bits_to_target(c.pow_limit_bits)) {
+            (Some(genesis), Some(limit)) if genesis > limit => {
+                return Err("Genesis bits are easier than the proof-of-work limit.".into());
+            }
+            (Some(_), Some(_)) => {}
+            _ => return Err("Genesis bits and proof-of-work limit must be valid compact targets.".into()),
+        }
+        if matches!(c.engine, ConsensusKind::Pos | ConsensusKind::Hybrid)
+            && !self.genesis.validators.iter().any(|v| v.amount >= MIN_STAKE)
+        {
+            return Err(format!("A {} network needs a genesis validator staking at least {}.", c.engine, MIN_STAKE));
+        }
+        if matches!(c.engine, ConsensusKind::Pos | ConsensusKind::Hybrid) && c.max_future_drift >= c.target_block_time {
+            return Err("Future drift must stay below one block time on a proof-of-stake network.".into());
+        }
+        if c.engine == ConsensusKind::Hybrid && c.finality_interval == 0 {
+            return Err("Finality interval must be non-zero.".into());
+        }
+        if matches!(c.engine, ConsensusKind::Pos | ConsensusKind::Hybrid) && c.unbonding_period == 0 {
+            return Err("Unbonding period must be non-zero.".into());
+        }
+        if c.equivocation_slash_percent > 100 || c.downtime_slash_percent > 100 {
+            return Err("Slash percentages must not exceed 100.".into());
+        }
+        let is_hash = |hash: &str| hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+        let pinned = self
+            .checkpoints
+            .iter()
+            .map(|(height, hash)| (*height, hash))
+            .chain(self.assume_valid.iter().map(|av| (av.height, &av.hash)));
+        for (height, hash) in pinned {
+            if !is_hash(hash) {
+                return Err(format!("Checkpoint at height {} is not a lowercase hex block hash.", height));
+            }
+            if self.checkpoints.get(&height).is_some_and(|other| other != hash) {
+                return Err(format!("Assume-valid block conflicts with the checkpoint at height {}.", height));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        Self::mainnet()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn staked(engine: ConsensusKind) -> ChainSpec {
+        let mut spec = ChainSpec::devnet();
+        spec.consensus.engine = engine;
+        spec.consensus.max_future_drift = 4;
+        spec.genesis.validators.push(GenesisAllocation { address: "validator".to_string(), amount: MIN_STAKE });
+        spec
+    }
+
+    #[test]
+    fn presets_are_valid() {
+        for name in ["mainnet", "testnet", "devnet"] {
+            let spec = ChainSpec::preset(name).unwrap();
+            assert_eq!(spec.validate(), Ok(()), "{}", name);
+        }
+        assert!(ChainSpec::preset("nonet").is_none());
+
+        let mainnet = ChainSpec::mainnet().consensus;
+        assert!(mainnet.max_future_drift < mainnet.target_block_time);
+    }
+
+    #[test]
+    fn rejects_unrepresentable_genesis_times() {
+        let mut spec = ChainSpec::devnet();
+        spec.genesis.timestamp = i64::MAX;
+        assert!(spec.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_genesis_easier_than_pow_limit() {
+        let mut spec = ChainSpec::mainnet();
+        spec.genesis.bits = spec.consensus.pow_limit_bits;
+        assert_eq!(spec.validate(), Ok(()));
+        spec.genesis.bits = 0x207fffff;
+        assert!(spec.validate().is_err());
+    }
+
+    #[test]
+    fn loads_json_with_default_consensus_parameters() {
+        let devnet = ChainSpec::devnet();
+        assert_eq!(ChainSpec::from_json(&devnet.to_json()), Ok(devnet));
+
+        let json = r#"{
+            "name": "acme-devnet",
+            "network_id": 90210,
+            "genesis": {
+                "timestamp": 1752194820,
+                "bits": 545259519,
+                "message": "ACME private devnet",
+                "allocations": [{ "address": "acme", "amount": 100 }]
+            },
+            "consensus": { "target_block_time": 5, "pow_limit_bits": 545259519 },
+            "forks": { "example_fork": 1000 }
+        }"#;
+        let spec = ChainSpec::from_json(json).unwrap();
+        assert_eq!(spec.consensus.target_block_time, 5);
+        assert_eq!(spec.consensus.retarget_interval, RETARGET_INTERVAL);
+        assert_eq!(spec.genesis_supply(), 100);
+        assert!(!spec.is_fork_active("example_fork", 999));
+        assert!(spec.is_fork_active("example_fork", 1000));
+        assert!(!spec.is_fork_active("unknown_fork", u64::MAX));
+
+        assert!(ChainSpec::from_json(&json.replace("\"target_block_time\": 5", "\"retarget_interval\": 1")).is_err());
+        assert!(ChainSpec::from_json(&json.replace("545259519", "0")).is_err());
+        assert!(ChainSpec::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn rejects_drift_of_a_block_time_under_stake() {
+        for engine in [ConsensusKind::Pos, ConsensusKind::Hybrid] {
+            let mut spec = staked(engine);
+            assert_eq!(spec.validate(), Ok(()));
+            spec.consensus.max_future_drift = spec.consensus.target_block_time;
+            assert!(spec.validate().is_err());
+        }
+
+        let mut pow = ChainSpec::devnet();
+        pow.consensus.max_future_drift = pow.consensus.target_block_time;
+        assert_eq!(pow.validate(), Ok(()));
+    }
+}
@@ -0,0 +1,888 @@
+//! # State Module
+//! This module tracks the world state of the OCOS blockchain: the balance,
+//! nonce and stake of every account, derived by applying each block's
+//! transactions in order and committed to by a sparse Merkle root.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::block::Block;
+use crate::blockchain::emission::block_subsidy;
+use crate::blockchain::spec::ChainSpec;
+use crate::consensus::slashing::slash_amount;
+use crate::crypto::smt::{smt_key, verify_smt_proof, SmtHash, SparseMerkleProof, SparseMerkleTree};
+use crate::transaction::tx::{Transaction, TxKind};
+
+/// Unstaked funds waiting for the end of their unbonding period.
+///
+/// They are released back into the balance at the start of the block
+/// `unbonding_period` blocks after the unstake (see
+/// [`ConsensusParams`](crate::blockchain::spec::ConsensusParams)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Unbonding {
+    pub amount: u64,
+
+    /// Height of the first block in which the funds are spendable again.
+    pub release_height: u64,
+}
+
+/// Balance, nonce and stake of a single account.
+///
+/// A `stake` transaction locks part of the balance; an `unstake` transaction
+/// moves stake into an [`Unbonding`] entry. The validator set is the
+/// [`StakingPool`](crate::consensus::pos::StakingPool) rebuilt from these stakes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Account {
+    /// Spendable balance.
+    pub balance: u64,
+
+    /// Number of transactions sent from this account (next expected nonce).
+    pub nonce: u64,
+
+    /// Balance locked as validator stake.
+    #[serde(default)]
+    pub staked: u64,
+
+    /// Unstaked funds not yet released, in the order they were unstaked.
+    #[serde(default)]
+    pub unbonding: Vec<Unbonding>,
+
+    /// Proposal rounds missed in a row since the account last proposed a block.
+    #[serde(default)]
+    pub missed_proposals: u64,
+
+    /// Set once the account is slashed for equivocation (see
+    /// [`crate::consensus::slashing`]); it never validates again.
+    #[serde(default)]
+    pub tombstoned: bool,
+}
+
+impl Account {
+    /// Total funds of the account: spendable, staked and unbonding.
+    pub fn holdings(&self) -> u64 {
+        self.unbonding
+            .iter()
+            .fold(self.balance.saturating_add(self.staked), |acc, entry| acc.saturating_add(entry.amount))
+    }
+
+    /// Returns true if the account has ever staked: it carries stake, unbonding
+    /// funds, missed proposals or a tombstone.
+    pub fn has_staking_state(&self) -> bool {
+        self.staked > 0 || !self.unbonding.is_empty() || self.missed_proposals > 0 || self.tombstoned
+    }
+
+    /// Burns `percent` of the stake and of every unbonding entry. Returns the
+    /// amount burned.
+    fn slash(&mut self, percent: u64) -> u64 {
+        let mut burned = slash_amount(self.staked, percent);
+        self.staked -= burned;
+        for entry in &mut self.unbonding {
+            let cut = slash_amount(entry.amount, percent);
+            entry.amount -= cut;
+            burned = burned.saturating_add(cut);
+        }
+        burned
+    }
+}
+
+/// Canonical Merkle leaf of an account: the length-prefixed address, then the
+/// balance and nonce as little-endian `u64`s.
+///
+/// An account with staking state appends its stake, the number of unbonding
+/// entries, each entry's amount and release height, its missed proposals and a
+/// tombstone byte; accounts that never staked keep the shorter leaf.
+pub fn account_leaf(address: &str, account: &Account) -> Vec<u8> {
+    let mut leaf = Vec::with_capacity(4 + address.len() + 16);
+    leaf.extend_from_slice(&(address.len() as u32).to_le_bytes());
+    leaf.extend_from_slice(address.as_bytes());
+    leaf.extend_from_slice(&account.balance.to_le_bytes());
+    leaf.extend_from_slice(&account.nonce.to_le_bytes());
+    if account.has_staking_state() {
+        leaf.extend_from_slice(&account.staked.to_le_bytes());
+        leaf.extend_from_slice(&(account.unbonding.len() as u32).to_le_bytes());
+        for entry in &account.unbonding {
+            leaf.extend_from_slice(&entry.amount.to_le_bytes());
+            leaf.extend_from_slice(&entry.release_height.to_le_bytes());
+        }
+        leaf.extend_from_slice(&account.missed_proposals.to_le_bytes());
+        leaf.push(u8::from(account.tombstoned));
+    }
+    leaf
+}
+
+/// Sparse Merkle tree key of an account: the SHA-256 of its address.
+pub fn account_key(address: &str) -> SmtHash {
+    smt_key(address.as_bytes())
+}
+
+/// Value committed for an account in the state tree: the SHA-256 of its leaf.
+pub fn account_value_hash(address: &str, account: &Account) -> SmtHash {
+    smt_key(&account_leaf(address, account))
+}
+
+/// State root of the given accounts, in any order (all zeros if there are none).
+pub fn state_root_of<'a, I>(accounts: I) -> String
+where
+    I: IntoIterator<Item = (&'a str, &'a Account)>,
+{
+    hex::encode(state_tree_of(accounts).root())
+}
+
+/// Builds the state tree of the given accounts, leaving out empty ones.
+fn state_tree_of<'a, I>(accounts: I) -> SparseMerkleTree
+where
+    I: IntoIterator<Item = (&'a str, &'a Account)>,
+{
+    let mut tree = SparseMerkleTree::new();
+    for (address, account) in accounts {
+        if *account != Account::default() {
+            tree.insert(account_key(address), account_value_hash(address, account));
+        }
+    }
+    tree
+}
+
+/// Proof that an account has a given balance and nonce (or has no state) under
+/// a state root, which a light client checks without the rest of the state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountProof {
+    pub address: String,
+
+    /// The proven account, or `None` if the address has no state.
+    pub account: Option<Account>,
+
+    pub proof: SparseMerkleProof,
+}
+
+impl AccountProof {
+    /// Checks the proof against a hex-encoded state root, e.g. from a block header.
+    pub fn verify(&self, state_root: &str) -> bool {
+        let root: SmtHash = match hex::decode(state_root).ok().and_then(|b| b.try_into().ok()) {
+            Some(root) => root,
+            None => return false,
+        };
+        // An all-default account is stored as no account at all.
+        let value_hash = self
+            .account
+            .as_ref()
+            .filter(|account| **account != Account::default())
+            .map(|account| account_value_hash(&self.address, account));
+        verify_smt_proof(&root, &account_key(&self.address), value_hash.as_ref(), &self.proof)
+    }
+
+    /// Balance proven for the address (zero if it has no state).
+    pub fn balance(&self) -> u64 {
+        self.account.as_ref().map(|a| a.balance).unwrap_or(0)
+    }
+}
+
+/// The set of all accounts known to the chain.
+///
+/// The state keeps a [sparse Merkle tree](crate::crypto::smt) over its accounts,
+/// keyed by the SHA-256 of the address, and updates the leaf of every account it
+/// writes, so a block costs only the paths of the accounts it touches.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "StoredState", into = "StoredState")]
+pub struct WorldState {
+    /// Every non-empty account.
+    accounts: HashMap<String, Account>,
+
+    /// State tree over `accounts`, kept in step with every write.
+    tree: SparseMerkleTree,
+
+    /// Previous account of every write since the journal was started, oldest
+    /// first; `None` while no journal is kept.
+    journal: Option<Vec<(String, Option<Account>)>>,
+
+    /// Coins burned by slashing since the journal was started.
+    burned: u64,
+}
+
+/// Serialized form of a [`WorldState`]: the accounts alone, the tree is rebuilt.
+#[derive(Serialize, Deserialize)]
+struct StoredState {
+    accounts: HashMap<String, Account>,
+}
+
+impl From<StoredState> for WorldState {
+    fn from(stored: StoredState) -> Self {
+        let mut state = WorldState::new();
+        for (address, account) in stored.accounts {
+            state.put(&address, Some(account));
+        }
+        state
+    }
+}
+
+impl From<WorldState> for StoredState {
+    fn from(state: WorldState) -> Self {
+        Self { accounts: state.accounts }
+    }
+}
+
+/// States are equal when they hold the same accounts; the tree follows from them.
+impl PartialEq for WorldState {
+    fn eq(&self, other: &Self) -> bool {
+        self.accounts == other.accounts
+    }
+}
+
+impl Eq for WorldState {}
+
+impl WorldState {
+    /// Creates an empty world state.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the account for an address (a default, empty account if unknown).
+    pub fn account(&self, address: &str) -> Account {
+        self.accounts.get(address).cloned().unwrap_or_default()
+    }
+
+    /// Returns the spendable balance of an address.
+    pub fn balance(&self, address: &str) -> u64 {
+        self.accounts.get(address).map(|a| a.balance).unwrap_or(0)
+    }
+
+    /// Returns the next expected nonce of an address.
+    pub fn nonce(&self, address: &str) -> u64 {
+        self.accounts.get(address).map(|a| a.nonce).unwrap_or(0)
+    }
+
+    /// Returns the stake locked by an address.
+    pub fn staked(&self, address: &str) -> u64 {
+        self.accounts.get(address).map(|a| a.staked).unwrap_or(0)
+    }
+
+    /// Addresses whose account differs between this state and `other`, in
+    /// ascending order.
+    pub fn changed_addresses<'a>(&'a self, other: &'a WorldState) -> BTreeSet<&'a str> {
+        self.accounts
+            .iter()
+            .filter(|(address, account)| other.accounts.get(*address) != Some(*account))
+            .chain(
+                other
+                    .accounts
+                    .iter()
+                    .filter(|(address, _)| !self.accounts.contains_key(*address)),
+            )
+            .map(|(address, _)| address.as_str())
+            .collect()
+    }
+
+    /// Iterates over every account with state, in no particular order.
+    pub fn accounts(&self) -> impl Iterator<Item = (&String, &Account)> {
+        self.accounts.iter()
+    }
+
+    /// Returns every non-empty account, in ascending address order.
+    pub fn sorted_accounts(&self) -> Vec<(&str, &Account)> {
+        let mut accounts: Vec<(&str, &Account)> = self
+            .accounts
+            .iter()
+            .map(|(address, account)| (address.as_str(), account))
+            .collect();
+        accounts.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        accounts
+    }
+
+    /// Returns the root committing to every non-empty account; block headers
+    /// carry it and state snapshots are verified against it.
+    ///
+    /// Accounts that are back to their default (zero balance, zero nonce) are
+    /// left out, so a state loaded from storage has the same root as the one it
+    /// was written from.
+    pub fn state_root(&self) -> String {
+        hex::encode(self.tree.root())
+    }
+
+    /// Builds a proof of an address's account (or its absence) against [`Self::state_root`].
+    pub fn prove_account(&self, address: &str) -> AccountProof {
+        AccountProof {
+            address: address.to_string(),
+            account: self.accounts.get(address).cloned(),
+            proof: self.tree.prove(&account_key(address)),
+        }
+    }
+
+    /// Returns the stored account of an address, or `None` if it has no state.
+    pub fn get_account(&self, address: &str) -> Option<&Account> {
+        self.accounts.get(address)
+    }
+
+    /// Replaces the account of an address (used when loading state from storage).
+    pub fn set_account(&mut self, address: &str, account: Account) {
+        self.write(address, Some(account));
+    }
+
+    /// Forgets the account of an address (used when reverting a block).
+    pub fn remove_account(&mut self, address: &str) {
+        self.write(address, None);
+    }
+
+    /// Starts recording the previous account of every address written from now
+    /// on, discarding any journal already kept.
+    ///
+    /// The journal is how a failed block is rolled back and how a block's undo
+    /// journal is built, without copying or comparing the whole state.
+    pub fn start_journal(&mut self) {
+        self.journal = Some(Vec::new());
+        self.burned = 0;
+    }
+
+    /// Returns the coins slashing burned since [`Self::start_journal`]; they are
+    /// gone from every account, and the block's undo journal takes them off the supply.
+    pub fn burned(&self) -> u64 {
+        self.burned
+    }
+
+    /// Stops journaling and returns, for every address written since
+    /// [`Self::start_journal`], its account before the first write (`None` if it
+    /// had no state), in ascending address order.
+    pub fn take_journal(&mut self) -> Vec<(String, Option<Account>)> {
+        let mut before: BTreeMap<String, Option<Account>> = BTreeMap::new();
+        for (address, account) in self.journal.take().unwrap_or_default() {
+            before.entry(address).or_insert(account);
+        }
+        before.into_iter().collect()
+    }
+
+    /// Runs `change`, and restores every account it wrote if it fails.
+    fn atomically<R>(&mut self, change: impl FnOnce(&mut Self) -> Result<R, String>) -> Result<R, String> {
+        let outer = self.journal.is_some();
+        let mark = self.journal.get_or_insert_with(Vec::new).len();
+        let burned = self.burned;
+        let result = change(self);
+        if result.is_err() {
+            self.burned = burned;
+            let written = self.journal.as_mut().map(|journal| journal.split_off(mark)).unwrap_or_default();
+            for (address, account) in written.into_iter().rev() {
+                self.put(&address, account);
+            }
+        }
+        if !outer {
+            self.journal = None;
+        }
+        result
+    }
+
+    /// Stores the account of an address and its leaf in the state tree; `None`
+    /// or an empty account removes both.
+    fn put(&mut self, address: &str, account: Option<Account>) {
+        let key = account_key(address);
+        match account.filter(|account| *account != Account::default()) {
+            Some(account) => {
+                self.tree.insert(key, account_value_hash(address, &account));
+                self.accounts.insert(address.to_string(), account);
+            }
+            None => {
+                self.tree.remove(&key);
+                self.accounts.remove(address);
+            }
+        }
+    }
+
+    /// [`Self::put`], recording the previous account if a journal is kept.
+    fn write(&mut self, address: &str, account: Option<Account>) {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.push((address.to_string(), self.accounts.get(address).cloned()));
+        }
+        self.put(address, account);
+    }
+
+    /// Changes the account of an address (an empty one if it has no state) with
+    /// `change`, then writes it back if it changed.
+    fn update<R>(&mut self, address: &str, change: impl FnOnce(&mut Account) -> R) -> R {
+        let before = self.accounts.get(address);
+        let mut account = before.cloned().unwrap_or_default();
+        let result = change(&mut account);
+        if before != Some(&account) && (before.is_some() || account != Account::default()) {
+            self.write(address, Some(account));
+        }
+        result
+    }
+
+    /// Returns the number of accounts with state.
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    /// Returns true if no account has state yet.
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// Checks that the sender can afford the transaction and that its nonce is the next one expected.
+    ///
+    /// A stake transaction spends its amount like a transfer; unstake and
+    /// evidence transactions need only the fee in the balance, and an unstake
+    /// transaction the amount staked. The evidence itself is signed for one
+    /// network, so it is checked when applied (see [`Self::apply_transaction`]).
+    pub fn check_transaction(&self, tx: &Transaction) -> Result<(), String> {
+        if tx.is_coinbase() {
+            return Err("Coinbase transactions are only valid as the first transaction of a block.".into());
+        }
+
+        let sender = self.account(&tx.from);
+        if tx.nonce != sender.nonce {
+            return Err(format!(
+                "Invalid nonce for {}: expected {}, got {}.",
+                tx.from, sender.nonce, tx.nonce
+            ));
+        }
+        if tx.kind == TxKind::Unstake && sender.staked < tx.amount {
+            return Err(format!(
+                "Insufficient stake for {}: has {}, unstakes {}.",
+                tx.from, sender.staked, tx.amount
+            ));
+        }
+        if tx.kind == TxKind::Stake && sender.tombstoned {
+            return Err(format!("{} was slashed for equivocation and cannot stake again.", tx.from));
+        }
+        let total = match tx.kind {
+            TxKind::Unstake | TxKind::Evidence => tx.fee,
+            _ => tx
+                .amount
+                .checked_add(tx.fee)
+                .ok_or_else(|| "Transaction amount plus fee overflows.".to_string())?,
+        };
+        if sender.balance < total {
+            return Err(format!(
+                "Insufficient balance for {}: has {}, needs {}.",
+                tx.from, sender.balance, total
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks the evidence of an evidence transaction against `network_id` and
+    /// that it accuses `to`, a validator not yet tombstoned that has stake or
+    /// unbonding funds to slash.
+    fn check_evidence(&self, tx: &Transaction, network_id: u32) -> Result<(), String> {
+        let evidence = tx
+            .evidence
+            .as_ref()
+            .ok_or_else(|| "Evidence transaction carries no evidence.".to_string())?;
+        let offender = evidence.verify(network_id).map_err(|e| format!("Invalid equivocation evidence: {}.", e))?;
+        if offender != tx.to {
+            return Err(format!("Evidence accuses {}, not {}.", offender, tx.to));
+        }
+        let account = self.account(&offender);
+        if account.tombstoned {
+            return Err(format!("{} was already slashed for equivocation.", offender));
+        }
+        if account.staked == 0 && account.unbonding.is_empty() {
+            return Err(format!("{} has no stake to slash.", offender));
+        }
+        Ok(())
+    }
+
+    /// Applies a single transaction of the block at `height` to the state.
+    ///
+    /// Equivocation evidence must verify for the spec's network, be from below
+    /// `height` and be at most `unbonding_period` blocks old.
+    pub fn apply_transaction(&mut self, tx: &Transaction, height: u64, spec: &ChainSpec) -> Result<(), String> {
+        self.atomically(|state| state.apply_transaction_unchecked(tx, height, spec))
+    }
+
+    /// [`Self::apply_transaction`], leaving the state partly changed on error.
+    fn apply_transaction_unchecked(&mut self, tx: &Transaction, height: u64, spec: &ChainSpec) -> Result<(), String> {
+        self.check_transaction(tx)?;
+        if let Some(evidence) = tx.evidence.as_ref() {
+            if evidence.height() >= height || height - evidence.height() > spec.consensus.unbonding_period {
+                return Err(format!(
+                    "Evidence from height {} is not admissible at height {}.",
+                    evidence.height(),
+                    height
+                ));
+            }
+            self.check_evidence(tx, spec.network_id)?;
+        }
+        if tx.kind == TxKind::Stake && self.staked(&tx.from).checked_add(tx.amount).is_none() {
+            return Err(format!("Stake overflow for {}.", tx.from));
+        }
+
+        self.update(&tx.from, |sender| {
+            sender.nonce += 1;
+            match tx.kind {
+                TxKind::Stake => {
+                    sender.balance -= tx.amount + tx.fee;
+                    sender.staked += tx.amount;
+                }
+                TxKind::Unstake => {
+                    sender.balance -= tx.fee;
+                    sender.staked -= tx.amount;
+                    sender.unbonding.push(Unbonding {
+                        amount: tx.amount,
+                        release_height: height.saturating_add(spec.consensus.unbonding_period),
+                    });
+                }
+                TxKind::Evidence => sender.balance -= tx.fee,
+                TxKind::Transfer | TxKind::Coinbase => sender.balance -= tx.amount + tx.fee,
+            }
+        });
+        match tx.kind {
+            TxKind::Evidence => {
+                let burned = self.update(&tx.to, |offender| {
+                    offender.tombstoned = true;
+                    offender.slash(spec.consensus.equivocation_slash_percent)
+                });
+                self.burned = self.burned.saturating_add(burned);
+                Ok(())
+            }
+            TxKind::Transfer | TxKind::Coinbase => self.credit(&tx.to, tx.amount),
+            TxKind::Stake | TxKind::Unstake => Ok(()),
+        }
+    }
+
+    /// Records the proposal outcome of `block`, once applied: its proposer (the
+    /// coinbase recipient) has its count of missed proposals reset, and each
+    /// validator in `missed` is charged one. A validator reaching
+    /// `downtime_threshold` misses in a row loses `downtime_slash_percent` of its
+    /// stake and starts counting again.
+    pub fn record_proposal(&mut self, block: &Block, missed: &[String], spec: &ChainSpec) {
+        if let Some(proposer) = block.transactions.first().filter(|tx| tx.is_coinbase()) {
+            self.update(&proposer.to, |account| account.missed_proposals = 0);
+        }
+        let threshold = spec.consensus.downtime_threshold;
+        for address in missed {
+            let burned = self.update(address, |account| {
+                account.missed_proposals += 1;
+                if threshold == 0 || account.missed_proposals < threshold {
+                    return 0;
+                }
+                let burned = slash_amount(account.staked, spec.consensus.downtime_slash_percent);
+                account.staked -= burned;
+                account.missed_proposals = 0;
+                burned
+            });
+            self.burned = self.burned.saturating_add(burned);
+        }
+    }
+
+    /// Moves every unbonding entry released at or before `height` back into its
+    /// account's balance.
+    pub fn release_unbonded(&mut self, height: u64) -> Result<(), String> {
+        self.atomically(|state| state.release_unbonded_unchecked(height))
+    }
+
+    /// [`Self::release_unbonded`], leaving the state partly changed on error.
+    fn release_unbonded_unchecked(&mut self, height: u64) -> Result<(), String> {
+        let mut releasing: Vec<String> = self
+            .accounts
+            .iter()
+            .filter(|(_, account)| account.unbonding.iter().any(|entry| entry.release_height <= height))
+            .map(|(address, _)| address.clone())
+            .collect();
+        releasing.sort_unstable();
+        for address in releasing {
+            self.update(&address, |account| {
+                let (released, pending): (Vec<Unbonding>, Vec<Unbonding>) = std::mem::take(&mut account.unbonding)
+                    .into_iter()
+                    .partition(|entry| entry.release_height <= height);
+                account.unbonding = pending;
+                for entry in released {
+                    account.balance = account
+                        .balance
+                        .checked_add(entry.amount)
+                        .ok_or_else(|| format!("Balance overflow for {}.", address))?;
+                }
+                Ok::<(), String>(())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Applies every transaction of a block, or nothing if any of them fails
+    /// (overdraw, wrong nonce, excessive coinbase).
+    ///
+    /// The block must start with exactly one coinbase, which may claim at most
+    /// the block subsidy (see [`emission`](crate::blockchain::emission)) plus
+    /// the fees of the block's other transactions. The genesis block instead
+    /// holds the spec's allocations; genesis validators start with their stake locked.
+    ///
+    /// Errors describe the failed rule; the caller adds the block index.
+    pub fn apply_block(&mut self, block: &Block, spec: &ChainSpec) -> Result<(), String> {
+        self.atomically(|state| state.apply_block_unchecked(block, spec))
+    }
+
+    /// [`Self::apply_block`], leaving the state partly changed on error.
+    fn apply_block_unchecked(&mut self, block: &Block, spec: &ChainSpec) -> Result<(), String> {
+        if block.index == 0 {
+            return self.apply_genesis(block, spec);
+        }
+
+        let (coinbase, transfers) = block
+            .transactions
+            .split_first()
+            .ok_or_else(|| "Missing coinbase transaction.".to_string())?;
+        if !coinbase.is_coinbase() {
+            return Err("First transaction is not a coinbase.".into());
+        }
+
+        self.release_unbonded_unchecked(block.index)?;
+        let mut fees: u64 = 0;
+
+        for tx in transfers {
+            self.apply_transaction_unchecked(tx, block.index, spec)
+                .map_err(|e| format!("Transaction {} rejected: {}", tx.hash, e))?;
+            fees = fees
+                .checked_add(tx.fee)
+                .ok_or_else(|| "Total fees overflow.".to_string())?;
+        }
+
+        let allowed = block_subsidy(spec, block.index).saturating_add(fees);
+        if coinbase.amount > allowed {
+            return Err(format!(
+                "Coinbase claims {} but subsidy plus fees is {}.",
+                coinbase.amount, allowed
+            ));
+        }
+        self.credit(&coinbase.to, coinbase.amount)
+    }
+
+    /// Applies the genesis block: only coinbase allocations, plus the stakes of the
+    /// spec's genesis validators, up to the spec's genesis supply.
+    fn apply_genesis(&mut self, block: &Block, spec: &ChainSpec) -> Result<(), String> {
+        if block.transactions.is_empty() || block.transactions.iter().any(|tx| !tx.is_coinbase()) {
+            return Err("Genesis block may only contain coinbase allocations.".into());
+        }
+        let total = block
+            .transactions
+            .iter()
+            .map(|tx| tx.amount)
+            .chain(spec.genesis.validators.iter().map(|v| v.amount))
+            .try_fold(0u64, |acc, amount| acc.checked_add(amount))
+            .ok_or_else(|| "Genesis allocations overflow.".to_string())?;
+        if total > spec.genesis_supply() {
+            return Err(format!(
+                "Genesis allocates {} but the chain spec allows {}.",
+                total,
+                spec.genesis_supply()
+            ));
+        }
+
+        for tx in &block.transactions {
+            self.credit(&tx.to, tx.amount)?;
+        }
+        for validator in &spec.genesis.validators {
+            self.update(&validator.address, |account| {
+                account.staked = account
+                    .staked
+                    .checked_add(validator.amount)
+                    .ok_or_else(|| format!("Stake overflow for {}.", validator.address))?;
+                Ok::<(), String>(())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Adds funds to an account, guarding against overflow.
+    fn credit(&mut self, address: &str, amount: u64) -> Result<(), String> {
+        self.update(address, |account| {
+            account.balance = account
+                .balance
+                .checked_add(amount)
+                .ok_or_else(|| format!("Balance overflow for {}.", address))?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::block::BlockSignature;
+    use crate::blockchain::spec::GenesisAllocation;
+    use crate::consensus::slashing::{EquivocationEvidence, SignedHeader};
+    use crate::crypto::keypair::PrivateKey;
+    use crate::wallet::wallet::derive_address;
+
+    fn signed(spec: &ChainSpec, key: &PrivateKey, nonce: u64) -> SignedHeader {
+        let mut block = Block::new(7, "11".repeat(32), Vec::new(), spec.genesis.bits);
+        block.timestamp = 1_700_000_000;
+        block.nonce = nonce;
+        let header = block.header();
+        SignedHeader { signature: BlockSignature::sign(spec.network_id, &header, key), header }
+    }
+
+    fn block(index: u64, claimed: u64, transfers: Vec<Transaction>) -> Block {
+        let coinbase = Transaction::coinbase(index, "miner", claimed, "");
+        let transactions = std::iter::once(coinbase).chain(transfers).collect();
+        Block::new(index, String::new(), transactions, 0)
+    }
+
+    /// The devnet spec with a genesis allocation of 100 to alice.
+    fn allocated() -> ChainSpec {
+        let mut spec = ChainSpec::devnet();
+        spec.genesis.allocations.push(GenesisAllocation { address: "alice".to_string(), amount: 100 });
+        spec
+    }
+
+    fn funded() -> WorldState {
+        let mut state = WorldState::new();
+        let genesis = Block::new(0, String::new(), vec![Transaction::coinbase(0, "alice", 100, "")], 0);
+        state.apply_block(&genesis, &allocated()).unwrap();
+        state
+    }
+
+    #[test]
+    fn applies_transfers_in_order() {
+        let spec = ChainSpec::devnet();
+        let mut state = funded();
+        let txs = vec![
+            Transaction::new("alice", "bob", 30, "").with_fee(2),
+            Transaction::new("alice", "bob", 20, "").with_nonce(1),
+        ];
+        let subsidy = block_subsidy(&spec, 1);
+        state.apply_block(&block(1, subsidy + 2, txs), &spec).unwrap();
+
+        assert_eq!(state.balance("alice"), 48);
+        assert_eq!(state.balance("bob"), 50);
+        assert_eq!(state.balance("miner"), subsidy + 2);
+        assert_eq!(state.nonce("alice"), 2);
+        assert_eq!(state.nonce("bob"), 0);
+    }
+
+    #[test]
+    fn rejects_a_block_atomically() {
+        let spec = ChainSpec::devnet();
+        let mut state = funded();
+
+        let overdraw = vec![
+            Transaction::new("alice", "bob", 60, ""),
+            Transaction::new("alice", "bob", 40, "").with_fee(1).with_nonce(1),
+        ];
+        assert!(state.apply_block(&block(1, 0, overdraw), &spec).is_err());
+
+        let replay = vec![Transaction::new("alice", "bob", 10, "").with_nonce(1)];
+        assert!(state.apply_block(&block(1, 0, replay), &spec).is_err());
+
+        assert_eq!(state.balance("alice"), 100);
+        assert_eq!(state.nonce("alice"), 0);
+        assert_eq!(state.balance("bob"), 0);
+        assert_eq!(state.balance("miner"), 0);
+    }
+
+    #[test]
+    fn caps_the_coinbase_at_subsidy_plus_fees() {
+        let spec = ChainSpec::devnet();
+        let mut state = funded();
+        let subsidy = block_subsidy(&spec, 1);
+        let fee_paying = || vec![Transaction::new("alice", "bob", 10, "").with_fee(3)];
+
+        assert!(state.apply_block(&block(1, subsidy + 4, fee_paying()), &spec).is_err());
+        state.apply_block(&block(1, subsidy + 3, fee_paying()), &spec).unwrap();
+
+        // A coinbase anywhere but first is rejected, and so is a block without one.
+        let late = vec![Transaction::coinbase(2, "miner", 0, "")];
+        assert!(state.apply_block(&block(2, 0, late), &spec).is_err());
+        assert!(state.apply_block(&Block::new(2, String::new(), vec![], 0), &spec).is_err());
+    }
+
+    #[test]
+    fn limits_genesis_to_the_spec_allocations() {
+        let spec = allocated();
+        let allocate = |amount| {
+            let coinbase = Transaction::coinbase(0, "alice", amount, "");
+            WorldState::new().apply_block(&Block::new(0, String::new(), vec![coinbase], 0), &spec)
+        };
+        assert!(allocate(spec.genesis_supply()).is_ok());
+        assert!(allocate(spec.genesis_supply() + 1).is_err());
+
+        let transfer = Transaction::new("alice", "bob", 0, "");
+        let genesis = Block::new(0, String::new(), vec![transfer], 0);
+        assert!(WorldState::new().apply_block(&genesis, &spec).is_err());
+    }
+
+    #[test]
+    fn releases_unbonded_funds_at_their_release_height() {
+        let spec = ChainSpec::devnet();
+        let period = spec.consensus.unbonding_period;
+        let mut state = WorldState::new();
+        state.set_account("alice", Account { balance: 5, staked: 50, ..Account::default() });
+
+        state.apply_transaction(&Transaction::unstake("alice", 20).with_fee(1), 10, &spec).unwrap();
+        let account = state.account("alice");
+        assert_eq!((account.balance, account.staked), (4, 30));
+        assert_eq!(account.unbonding, vec![Unbonding { amount: 20, release_height: 10 + period }]);
+        assert_eq!(account.holdings(), 54);
+
+        // Unbonding funds cannot be unstaked or spent again.
+        let overdraw = Transaction::unstake("alice", 31).with_nonce(1);
+        assert!(state.apply_transaction(&overdraw, 11, &spec).is_err());
+        assert_eq!(state.account("alice"), account);
+
+        state.release_unbonded(10 + period - 1).unwrap();
+        assert_eq!(state.account("alice"), account);
+
+        state.release_unbonded(10 + period).unwrap();
+        let released = state.account("alice");
+        assert_eq!((released.balance, released.staked), (24, 30));
+        assert!(released.unbonding.is_empty());
+        assert_eq!(released.holdings(), account.holdings());
+    }
+
+    #[test]
+    fn burns_stake_and_unbonding_funds_of_an_equivocator() {
+        let spec = ChainSpec::devnet();
+        let key = PrivateKey::generate();
+        let offender = derive_address(&key.public_key());
+        let mut state = WorldState::new();
+        state.set_account("reporter", Account { balance: 10, ..Account::default() });
+        state.set_account(
+            &offender,
+            Account {
+                staked: 1_000,
+                unbonding: vec![Unbonding { amount: 100, release_height: 50 }],
+                ..Account::default()
+            },
+        );
+
+        let evidence = EquivocationEvidence::new(signed(&spec, &key, 1), signed(&spec, &key, 2));
+        let report = Transaction::evidence("reporter", evidence).with_fee(1);
+        state.start_journal();
+        state.apply_transaction(&report, 8, &spec).unwrap();
+
+        let slashed = state.account(&offender);
+        assert!(slashed.tombstoned);
+        assert_eq!(slashed.staked, 950);
+        assert_eq!(slashed.unbonding[0].amount, 95);
+        assert_eq!(state.burned(), 55);
+
+        // The same offender cannot be slashed twice, and a failed report burns nothing.
+        let again = report.with_nonce(1);
+        assert!(state.apply_transaction(&again, 9, &spec).is_err());
+        assert_eq!(state.burned(), 55);
+        assert_eq!(state.account(&offender), slashed);
+    }
+
+    #[test]
+    fn burns_stake_of_a_validator_missing_its_proposals() {
+        let mut spec = ChainSpec::devnet();
+        spec.consensus.downtime_threshold = 2;
+        let mut state = WorldState::new();
+        state.set_account("idle", Account { staked: 1_000, ..Account::default() });
+        let block = |proposer: &str| {
+            let coinbase = Transaction::coinbase(4, proposer, 0, "2026-01-01T00:00:00Z");
+            Block::new(4, "00".repeat(32), vec![coinbase], spec.genesis.bits)
+        };
+        let missed = ["idle".to_string()];
+
+        state.start_journal();
+        state.record_proposal(&block("miner"), &missed, &spec);
+        assert_eq!(state.account("idle").missed_proposals, 1);
+        assert_eq!(state.burned(), 0);
+
+        state.record_proposal(&block("miner"), &missed, &spec);
+        let idle = state.account("idle");
+        assert_eq!((idle.staked, idle.missed_proposals), (990, 0));
+        assert_eq!(state.burned(), 10);
+
+        // Proposing resets the count.
+        state.record_proposal(&block("miner"), &missed, &spec);
+        state.record_proposal(&block("idle"), &[], &spec);
+        assert_eq!(state.account("idle").missed_proposals, 0);
+    }
+}
@@ -0,0 +1,273 @@
+//! # Block Templates
+//! Work for external miners, in the spirit of Bitcoin's `getblocktemplate`.
+//!
+//! A [`BlockTemplate`] carries everything needed to build the next block: the
+//! header fields, the full target, the coinbase and the selected transactions.
+//! A miner searches for a nonce, optionally moving the timestamp within
+//! `min_time..=max_time` and rolling the extranonce (the coinbase `nonce`, which
+//! changes the merkle root) once the nonce space is exhausted. The solution goes
+//! back as a [`WorkSubmission`].
+//! The node keeps the templates it issued in a [`TemplateStore`] and rebuilds
+//! the block from its own copy, so transactions never travel back over the wire.
+//!
+//! A template's block has a zero seed and no proposer signature, so only `pow`
+//! and `hybrid` networks accept it once mined; a `pos` node signs its own.
+
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::block::{Block, BlockHash, BlockHeader};
+use crate::consensus::difficulty::hash_meets_target;
+use crate::transaction::tx::Transaction;
+
+/// Number of recently issued templates kept for work submission.
+pub const MAX_PENDING_TEMPLATES: usize = 64;
+
+/// Everything an external miner needs to mine the next block.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockTemplate {
+    /// Identifier to quote when submitting work (the merkle root at extranonce 0).
+    pub template_id: String,
+
+    pub version: u32,
+
+    /// Height of the block being built.
+    pub height: u64,
+
+    /// Hash of the current tip the block builds on.
+    pub previous_hash: BlockHash,
+
+    /// Compact proof-of-work target required at this height.
+    pub bits: u32,
+
+    /// The full 256-bit target, hex-encoded big-endian.
+    pub target: String,
+
+    /// Suggested block time, in UNIX seconds.
+    pub timestamp: i64,
+
+    /// Earliest acceptable block time (median-time-past + 1), or `timestamp`
+    /// where the state root depends on the block time.
+    pub min_time: i64,
+
+    /// Latest block time the node would accept at template creation, or `timestamp`
+    /// where the state root depends on the block time.
+    pub max_time: i64,
+
+    pub merkle_root: String,
+
+    /// Root of the world state after the block; the extranonce does not affect it.
+    pub state_root: String,
+
+    /// Subsidy plus fees claimed by the coinbase.
+    pub coinbase_value: u64,
+
+    /// The coinbase, always the first transaction of the block.
+    pub coinbase: Transaction,
+
+    /// Selected mempool transactions, in block order after the coinbase.
+    pub transactions: Vec<Transaction>,
+}
+
+impl BlockTemplate {
+    /// Returns the block's transactions for an extranonce, coinbase first.
+    pub fn block_transactions(&self, extra_nonce: u64) -> Vec<Transaction> {
+        let coinbase = self.coinbase.clone().with_nonce(extra_nonce);
+        std::iter::once(coinbase)
+            .chain(self.transactions.iter().cloned())
+            .collect()
+    }
+
+    /// Builds the block for the given extranonce, timestamp and nonce, with its hash filled in.
+    pub fn to_block(&self, extra_nonce: u64, timestamp: i64, nonce: u64) -> Block {
+        let transactions = self.block_transactions(extra_nonce);
+        let merkle_root = if extra_nonce == self.coinbase.nonce {
+            self.merkle_root.clone()
+        } else {
+            Block::calculate_merkle_root(&transactions)
+        };
+
+        let mut block = Block {
+            version: self.version,
+            index: self.height,
+            timestamp,
+            previous_hash: self.previous_hash.clone(),
+            merkle_root,
+            state_root: self.state_root.clone(),
+            seed: "0".repeat(64),
+            hash: String::new(),
+            nonce,
+            bits: self.bits,
+            transactions,
+            signature: None,
+            seed_proof: None,
+        };
+        block.hash = block.calculate_hash();
+        block
+    }
+
+    /// Returns the binary header a miner hashes for the given extranonce, timestamp and nonce.
+    pub fn header(&self, extra_nonce: u64, timestamp: i64, nonce: u64) -> BlockHeader {
+        self.to_block(extra_nonce, timestamp, nonce).header()
+    }
+}
+
+/// A solved nonce for a previously issued template.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkSubmission {
+    pub template_id: String,
+    pub nonce: u64,
+
+    /// Coinbase extranonce the miner hashed with.
+    #[serde(default)]
+    pub extra_nonce: u64,
+
+    /// Block time the miner hashed with; defaults to the template's suggested timestamp.
+    #[serde(default)]
+    pub timestamp: Option<i64>,
+}
+
+/// Recently issued templates, oldest evicted first.
+#[derive(Debug, Default)]
+pub struct TemplateStore {
+    templates: HashMap<String, BlockTemplate>,
+    order: VecDeque<String>,
+}
+
+impl TemplateStore {
+    /// Creates an empty template store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remembers an issued template, evicting the oldest one when full.
+    pub fn insert(&mut self, template: BlockTemplate) {
+        if self.templates.contains_key(&template.template_id) {
+            return;
+        }
+        while self.order.len() >= MAX_PENDING_TEMPLATES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.templates.remove(&oldest);
+            }
+        }
+        self.order.push_back(template.template_id.clone());
+        self.templates.insert(template.template_id.clone(), template);
+    }
+
+    /// Returns the template with the given id, if it is still pending.
+    pub fn get(&self, template_id: &str) -> Option<&BlockTemplate> {
+        self.templates.get(template_id)
+    }
+
+    /// Returns the number of pending templates.
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Returns true if no template is pending.
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Drops every template that does not build on `tip_hash`.
+    pub fn retain_tip(&mut self, tip_hash: &str) {
+        self.templates.retain(|_, t| t.previous_hash == tip_hash);
+        let templates = &self.templates;
+        self.order.retain(|id| templates.contains_key(id));
+    }
+
+    /// Rebuilds the block for a submission and checks its proof of work.
+    ///
+    /// Only the cheap checks happen here; the chain still validates the block in full.
+    pub fn solve(&self, submission: &WorkSubmission) -> Result<Block, String> {
+        let template = self
+            .get(&submission.template_id)
+            .ok_or_else(|| format!("Unknown or stale template {}.", submission.template_id))?;
+
+        let timestamp = submission.timestamp.unwrap_or(template.timestamp);
+        if timestamp < template.min_time || timestamp > template.max_time {
+            return Err(format!(
+                "Timestamp {} is outside the template range {}..={}.",
+                timestamp, template.min_time, template.max_time
+            ));
+        }
+
+        let block = template.to_block(submission.extra_nonce, timestamp, submission.nonce);
+        if !hash_meets_target(&block.hash, block.bits) {
+            return Err(format!("Hash {} does not meet the target.", block.hash));
+        }
+        Ok(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::spec::ChainSpec;
+    use crate::blockchain::Blockchain;
+    use crate::transaction::mempool::Mempool;
+
+    /// First nonce whose block meets (or, with `meets` false, misses) the target.
+    fn nonce(template: &BlockTemplate, extra_nonce: u64, timestamp: i64, meets: bool) -> u64 {
+        (0..)
+            .find(|&nonce| hash_meets_target(&template.to_block(extra_nonce, timestamp, nonce).hash, template.bits) == meets)
+            .unwrap()
+    }
+
+    fn submission(template: &BlockTemplate, extra_nonce: u64, timestamp: i64) -> WorkSubmission {
+        WorkSubmission {
+            template_id: template.template_id.clone(),
+            nonce: nonce(template, extra_nonce, timestamp, true),
+            extra_nonce,
+            timestamp: Some(timestamp),
+        }
+    }
+
+    #[test]
+    fn solved_work_connects_at_any_allowed_time() {
+        let mut chain = Blockchain::new(ChainSpec::devnet());
+        let template = chain.block_template(&[], "miner").unwrap();
+        assert!(template.min_time < template.max_time);
+        let mut store = TemplateStore::new();
+        store.insert(template.clone());
+
+        let block = store.solve(&submission(&template, 7, template.max_time)).unwrap();
+        assert_eq!(block.state_root, template.state_root);
+        assert!(chain.accept_block(block, &mut Mempool::new()).unwrap());
+    }
+
+    #[test]
+    fn rejects_unknown_late_and_unworked_submissions() {
+        let chain = Blockchain::new(ChainSpec::devnet());
+        let template = chain.block_template(&[], "miner").unwrap();
+        let mut store = TemplateStore::new();
+        store.insert(template.clone());
+        let valid = submission(&template, 0, template.timestamp);
+        assert!(store.solve(&valid).is_ok());
+
+        let unknown = WorkSubmission { template_id: "00".repeat(32), ..valid.clone() };
+        assert!(store.solve(&unknown).is_err());
+        let late = WorkSubmission { timestamp: Some(template.max_time + 1), ..valid.clone() };
+        assert!(store.solve(&late).is_err());
+        let unworked = WorkSubmission { nonce: nonce(&template, 0, template.timestamp, false), ..valid };
+        assert!(store.solve(&unworked).is_err());
+    }
+
+    #[test]
+    fn keeps_recent_templates_on_the_tip() {
+        let template = Blockchain::new(ChainSpec::devnet()).block_template(&[], "miner").unwrap();
+        let mut store = TemplateStore::new();
+        for i in 0..=MAX_PENDING_TEMPLATES {
+            store.insert(BlockTemplate { template_id: i.to_string(), ..template.clone() });
+        }
+        assert_eq!(store.len(), MAX_PENDING_TEMPLATES);
+        assert!(store.get("0").is_none());
+
+        let side = BlockTemplate { template_id: "side".to_string(), previous_hash: "11".repeat(32), ..template.clone() };
+        store.insert(side);
+        store.retain_tip(&template.previous_hash);
+        assert_eq!(store.len(), MAX_PENDING_TEMPLATES - 1);
+        assert!(store.get("side").is_none());
+    }
+}
@@ -0,0 +1,189 @@
+//! # Block Tree Module
+//! Keeps every known valid block — not only the active chain — indexed by hash,
+//! together with its height and the cumulative weight of its branch.
+//!
+//! Fork choice is "most cumulative work wins": the chain switches to a competing
+//! branch only when that branch's tip carries strictly more total work than the
+//! current tip. Ties keep the first-seen tip. A block's work is what the
+//! consensus engine weighs it at ([`ConsensusEngine::block_weight`]): the proof
+//! of work its target demands, or under proof of stake a weight that drops with
+//! every proposal round the block's leader was late.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::blockchain::block::Block;
+use crate::consensus::difficulty::target_work;
+use crate::consensus::ConsensusEngine;
+
+/// A block known to the node, with its position in the tree.
+#[derive(Debug, Clone)]
+pub struct TreeEntry {
+    pub block: Block,
+    pub height: u64,
+    pub total_work: u128,
+}
+
+/// All known blocks, keyed by block hash.
+#[derive(Debug, Clone, Default)]
+pub struct BlockTree {
+    entries: HashMap<String, TreeEntry>,
+}
+
+/// Relative amount of work needed to mine a block at the given compact target.
+pub fn block_work(bits: u32) -> u128 {
+    target_work(bits)
+}
+
+impl BlockTree {
+    /// Creates a tree rooted at the given block: the genesis block, or the oldest
+    /// header of a snapshot the chain started from. The root keeps its own height;
+    /// work is counted from the root, which is all fork choice needs.
+    pub fn new(root: &Block) -> Self {
+        let mut entries = HashMap::new();
+        entries.insert(
+            root.hash.clone(),
+            TreeEntry {
+                block: root.clone(),
+                height: root.index,
+                total_work: block_work(root.bits),
+            },
+        );
+        Self { entries }
+    }
+
+    /// Returns true if a block with this hash is known.
+    pub fn contains(&self, hash: &str) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    /// Looks up a block entry by hash.
+    pub fn get(&self, hash: &str) -> Option<&TreeEntry> {
+        self.entries.get(hash)
+    }
+
+    /// Inserts a block whose parent is already in the tree, weighed by `engine`,
+    /// and returns its entry.
+    pub fn insert(&mut self, block: Block, engine: &dyn ConsensusEngine) -> Result<&TreeEntry, String> {
+        if self.entries.contains_key(&block.hash) {
+            return Err(format!("Block {} is already known.", block.hash));
+        }
+
+        let parent = self
+            .entries
+            .get(&block.previous_hash)
+            .ok_or_else(|| format!("Unknown parent {} for block {}.", block.previous_hash, block.hash))?;
+
+        let height = parent.height + 1;
+        if block.index != height {
+            return Err(format!(
+                "Block {} claims index {} but its parent is at height {}.",
+                block.hash, block.index, parent.height
+            ));
+        }
+
+        let total_work = parent
+            .total_work
+            .saturating_add(engine.block_weight(&block, &parent.block));
+        let hash = block.hash.clone();
+        self.entries.insert(
+            hash.clone(),
+            TreeEntry {
+                block,
+                height,
+                total_work,
+            },
+        );
+        Ok(&self.entries[&hash])
+    }
+
+    /// Removes a block and every known descendant (used when a branch turns out invalid).
+    ///
+    /// Returns the hashes of the removed blocks.
+    pub fn remove_branch(&mut self, hash: &str) -> Vec<String> {
+        let mut removed = Vec::new();
+        let mut doomed = vec![hash.to_string()];
+        while let Some(current) = doomed.pop() {
+            if self.entries.remove(&current).is_some() {
+                removed.push(current.clone());
+                doomed.extend(
+                    self.entries
+                        .values()
+                        .filter(|e| e.block.previous_hash == current)
+                        .map(|e| e.block.hash.clone()),
+                );
+            }
+        }
+        removed
+    }
+
+    /// Forgets what a pruned node no longer needs below the block `base`: ancestors
+    /// of `base` keep their headers but lose their transactions, and every other
+    /// block below `base` is removed together with its descendants (a branch
+    /// forking below `base` can never be reorganized to).
+    ///
+    /// Returns the hashes of the removed blocks.
+    pub fn prune_below(&mut self, base: &str) -> Vec<String> {
+        let base_height = match self.entries.get(base) {
+            Some(entry) => entry.height,
+            None => return Vec::new(),
+        };
+
+        let mut ancestors = HashSet::new();
+        let mut cursor = self.entries.get(base).map(|e| e.block.previous_hash.clone());
+        while let Some(hash) = cursor {
+            cursor = self.entries.get(&hash).map(|e| e.block.previous_hash.clone());
+            ancestors.insert(hash);
+        }
+
+        let stale: Vec<String> = self
+            .entries
+            .values()
+            .filter(|e| e.height < base_height && !ancestors.contains(&e.block.hash))
+            .map(|e| e.block.hash.clone())
+            .collect();
+        let mut removed = Vec::new();
+        for hash in stale {
+            removed.extend(self.remove_branch(&hash));
+        }
+
+        for hash in &ancestors {
+            if let Some(entry) = self.entries.get_mut(hash) {
+                entry.block.transactions = Vec::new();
+            }
+        }
+        removed
+    }
+
+    /// Returns the ancestor of `hash` (or the block itself) at the given height.
+    pub fn ancestor(&self, hash: &str, height: u64) -> Option<&TreeEntry> {
+        let mut cursor = self.entries.get(hash)?;
+        if height > cursor.height {
+            return None;
+        }
+        while cursor.height > height {
+            cursor = self.entries.get(&cursor.block.previous_hash)?;
+        }
+        Some(cursor)
+    }
+
+    /// Returns the branch ending at `tip`, walking back until `stop` returns true.
+    ///
+    /// The block for which `stop` matched is not included; the result is ordered
+    /// from the oldest block to `tip`.
+    pub fn branch_until<F>(&self, tip: &str, mut stop: F) -> Vec<&TreeEntry>
+    where
+        F: FnMut(&TreeEntry) -> bool,
+    {
+        let mut branch = Vec::new();
+        let mut cursor = self.entries.get(tip);
+        while let Some(entry) = cursor {
+            if stop(entry) {
+                break;
+            }
+            branch.push(entry);
+            cursor = self.entries.get(&entry.block.previous_hash);
+        }
+        branch.reverse();
+        branch
+    }
+}
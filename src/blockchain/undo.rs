@@ -0,0 +1,110 @@
+//! # Undo Journals
+//! Per-block records of what a block changed in the world state, so the block
+//! can be disconnected again without replaying the chain.
+//!
+//! A [`BlockUndo`] is built from the state's journal while the block is applied
+//! (see [`WorldState::start_journal`]): for every address the block wrote (the
+//! senders and recipients of its transactions, accounts whose unbonding funds it
+//! releases, and validators slashed or charged a missed proposal) it keeps the
+//! whole previous account, or `None` if the address had no state.
+//! Reverting restores those accounts exactly. Since a block's parent state is
+//! fixed by its ancestry, the journal of a block never changes and is kept for
+//! as long as the block body is.
+
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::block::{Block, BlockHash};
+use crate::blockchain::state::{Account, WorldState};
+
+/// The accounts a block overwrote, as they were before it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockUndo {
+    /// Hash of the block this journal reverts.
+    pub block_hash: BlockHash,
+
+    /// Height of that block.
+    pub index: u64,
+
+    /// Previous account of every touched address, in ascending address order;
+    /// `None` if the address had no state.
+    pub accounts: Vec<(String, Option<Account>)>,
+
+    /// Coins the block burned by slashing.
+    #[serde(default)]
+    pub burned: u64,
+}
+
+impl BlockUndo {
+    /// Records the accounts `block` changed, from `journal`, the state's journal
+    /// taken right after the block was applied ([`WorldState::take_journal`]),
+    /// and the coins it burned ([`WorldState::burned`]).
+    pub fn new(block: &Block, journal: Vec<(String, Option<Account>)>, burned: u64) -> Self {
+        Self {
+            block_hash: block.hash.clone(),
+            index: block.index,
+            accounts: journal,
+            burned,
+        }
+    }
+
+    /// Addresses whose accounts the journal restores.
+    pub fn addresses(&self) -> impl Iterator<Item = &str> {
+        self.accounts.iter().map(|(address, _)| address.as_str())
+    }
+
+    /// Puts every recorded account back into `state`, the state after the block.
+    pub fn revert(&self, state: &mut WorldState) {
+        for (address, account) in &self.accounts {
+            match account {
+                Some(account) => state.set_account(address, account.clone()),
+                None => state.remove_account(address),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::spec::ChainSpec;
+    use crate::blockchain::state::Unbonding;
+    use crate::transaction::tx::Transaction;
+
+    #[test]
+    fn revert_restores_the_exact_state() {
+        let spec = ChainSpec::devnet();
+        let mut state = WorldState::new();
+        state.set_account("alice", Account { balance: 100, staked: 40, ..Account::default() });
+        state.set_account(
+            "carol",
+            Account {
+                balance: 1,
+                unbonding: vec![Unbonding { amount: 25, release_height: 3 }],
+                ..Account::default()
+            },
+        );
+        let before = state.clone();
+
+        let block = Block::new(
+            3,
+            "00".repeat(32),
+            vec![
+                Transaction::coinbase(3, "miner", 0, "2026-01-01T00:00:00Z"),
+                Transaction::new("alice", "bob", 30, "").with_fee(2),
+                Transaction::unstake("alice", 15).with_nonce(1),
+            ],
+            spec.genesis.bits,
+        );
+        state.start_journal();
+        state.apply_block(&block, &spec).unwrap();
+        let undo = BlockUndo::new(&block, state.take_journal(), state.burned());
+        assert_ne!(state, before);
+        assert_eq!(undo.addresses().collect::<Vec<_>>(), ["alice", "bob", "carol"]);
+
+        // Bob and the miner had no state before the block and have none after the revert.
+        undo.revert(&mut state);
+        assert_eq!(state, before);
+        assert_eq!(state.state_root(), before.state_root());
+        assert!(state.get_account("bob").is_none());
+    }
+}
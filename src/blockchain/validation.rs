@@ -0,0 +1,206 @@
+//! # Block Validation
+//! Stateless stages of the block acceptance pipeline.
+//!
+//! A block received from a peer or a miner goes through, in order:
+//!
+//! 1. **Header** — version, canonical fields and hash ([`check_header`]);
+//!    parent, height and timestamp rules need the chain and are checked by
+//!    [`Blockchain::validate_block`](crate::blockchain::Blockchain::validate_block).
+//! 2. **Consensus** — the target required by the retargeting rules, then the
+//!    seal, checked by the chain's consensus engine ([`check_consensus`]).
+//! 3. **Merkle root** — recomputed from the transactions ([`check_merkle_root`]).
+//! 4. **Transactions** — block size limits, coinbase placement, hash integrity
+//!    and [`TransactionValidator`] rules ([`check_transactions`]). Signature checks
+//!    are skipped for ancestors of the chain spec's assume-valid block.
+//! 5. **State** — when the block is connected: its proposer, checked against
+//!    the staking pool of its parent state ([`check_proposer`]), then the block
+//!    applied to the world state.
+//!
+//! Each stage returns the first [`ChainError`] it finds.
+
+use crate::blockchain::block::{Block, MAX_BLOCK_SIZE, MAX_BLOCK_TRANSACTIONS};
+use crate::blockchain::error::{ChainError, ChainResult};
+use crate::consensus::difficulty::hash_meets_target;
+use crate::consensus::pos::StakingPool;
+use crate::consensus::pow::ProofOfWork;
+use crate::consensus::{ConsensusEngine, ConsensusKind, ConsensusResult};
+use crate::transaction::validator::TransactionValidator;
+
+/// Checks the header fields that do not depend on the rest of the chain.
+pub fn check_header(block: &Block) -> ChainResult<()> {
+    if !block.has_canonical_fields() {
+        return Err(ChainError::NonCanonicalHeader { index: block.index });
+    }
+    if block.hash != block.calculate_hash() {
+        return Err(ChainError::BadHash { index: block.index });
+    }
+    Ok(())
+}
+
+/// Checks the block's target against the one the chain expects, then its seal
+/// and transactions against the consensus engine.
+///
+/// A hash above the target of a mined block is [`ChainError::InsufficientWork`];
+/// any other verdict of the engine is [`ChainError::ConsensusRejected`] with its reason.
+pub fn check_consensus(engine: &dyn ConsensusEngine, block: &Block, expected_bits: u32) -> ChainResult<()> {
+    if block.bits != expected_bits {
+        return Err(ChainError::BadBits {
+            index: block.index,
+            expected: expected_bits,
+            found: block.bits,
+        });
+    }
+    if matches!(engine.kind(), ConsensusKind::Pow | ConsensusKind::Hybrid) && !hash_meets_target(&block.hash, block.bits) {
+        return Err(ChainError::InsufficientWork { index: block.index });
+    }
+    let verdict = match engine.validate_block(block) {
+        ConsensusResult::Valid => engine.validate_transactions(&block.transactions),
+        invalid => invalid,
+    };
+    verdict.into_result().map_err(|reason| ChainError::ConsensusRejected {
+        index: block.index,
+        reason,
+    })
+}
+
+/// Checks the block's proposer against `validators`, the staking pool of the
+/// state the block's parent `parent` left.
+pub fn check_proposer(
+    engine: &dyn ConsensusEngine,
+    block: &Block,
+    parent: &Block,
+    validators: &StakingPool,
+) -> ChainResult<()> {
+    engine
+        .validate_proposer(block, parent, validators)
+        .into_result()
+        .map_err(|reason| ChainError::ConsensusRejected {
+            index: block.index,
+            reason,
+        })
+}
+
+/// Checks the block's proof of work for the target the chain expects,
+/// whatever engine the chain runs (the genesis block is always mined).
+pub fn check_proof_of_work(block: &Block, expected_bits: u32) -> ChainResult<()> {
+    check_consensus(&ProofOfWork::default(), block, expected_bits)
+}
+
+/// Recomputes the merkle root from the block's transactions.
+pub fn check_merkle_root(block: &Block) -> ChainResult<()> {
+    let expected = Block::calculate_merkle_root(&block.transactions);
+    if expected != block.merkle_root {
+        return Err(ChainError::BadMerkleRoot {
+            index: block.index,
+            expected,
+            found: block.merkle_root.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// Checks the block's size, its coinbase and every transaction on its own.
+///
+/// A block holds at most [`MAX_BLOCK_TRANSACTIONS`] transactions and
+/// [`MAX_BLOCK_SIZE`] encoded bytes. The genesis block holds only coinbase
+/// allocations; every later block starts with exactly one coinbase, whose
+/// message commits to the block height, followed by signed transfers. With
+/// `verify_signatures` off, transfers are checked for everything but their signature,
+/// which otherwise must be made for `network_id`.
+pub fn check_transactions(block: &Block, network_id: u32, verify_signatures: bool) -> ChainResult<()> {
+    let index = block.index;
+    let bad_coinbase = |reason: &str| ChainError::BadCoinbase {
+        index,
+        reason: reason.to_string(),
+    };
+
+    if block.transactions.len() > MAX_BLOCK_TRANSACTIONS {
+        return Err(ChainError::OversizedBlock {
+            index,
+            reason: format!("{} transactions, at most {} allowed", block.transactions.len(), MAX_BLOCK_TRANSACTIONS),
+        });
+    }
+    let size = block.encoded_size();
+    if size > MAX_BLOCK_SIZE {
+        return Err(ChainError::OversizedBlock {
+            index,
+            reason: format!("{} bytes, at most {} allowed", size, MAX_BLOCK_SIZE),
+        });
+    }
+
+    match block.transactions.first() {
+        None => return Err(bad_coinbase("block has no transactions")),
+        Some(first) if !first.is_coinbase() => return Err(bad_coinbase("first transaction is not a coinbase")),
+        Some(first) if index > 0 && first.message != format!("coinbase:{}", index) => {
+            return Err(bad_coinbase("coinbase does not commit to the block height"))
+        }
+        _ => {}
+    }
+    if index > 0 && block.transactions.iter().skip(1).any(|tx| tx.is_coinbase()) {
+        return Err(bad_coinbase("more than one coinbase"));
+    }
+
+    for tx in &block.transactions {
+        let reject = |reason: String| ChainError::BadTransaction {
+            index,
+            tx_hash: tx.hash.clone(),
+            reason,
+        };
+        if tx.hash != tx.compute_hash() {
+            return Err(reject("hash does not match contents".into()));
+        }
+        if tx.is_coinbase() {
+            if tx.to.trim().is_empty() {
+                return Err(reject("coinbase has no recipient".into()));
+            }
+        } else if index == 0 {
+            return Err(bad_coinbase("genesis may only contain coinbase allocations"));
+        } else if verify_signatures {
+            TransactionValidator::validate(tx, network_id).map_err(reject)?;
+        } else {
+            TransactionValidator::validate_unsigned(tx).map_err(reject)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs every stateless stage that needs no chain context (header, merkle root, transactions).
+pub fn check_block(block: &Block, network_id: u32) -> ChainResult<()> {
+    check_header(block)?;
+    check_merkle_root(block)?;
+    check_transactions(block, network_id, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::tx::Transaction;
+
+    fn block(index: u64, transfers: Vec<Transaction>) -> Block {
+        let coinbase = Transaction::coinbase(index, "miner", 0, "");
+        Block::new(index, "00".repeat(32), std::iter::once(coinbase).chain(transfers).collect(), 0x207fffff)
+    }
+
+    #[test]
+    fn coinbase_commits_to_the_block_height() {
+        assert!(check_transactions(&block(3, vec![]), 0, false).is_ok());
+
+        let mut moved = block(3, vec![]);
+        moved.index = 4;
+        assert_eq!(check_transactions(&moved, 0, false).unwrap_err().rule(), "bad-coinbase");
+    }
+
+    #[test]
+    fn rejects_blocks_over_the_limits() {
+        let transfer = |nonce: u64| Transaction::new("alice", "bob", 1, "").with_nonce(nonce);
+        let full = block(3, (1..MAX_BLOCK_TRANSACTIONS as u64).map(transfer).collect());
+        assert!(check_transactions(&full, 0, false).is_ok());
+
+        let crowded = block(3, (0..MAX_BLOCK_TRANSACTIONS as u64).map(transfer).collect());
+        assert_eq!(check_transactions(&crowded, 0, false).unwrap_err().rule(), "oversized-block");
+
+        let large = Transaction::new("alice", &"b".repeat(MAX_BLOCK_SIZE as usize), 1, "");
+        let heavy = block(3, vec![large]);
+        assert_eq!(check_transactions(&heavy, 0, false).unwrap_err().rule(), "oversized-block");
+    }
+}
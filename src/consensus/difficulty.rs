@@ -0,0 +1,227 @@
+//! # consensus::difficulty
+//!
+//! Proof-of-Work difficulty: compact `bits` targets and the retargeting algorithm.
+//!
+//! A target is a 256-bit big-endian number; a block hash satisfies it when the hash,
+//! read as a number, is less than or equal to the target. Targets are carried in
+//! block headers in Bitcoin's compact form: the top byte is a base-256 exponent
+//! (the target's length in bytes) and the low 23 bits are the mantissa.
+//!
+//! Every [`RETARGET_INTERVAL`] blocks the target is scaled by the ratio of the time
+//! the last interval actually took to the time it should have taken at
+//! [`TARGET_BLOCK_TIME`], clamped to a factor of [`MAX_ADJUSTMENT_FACTOR`] either way.
+//!
+//! The constants are the mainnet values; the retargeting functions take the
+//! network's [`ConsensusParams`].
+
+use crate::blockchain::spec::ConsensusParams;
+
+/// Target spacing between blocks, in seconds (see `docs/vision/S470SHI_Chain.md`).
+pub const TARGET_BLOCK_TIME: i64 = 47;
+
+/// Number of blocks between difficulty adjustments (~94 minutes at target spacing).
+pub const RETARGET_INTERVAL: u64 = 120;
+
+/// Largest factor by which the target may grow or shrink in one adjustment.
+pub const MAX_ADJUSTMENT_FACTOR: i64 = 4;
+
+/// Easiest target any block may use.
+pub const POW_LIMIT_BITS: u32 = 0x2000ffff;
+
+/// A 256-bit big-endian target.
+pub type Target = [u8; 32];
+
+/// Expands compact `bits` into a full target.
+///
+/// Returns `None` for negative, zero or overflowing encodings.
+pub fn bits_to_target(bits: u32) -> Option<Target> {
+    let exponent = (bits >> 24) as isize;
+    let mantissa = bits & 0x007f_ffff;
+    if bits & 0x0080_0000 != 0 || mantissa == 0 {
+        return None;
+    }
+
+    let mut target = [0u8; 32];
+    for i in 0..3 {
+        let byte = ((mantissa >> (8 * (2 - i))) & 0xff) as u8;
+        let position = 32 - exponent + i as isize;
+        if position < 0 {
+            if byte != 0 {
+                return None;
+            }
+        } else if position < 32 {
+            target[position as usize] = byte;
+        }
+    }
+
+    if target.iter().all(|b| *b == 0) {
+        return None;
+    }
+    Some(target)
+}
+
+/// Compresses a target into compact `bits` (lossy below the top three significant bytes).
+pub fn target_to_bits(target: &Target) -> u32 {
+    let first = match target.iter().position(|b| *b != 0) {
+        Some(i) => i,
+        None => return 0,
+    };
+
+    let mut size = (32 - first) as u32;
+    let mut mantissa: u32 = 0;
+    for i in 0..3 {
+        let byte = target.get(first + i).copied().unwrap_or(0);
+        mantissa = (mantissa << 8) | byte as u32;
+    }
+
+    // The 0x00800000 bit is a sign bit in the compact format.
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+    (size << 24) | mantissa
+}
+
+/// Returns true if the hex-encoded hash is numerically at or below the target encoded by `bits`.
+pub fn hash_meets_target(hash_hex: &str, bits: u32) -> bool {
+    let target = match bits_to_target(bits) {
+        Some(t) => t,
+        None => return false,
+    };
+    match hex::decode(hash_hex) {
+        Ok(hash) if hash.len() == 32 => hash.as_slice() <= &target[..],
+        _ => false,
+    }
+}
+
+/// Approximate amount of work (expected hashes, scaled by 2^-128) to meet `bits`.
+///
+/// Only used to compare branches, so the scaling is irrelevant; harder targets
+/// always yield strictly more work.
+pub fn target_work(bits: u32) -> u128 {
+    let target = match bits_to_target(bits) {
+        Some(t) => t,
+        None => return 0,
+    };
+    let mut high = [0u8; 16];
+    high.copy_from_slice(&target[..16]);
+    let high = u128::from_be_bytes(high);
+    u128::MAX / high.saturating_add(1)
+}
+
+/// Returns true if a block at `height` must carry a freshly adjusted target.
+pub fn is_retarget_height(params: &ConsensusParams, height: u64) -> bool {
+    height > 0 && height.is_multiple_of(params.retarget_interval)
+}
+
+/// Scales the target in `bits` by `actual_timespan / expected_timespan`.
+///
+/// The ratio is clamped to the maximum adjustment factor in both directions and
+/// the result never exceeds the proof-of-work limit.
+pub fn retarget(params: &ConsensusParams, bits: u32, actual_timespan: i64, expected_timespan: i64) -> u32 {
+    let factor = params.max_adjustment_factor.max(1);
+    let expected = expected_timespan.max(1);
+    let actual = actual_timespan.clamp(expected / factor, expected * factor).max(1);
+
+    let exponent = (bits >> 24) as i32;
+    let mantissa = (bits & 0x007f_ffff) as u128;
+
+    // Widen the mantissa by four bytes so the division keeps its precision.
+    let mut scaled = (mantissa << 32) * actual as u128 / expected as u128;
+    let mut exponent = exponent - 4;
+    while scaled > 0x007f_ffff {
+        scaled >>= 8;
+        exponent += 1;
+    }
+    if exponent <= 0 || scaled == 0 {
+        return 0x0300_0001; // hardest representable target
+    }
+
+    let candidate = ((exponent as u32) << 24) | scaled as u32;
+    let limit = match bits_to_target(params.pow_limit_bits) {
+        Some(limit) => limit,
+        None => return params.pow_limit_bits,
+    };
+    match bits_to_target(candidate) {
+        Some(target) if target <= limit => target_to_bits(&target),
+        _ => params.pow_limit_bits,
+    }
+}
+
+/// Computes the `bits` required for the block at `height`.
+///
+/// - `parent_bits` — target of the parent block (height − 1)
+/// - `parent_time` — timestamp of the parent block, in UNIX seconds
+/// - `interval_start_time` — timestamp of the block at height − `retarget_interval`
+///
+/// Outside retarget heights the parent's target carries over unchanged.
+pub fn next_bits(
+    params: &ConsensusParams,
+    height: u64,
+    parent_bits: u32,
+    parent_time: i64,
+    interval_start_time: i64,
+) -> u32 {
+    if !is_retarget_height(params, height) {
+        return parent_bits;
+    }
+    let expected = (params.retarget_interval as i64 - 1) * params.target_block_time;
+    retarget(params, parent_bits, parent_time - interval_start_time, expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_bits_round_trip() {
+        for bits in [0x1d00ffff, 0x1f00ffff, POW_LIMIT_BITS, 0x207fffff] {
+            let target = bits_to_target(bits).unwrap();
+            assert_eq!(target_to_bits(&target), bits, "{:#010x}", bits);
+        }
+        // Negative, zero and overflowing encodings.
+        for bits in [0x1d80ffff, 0x1d000000, 0x2300ffff] {
+            assert!(bits_to_target(bits).is_none(), "{:#010x}", bits);
+        }
+
+        assert!(hash_meets_target(&"00".repeat(32), 0x1d00ffff));
+        assert!(!hash_meets_target(&"ff".repeat(32), 0x1d00ffff));
+        assert!(target_work(0x1c00ffff) > target_work(0x1d00ffff));
+    }
+
+    #[test]
+    fn retargets_by_the_observed_timespan() {
+        let params = ConsensusParams::default();
+        let expected = 1_000;
+        assert_eq!(retarget(&params, 0x1d00ffff, expected, expected), 0x1d00ffff);
+        // Blocks twice as slow double the target; twice as fast halve it.
+        assert_eq!(retarget(&params, 0x1d00ffff, 2 * expected, expected), 0x1d01fffe);
+        assert_eq!(retarget(&params, 0x1d00ffff, expected / 2, expected), 0x1c7fff80);
+    }
+
+    #[test]
+    fn clamps_adjustments_and_the_pow_limit() {
+        let params = ConsensusParams::default();
+        let factor = params.max_adjustment_factor;
+        let expected = 1_000;
+        assert_eq!(
+            retarget(&params, 0x1d00ffff, 100 * expected, expected),
+            retarget(&params, 0x1d00ffff, factor * expected, expected)
+        );
+        assert_eq!(
+            retarget(&params, 0x1d00ffff, 0, expected),
+            retarget(&params, 0x1d00ffff, expected / factor, expected)
+        );
+        assert_eq!(retarget(&params, POW_LIMIT_BITS, 2 * expected, expected), POW_LIMIT_BITS);
+    }
+
+    #[test]
+    fn keeps_the_parent_target_between_retargets() {
+        let params = ConsensusParams::default();
+        let interval = params.retarget_interval;
+        assert_eq!(next_bits(&params, interval - 1, 0x1d00ffff, 0, 1), 0x1d00ffff);
+        assert!(!is_retarget_height(&params, 0));
+        assert!(is_retarget_height(&params, interval));
+        assert_ne!(next_bits(&params, interval, 0x1d00ffff, 1, 0), 0x1d00ffff);
+    }
+}
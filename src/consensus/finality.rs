@@ -0,0 +1,219 @@
+//! # consensus::finality
+//!
+//! Stake-signed finality checkpoints for the hybrid PoW + PoS engine.
+//!
+//! Proof of work produces the blocks. Every `finality_interval` blocks, the
+//! validators of the [`StakingPool`] sign the active-chain block at that height
+//! with a [`CheckpointVote`]. Once the signers hold at least two thirds of the
+//! total validator stake, the votes form a [`FinalityCheckpoint`] and the block
+//! is final: the chain never disconnects it and rejects every branch that does
+//! not contain it, however much work that branch has.
+//!
+//! A vote signs the network id, the height and the block hash (see
+//! [`CheckpointVote::message`]), so it cannot be replayed on another network or
+//! for another block. The validator is identified by the address derived from
+//! the public key carried in the vote.
+
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::block::BlockHash;
+use crate::consensus::pos::StakingPool;
+use crate::crypto::keypair::{PrivateKey, PublicKey};
+use crate::crypto::signature::{sign_message, verify_signature, Signature};
+use crate::wallet::wallet::derive_address;
+
+/// Default number of blocks between finality checkpoints.
+pub const FINALITY_INTERVAL: u64 = 100;
+
+/// Domain separator of the signed vote message.
+const VOTE_DOMAIN: &[u8] = b"OCOS-FINALITY";
+
+/// A validator's signature on the checkpoint block at one height.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointVote {
+    pub height: u64,
+    pub block_hash: BlockHash,
+
+    /// Address of the signing validator.
+    pub validator: String,
+
+    /// Hex-encoded uncompressed public key the address is derived from.
+    pub public_key: String,
+
+    /// Hex-encoded DER signature over [`CheckpointVote::message`].
+    pub signature: String,
+}
+
+impl CheckpointVote {
+    /// The bytes a vote signs: domain, network id, height and block hash.
+    pub fn message(network_id: u32, height: u64, block_hash: &str) -> Vec<u8> {
+        let mut message = VOTE_DOMAIN.to_vec();
+        message.extend_from_slice(&network_id.to_le_bytes());
+        message.extend_from_slice(&height.to_le_bytes());
+        message.extend_from_slice(block_hash.as_bytes());
+        message
+    }
+
+    /// Signs the checkpoint block `block_hash` at `height` with a validator key.
+    pub fn sign(network_id: u32, height: u64, block_hash: &str, private_key: &PrivateKey) -> Self {
+        let public_key = private_key.public_key();
+        let signature = sign_message(&Self::message(network_id, height, block_hash), private_key);
+        Self {
+            height,
+            block_hash: block_hash.to_string(),
+            validator: derive_address(&public_key),
+            public_key: hex::encode(public_key.to_bytes()),
+            signature: hex::encode(signature.to_bytes()),
+        }
+    }
+
+    /// Checks that the public key belongs to `validator` and signed this vote.
+    pub fn verify(&self, network_id: u32) -> Result<(), String> {
+        let public_key = hex::decode(&self.public_key)
+            .ok()
+            .and_then(|bytes| PublicKey::from_bytes(&bytes))
+            .ok_or_else(|| "vote carries an invalid public key".to_string())?;
+        if derive_address(&public_key) != self.validator {
+            return Err(format!("public key does not belong to {}", self.validator));
+        }
+        let signature = hex::decode(&self.signature)
+            .ok()
+            .and_then(|bytes| Signature::from_bytes(&bytes))
+            .ok_or_else(|| "vote carries an invalid signature".to_string())?;
+        let message = Self::message(network_id, self.height, &self.block_hash);
+        if !verify_signature(&message, &signature, &public_key) {
+            return Err("signature does not match the vote".into());
+        }
+        Ok(())
+    }
+}
+
+/// The votes collected for one checkpoint block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FinalityCheckpoint {
+    pub height: u64,
+    pub block_hash: BlockHash,
+
+    /// At most one vote per validator, in arrival order.
+    pub votes: Vec<CheckpointVote>,
+}
+
+impl FinalityCheckpoint {
+    /// Starts collecting votes for the block `block_hash` at `height`.
+    pub fn new(height: u64, block_hash: &str) -> Self {
+        Self {
+            height,
+            block_hash: block_hash.to_string(),
+            votes: Vec::new(),
+        }
+    }
+
+    /// Returns true if `validator` already voted.
+    pub fn has_vote_from(&self, validator: &str) -> bool {
+        self.votes.iter().any(|vote| vote.validator == validator)
+    }
+
+    /// Stake of the validators in `pool` that voted.
+    pub fn signed_stake(&self, pool: &StakingPool) -> u64 {
+        self.votes
+            .iter()
+            .filter(|vote| pool.is_validator(&vote.validator))
+            .fold(0u64, |acc, vote| acc.saturating_add(pool.get_stake(&vote.validator)))
+    }
+
+    /// Returns true if the voters hold at least two thirds of the validator stake.
+    pub fn is_final(&self, pool: &StakingPool) -> bool {
+        has_supermajority(self.signed_stake(pool), pool.total_stake())
+    }
+
+    /// Checks every vote against this checkpoint and `network_id`, and that the
+    /// votes finalize it under `pool`.
+    pub fn verify(&self, network_id: u32, pool: &StakingPool) -> Result<(), String> {
+        for (i, vote) in self.votes.iter().enumerate() {
+            if vote.height != self.height || vote.block_hash != self.block_hash {
+                return Err(format!("vote of {} is for another block", vote.validator));
+            }
+            if self.votes[..i].iter().any(|other| other.validator == vote.validator) {
+                return Err(format!("{} voted twice", vote.validator));
+            }
+            vote.verify(network_id)?;
+        }
+        if !self.is_final(pool) {
+            return Err("votes hold less than two thirds of the stake".into());
+        }
+        Ok(())
+    }
+}
+
+/// Returns true if `signed` is at least two thirds of a non-zero `total`.
+pub fn has_supermajority(signed: u64, total: u64) -> bool {
+    total > 0 && u128::from(signed) * 3 >= u128::from(total) * 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two validators with two thirds and one third of the stake.
+    fn validators() -> (PrivateKey, PrivateKey, StakingPool) {
+        let (major, minor) = (PrivateKey::generate(), PrivateKey::generate());
+        let mut pool = StakingPool::new();
+        pool.stake(derive_address(&major.public_key()), 200);
+        pool.stake(derive_address(&minor.public_key()), 100);
+        (major, minor, pool)
+    }
+
+    #[test]
+    fn votes_only_verify_on_their_network() {
+        let key = PrivateKey::generate();
+        let vote = CheckpointVote::sign(1, 100, "hash", &key);
+        assert!(vote.verify(1).is_ok());
+        assert!(vote.verify(2).is_err());
+
+        let mut moved = vote.clone();
+        moved.height = 200;
+        assert!(moved.verify(1).is_err());
+
+        let mut stolen = vote;
+        stolen.validator = derive_address(&PrivateKey::generate().public_key());
+        assert!(stolen.verify(1).is_err());
+    }
+
+    #[test]
+    fn finalizes_with_two_thirds_of_the_stake() {
+        let (major, minor, pool) = validators();
+        let mut checkpoint = FinalityCheckpoint::new(100, "hash");
+        checkpoint.votes.push(CheckpointVote::sign(1, 100, "hash", &minor));
+        assert!(!checkpoint.is_final(&pool));
+        assert!(checkpoint.verify(1, &pool).is_err());
+
+        checkpoint.votes.push(CheckpointVote::sign(1, 100, "hash", &major));
+        assert_eq!(checkpoint.signed_stake(&pool), 300);
+        assert!(checkpoint.verify(1, &pool).is_ok());
+
+        let mut outsider = checkpoint.clone();
+        outsider.votes.push(CheckpointVote::sign(1, 100, "hash", &PrivateKey::generate()));
+        assert_eq!(outsider.signed_stake(&pool), 300);
+    }
+
+    #[test]
+    fn rejects_duplicate_and_foreign_votes() {
+        let (major, _, pool) = validators();
+        let mut twice = FinalityCheckpoint::new(100, "hash");
+        twice.votes.push(CheckpointVote::sign(1, 100, "hash", &major));
+        twice.votes.push(CheckpointVote::sign(1, 100, "hash", &major));
+        assert!(twice.verify(1, &pool).is_err());
+
+        let mut foreign = FinalityCheckpoint::new(100, "hash");
+        foreign.votes.push(CheckpointVote::sign(1, 100, "other", &major));
+        assert!(foreign.verify(1, &pool).is_err());
+    }
+
+    #[test]
+    fn supermajority_needs_a_non_zero_total() {
+        assert!(has_supermajority(2, 3));
+        assert!(!has_supermajority(1, 2));
+        assert!(!has_supermajority(0, 0));
+        assert!(has_supermajority(u64::MAX, u64::MAX));
+    }
+}
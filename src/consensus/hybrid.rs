@@ -0,0 +1,60 @@
+//! Hybrid PoW + PoS consensus engine.
+//!
+//! Blocks are produced and validated exactly as under [`ProofOfWork`]; the
+//! validators who locked stake on chain additionally finalize a block every
+//! `finality_interval` blocks by signing it (see [`crate::consensus::finality`]).
+
+use crate::blockchain::block::{Block, BlockHeader};
+use crate::consensus::miner::CancelHandle;
+use crate::consensus::pos::StakingPool;
+use crate::consensus::pow::ProofOfWork;
+use crate::consensus::{ConsensusEngine, ConsensusKind, ConsensusResult};
+use crate::crypto::keypair::PrivateKey;
+use crate::transaction::tx::Transaction;
+
+/// Proof of work for block production, stake-signed checkpoints for finality.
+#[derive(Debug, Clone)]
+pub struct HybridConsensus {
+    pow: ProofOfWork,
+}
+
+impl HybridConsensus {
+    /// Creates an engine mining with `pow`.
+    pub fn new(pow: ProofOfWork) -> Self {
+        Self { pow }
+    }
+}
+
+impl ConsensusEngine for HybridConsensus {
+    fn kind(&self) -> ConsensusKind {
+        ConsensusKind::Hybrid
+    }
+
+    fn name(&self) -> &'static str {
+        "HybridPowPos"
+    }
+
+    fn seal_block(&self, block: &mut Block, parent: &Block, signer: Option<&PrivateKey>, cancel: &CancelHandle) -> Result<(), String> {
+        self.pow.seal_block(block, parent, signer, cancel)
+    }
+
+    fn validate_block(&self, block: &Block) -> ConsensusResult {
+        self.pow.validate_block(block)
+    }
+
+    fn validate_header(&self, header: &BlockHeader) -> ConsensusResult {
+        self.pow.validate_header(header)
+    }
+
+    fn validate_transactions(&self, txs: &[Transaction]) -> ConsensusResult {
+        self.pow.validate_transactions(txs)
+    }
+
+    fn select_proposer(&self, previous_block: &Block, time: i64, validators: &StakingPool) -> Option<String> {
+        self.pow.select_proposer(previous_block, time, validators)
+    }
+
+    fn has_finality(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,278 @@
+//! # consensus::miner
+//!
+//! Multi-threaded, cancellable proof-of-work search.
+//!
+//! A [`Miner`] splits the nonce range of a header across a pool of worker
+//! threads. Workers poll a shared [`CancelHandle`] so mining stops promptly when
+//! a competing block arrives, or when the handle's deadline passes and the block
+//! should be rebuilt with a fresh timestamp. When a whole range is exhausted without a solution,
+//! the miner rolls the *extranonce* (the `nonce` field of the block's coinbase,
+//! which changes the merkle root) and starts over; blocks without a coinbase roll
+//! their timestamp instead.
+//!
+//! With a single thread the search is sequential from nonce 0, so the result is
+//! deterministic (the genesis block relies on this).
+//!
+//! Workers publish their hash count to the mining metrics every
+//! `CANCEL_CHECK_INTERVAL` hashes, so the hash rate is visible while a round is
+//! still running.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use crate::blockchain::block::{Block, BlockHeader};
+use crate::consensus::difficulty::bits_to_target;
+use crate::crypto::hash::double_sha256;
+use crate::network::metrics;
+
+/// Number of hashes a worker computes between checks of the stop flags and
+/// updates of the mining metrics.
+const CANCEL_CHECK_INTERVAL: u64 = 4_096;
+
+/// Shared flag used to stop a running search, optionally once a deadline passes.
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancelHandle {
+    /// Creates a handle that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a handle that also counts as cancelled from `deadline` on, so a
+    /// search on a template can be restarted with a fresh timestamp.
+    pub fn with_deadline(deadline: Instant) -> Self {
+        Self {
+            cancelled: Arc::default(),
+            deadline: Some(deadline),
+        }
+    }
+
+    /// Requests every search using this handle to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true once [`CancelHandle::cancel`] has been called or the deadline
+    /// has passed.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed) || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// Outcome of a search round over one header.
+struct RoundResult {
+    solution: Option<(u64, Vec<u8>)>,
+}
+
+/// A pool of proof-of-work worker threads.
+#[derive(Debug, Clone)]
+pub struct Miner {
+    threads: usize,
+    nonce_range: u64,
+}
+
+impl Miner {
+    /// Creates a miner with the given number of worker threads (at least one),
+    /// each round covering the full `u64` nonce space.
+    pub fn new(threads: usize) -> Self {
+        Self {
+            threads: threads.max(1),
+            nonce_range: u64::MAX,
+        }
+    }
+
+    /// Creates a miner with one worker per available CPU.
+    pub fn with_available_parallelism() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+
+    /// Limits each round to nonces `0..=nonce_range` before the extranonce is rolled.
+    pub fn with_nonce_range(mut self, nonce_range: u64) -> Self {
+        self.nonce_range = nonce_range.max(1);
+        self
+    }
+
+    /// Returns the number of worker threads.
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Searches for a nonce that makes the block hash meet its `bits` target.
+    ///
+    /// On success the block's `nonce` and `hash` are set (and its coinbase,
+    /// merkle root or timestamp may have been rolled) and `true` is returned.
+    /// Returns `false` if the search was cancelled or `bits` is not a valid target.
+    pub fn mine(&self, block: &mut Block, cancel: &CancelHandle) -> bool {
+        let target = match bits_to_target(block.bits) {
+            Some(target) => target,
+            None => return false,
+        };
+
+        while !cancel.is_cancelled() {
+            let round = self.search(&block.header(), &target, cancel);
+            if let Some((nonce, hash)) = round.solution {
+                block.nonce = nonce;
+                block.hash = hex::encode(hash);
+                return true;
+            }
+            if !cancel.is_cancelled() {
+                Self::roll_extra_nonce(block);
+            }
+        }
+        false
+    }
+
+    /// Scans `0..=nonce_range` for `header`, split evenly across the workers.
+    fn search(&self, header: &BlockHeader, target: &[u8; 32], cancel: &CancelHandle) -> RoundResult {
+        let found = AtomicBool::new(false);
+        let hashes = AtomicU64::new(0);
+        let started = Instant::now();
+        // Adds a worker's new hashes to the round total and publishes the rate.
+        let publish = |count: u64| {
+            let round_hashes = hashes.fetch_add(count, Ordering::Relaxed) + count;
+            metrics::record_mining_progress(count, round_hashes, started.elapsed());
+        };
+        let solution: Mutex<Option<(u64, Vec<u8>)>> = Mutex::new(None);
+
+        let workers = self.threads as u64;
+        let chunk = (self.nonce_range / workers).max(1);
+
+        thread::scope(|scope| {
+            for worker in 0..workers {
+                let start = worker.saturating_mul(chunk);
+                let end = if worker + 1 == workers {
+                    self.nonce_range
+                } else {
+                    start.saturating_add(chunk - 1)
+                };
+                if start > self.nonce_range {
+                    break;
+                }
+
+                let (found, solution, publish) = (&found, &solution, &publish);
+                let mut header = header.clone();
+                scope.spawn(move || {
+                    let mut count: u64 = 0;
+                    let mut published: u64 = 0;
+                    let mut nonce = start;
+                    loop {
+                        if count.is_multiple_of(CANCEL_CHECK_INTERVAL) {
+                            if count > published {
+                                publish(count - published);
+                                published = count;
+                            }
+                            if found.load(Ordering::Relaxed) || cancel.is_cancelled() {
+                                break;
+                            }
+                        }
+                        header.nonce = nonce;
+                        let hash = double_sha256(&header.to_bytes());
+                        count += 1;
+                        if hash.as_slice() <= &target[..] {
+                            let mut best = solution.lock().unwrap();
+                            // Keep the lowest nonce if several workers succeed at once.
+                            if best.as_ref().is_none_or(|(n, _)| nonce < *n) {
+                                *best = Some((nonce, hash));
+                            }
+                            found.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                        if nonce == end {
+                            break;
+                        }
+                        nonce += 1;
+                    }
+                    if count > published {
+                        publish(count - published);
+                    }
+                });
+            }
+        });
+
+        RoundResult {
+            solution: solution.into_inner().unwrap(),
+        }
+    }
+
+    /// Changes the header preimage after a round was exhausted.
+    fn roll_extra_nonce(block: &mut Block) {
+        match block.transactions.first_mut() {
+            Some(coinbase) if coinbase.is_coinbase() => {
+                coinbase.nonce = coinbase.nonce.wrapping_add(1);
+                coinbase.hash = coinbase.compute_hash();
+                block.merkle_root = Block::calculate_merkle_root(&block.transactions);
+            }
+            _ => block.timestamp += 1,
+        }
+        block.nonce = 0;
+    }
+}
+
+impl Default for Miner {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::transaction::tx::Transaction;
+
+    fn block(bits: u32) -> Block {
+        let coinbase = Transaction::coinbase(1, "miner", 0, "");
+        let mut block = Block::new(1, "00".repeat(32), vec![coinbase], bits);
+        block.timestamp = 1_700_000_000;
+        block
+    }
+
+    #[test]
+    fn finds_a_valid_nonce_on_every_thread_count() {
+        for threads in [1, 4] {
+            let mut block = block(0x2000ffff);
+            assert!(Miner::new(threads).mine(&mut block, &CancelHandle::new()));
+            assert!(block.is_valid(0x2000ffff), "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn single_thread_search_is_deterministic() {
+        let (mut first, mut second) = (block(0x2000ffff), block(0x2000ffff));
+        Miner::new(1).mine(&mut first, &CancelHandle::new());
+        Miner::new(1).mine(&mut second, &CancelHandle::new());
+        assert_eq!(first.hash, second.hash);
+    }
+
+    #[test]
+    fn rolls_the_extranonce_when_a_range_is_exhausted() {
+        let mut block = block(0x2000ffff);
+        assert!(Miner::new(1).with_nonce_range(1).mine(&mut block, &CancelHandle::new()));
+        assert!(block.transactions[0].nonce > 0);
+        assert_eq!(block.merkle_root, Block::calculate_merkle_root(&block.transactions));
+        assert!(block.is_valid(0x2000ffff));
+    }
+
+    #[test]
+    fn stops_when_cancelled() {
+        let cancel = CancelHandle::new();
+        let stopper = cancel.clone();
+        let timer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            stopper.cancel();
+        });
+
+        // Far harder than anything the test could solve.
+        let mut block = block(0x0300ffff);
+        assert!(!Miner::new(2).mine(&mut block, &cancel));
+        assert!(block.hash.is_empty());
+        timer.join().unwrap();
+    }
+}
@@ -8,15 +8,45 @@
 //! - Allow easy swapping of consensus mechanisms
 //! - Standardize interaction with mining, validation and block proposal
 //!
+//! Every network names its engine in the chain spec (`consensus.engine`);
+//! [`engine_for`] builds it, and the [`Blockchain`](crate::blockchain::Blockchain)
+//! seals the blocks it produces and validates the blocks it receives through it.
+//! One build can therefore run a network of any kind.
+//!
 //! ## Implementations (in this project)
+//! - `difficulty`: compact targets and retargeting toward the 47-second block time
+//! - `finality`: stake-signed finality checkpoints
+//! - `miner`: multi-threaded, cancellable nonce search with extranonce rollover
+//! - `timestamp`: median-time-past and future-drift rules for block times
 //! - `pow::ProofOfWork`
 //! - `pos::ProofOfStake`
+//! - `slashing`: equivocation evidence and penalties for double-signing and downtime
+//! - `hybrid::HybridConsensus` (PoW blocks, PoS finality checkpoints)
+//! - `DummyConsensus` (no sealing, for tests and local experiments)
 //! - Custom DAO governance model (planned)
 
+pub mod difficulty;
+pub mod finality;
+pub mod hybrid;
+pub mod miner;
 pub mod pow;
 pub mod pos;
+pub mod slashing;
+pub mod timestamp;
+
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
 
-use crate::blockchain::block::Block;
+use crate::blockchain::block::{Block, BlockHeader};
+use crate::blockchain::spec::ChainSpec;
+use crate::blockchain::tree::block_work;
+use crate::consensus::hybrid::HybridConsensus;
+use crate::consensus::miner::CancelHandle;
+use crate::crypto::keypair::PrivateKey;
+use crate::consensus::pos::{ProofOfStake, StakingPool};
+use crate::consensus::pow::ProofOfWork;
 use crate::transaction::tx::Transaction;
 
 /// Represents the result of a consensus validation operation
@@ -26,32 +56,143 @@ pub enum ConsensusResult {
     Invalid(String),
 }
 
+impl ConsensusResult {
+    /// Converts the result into a `Result`, with the rejection reason as the error.
+    pub fn into_result(self) -> Result<(), String> {
+        match self {
+            ConsensusResult::Valid => Ok(()),
+            ConsensusResult::Invalid(reason) => Err(reason),
+        }
+    }
+}
+
+/// The consensus algorithm a network runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsensusKind {
+    /// Proof of Work ([`ProofOfWork`]).
+    #[default]
+    Pow,
+    /// Proof of Stake ([`ProofOfStake`]).
+    Pos,
+    /// No sealing at all ([`DummyConsensus`]).
+    Dummy,
+    /// Proof of Work with stake-signed finality checkpoints ([`HybridConsensus`]).
+    Hybrid,
+}
+
+impl fmt::Display for ConsensusKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ConsensusKind::Pow => "pow",
+            ConsensusKind::Pos => "pos",
+            ConsensusKind::Dummy => "dummy",
+            ConsensusKind::Hybrid => "hybrid",
+        };
+        f.write_str(name)
+    }
+}
+
 /// Trait to define a consensus algorithm’s interface
-pub trait ConsensusEngine {
+///
+/// Engines see one block at a time. Rules that need the chain (the target from
+/// the retargeting rules, timestamps, checkpoints, state) stay with the
+/// [`Blockchain`](crate::blockchain::Blockchain); a block's `bits` field is
+/// checked against the expected target before the engine is consulted, and the
+/// chain hands in the staking pool of the parent state where stakes matter.
+pub trait ConsensusEngine: fmt::Debug + Send + Sync {
+    /// Returns the kind of the engine
+    fn kind(&self) -> ConsensusKind;
+
     /// Returns the name/type of the consensus algorithm
     fn name(&self) -> &'static str;
 
-    /// Validates a block according to consensus rules
+    /// Seals a block on top of `parent` whose other fields are final, setting
+    /// its nonce and hash (and seed and signature, for engines whose blocks are
+    /// signed by `signer`).
+    ///
+    /// Fails if sealing was cancelled or the engine needs a signer it was not
+    /// given; the block is then unusable.
+    fn seal_block(&self, block: &mut Block, parent: &Block, signer: Option<&PrivateKey>, cancel: &CancelHandle) -> Result<(), String>;
+
+    /// Validates the seal of a block according to consensus rules
     fn validate_block(&self, block: &Block) -> ConsensusResult;
 
+    /// Validates what a header alone shows of the seal (e.g. the headers of a
+    /// state snapshot, which come without bodies).
+    fn validate_header(&self, _header: &BlockHeader) -> ConsensusResult {
+        ConsensusResult::Valid
+    }
+
     /// Determines if a given set of transactions can be included in the next block
     fn validate_transactions(&self, txs: &[Transaction]) -> ConsensusResult;
 
-    /// Calculates the next proposer or miner, depending on the consensus algorithm
-    fn select_proposer(&self, previous_block: &Block) -> String;
+    /// Validates what the seal of a block says about its proposer against
+    /// `validators`, the staking pool of the state `parent` left.
+    fn validate_proposer(&self, _block: &Block, _parent: &Block, _validators: &StakingPool) -> ConsensusResult {
+        ConsensusResult::Valid
+    }
 
-    /// Optionally adjusts difficulty or state (used in PoW or PoS)
-    fn update_state(&mut self, previous_block: &Block);
+    /// Returns the validators that were due to propose a block on `parent`
+    /// before the proposer of `block`, and did not.
+    fn missed_proposers(&self, _block: &Block, _parent: &Block, _validators: &StakingPool) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Returns the only address allowed to produce a block with timestamp `time`
+    /// after `previous_block`, or `None` if the engine leaves it open.
+    fn select_proposer(&self, previous_block: &Block, time: i64, validators: &StakingPool) -> Option<String>;
+
+    /// Returns true if `address` may produce a block with timestamp `time` after
+    /// `previous_block`.
+    fn may_propose(&self, previous_block: &Block, time: i64, address: &str, validators: &StakingPool) -> bool {
+        self.select_proposer(previous_block, time, validators)
+            .is_none_or(|proposer| proposer == address)
+    }
+
+    /// Fork-choice weight `block` adds to its branch on top of `parent`: by
+    /// default the work its target demands.
+    fn block_weight(&self, block: &Block, _parent: &Block) -> u128 {
+        block_work(block.bits)
+    }
+
+    /// Returns true if the validators of the chain's staking pool finalize blocks.
+    fn has_finality(&self) -> bool {
+        false
+    }
+}
+
+/// Builds the consensus engine of the network described by `spec`.
+pub fn engine_for(spec: &ChainSpec) -> Arc<dyn ConsensusEngine> {
+    match spec.consensus.engine {
+        ConsensusKind::Pow => Arc::new(ProofOfWork::new()),
+        ConsensusKind::Pos => Arc::new(ProofOfStake::new(spec.consensus.target_block_time, spec.network_id)),
+        ConsensusKind::Dummy => Arc::new(DummyConsensus),
+        ConsensusKind::Hybrid => Arc::new(HybridConsensus::new(ProofOfWork::new())),
+    }
 }
 
 /// Default placeholder engine for testing or no-consensus mode
+#[derive(Debug, Clone, Copy, Default)]
 pub struct DummyConsensus;
 
 impl ConsensusEngine for DummyConsensus {
+    fn kind(&self) -> ConsensusKind {
+        ConsensusKind::Dummy
+    }
+
     fn name(&self) -> &'static str {
         "DummyConsensus"
     }
 
+    fn seal_block(&self, block: &mut Block, _parent: &Block, _signer: Option<&PrivateKey>, cancel: &CancelHandle) -> Result<(), String> {
+        block.hash = block.calculate_hash();
+        if cancel.is_cancelled() {
+            return Err("sealing was cancelled".into());
+        }
+        Ok(())
+    }
+
     fn validate_block(&self, _block: &Block) -> ConsensusResult {
         ConsensusResult::Valid
     }
@@ -60,11 +201,30 @@ impl ConsensusEngine for DummyConsensus {
         ConsensusResult::Valid
     }
 
-    fn select_proposer(&self, _previous_block: &Block) -> String {
-        "0x0000000000000000".to_string()
+    fn select_proposer(&self, _previous_block: &Block, _time: i64, _validators: &StakingPool) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_the_engine_named_by_the_spec() {
+        let mut spec = ChainSpec::devnet();
+        for kind in [ConsensusKind::Pow, ConsensusKind::Pos, ConsensusKind::Dummy, ConsensusKind::Hybrid] {
+            spec.consensus.engine = kind;
+            assert_eq!(engine_for(&spec).kind(), kind);
+        }
     }
 
-    fn update_state(&mut self, _previous_block: &Block) {
-        // No-op
+    #[test]
+    fn engine_names_match_the_spec_format() {
+        for kind in [ConsensusKind::Pow, ConsensusKind::Pos, ConsensusKind::Dummy, ConsensusKind::Hybrid] {
+            let json = serde_json::to_string(&kind).unwrap();
+            assert_eq!(json, format!("\"{}\"", kind));
+            assert_eq!(serde_json::from_str::<ConsensusKind>(&json).unwrap(), kind);
+        }
     }
 }
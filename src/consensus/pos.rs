@@ -3,14 +3,74 @@
 //! This module implements a simplified Proof-of-Stake consensus logic,
 //! including staking, validator management, and leader selection.
 
-use std::collections::{HashMap, HashSet};
-use rand::{seq::IteratorRandom, thread_rng};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::blockchain::block::{Block, BlockSignature};
+use crate::blockchain::state::WorldState;
+use crate::consensus::miner::CancelHandle;
+use crate::consensus::{ConsensusEngine, ConsensusKind, ConsensusResult};
+use crate::crypto::hash::sha256_hash;
+use crate::crypto::keypair::{PrivateKey, PublicKey};
+use crate::crypto::vrf::{vrf_prove, vrf_verify, VrfProof};
+use crate::transaction::tx::Transaction;
 
 /// Type alias for address
 pub type Address = String;
 
 /// Minimum stake required to become a validator
-const MIN_STAKE: u64 = 100;
+pub const MIN_STAKE: u64 = 100;
+
+/// Default number of blocks unstaked funds stay locked before they are spendable.
+pub const UNBONDING_PERIOD: u64 = 1_000;
+
+/// Domain separator of the leader draw.
+const LEADER_DOMAIN: &[u8] = b"OCOS-LEADER";
+
+/// Domain separator of the VRF input a proposer derives its block's seed from.
+const SEED_DOMAIN: &[u8] = b"OCOS-SEED";
+
+/// Fork-choice weight of a block proposed in round 0; each later round halves it,
+/// down to a weight of 1.
+///
+/// A branch built by a minority of the stake has to wait out the rounds of the
+/// validators it lacks, so over the same stretch of time it stays lighter than a
+/// branch the majority extends.
+pub const PROPOSAL_WEIGHT: u128 = 1 << 64;
+
+/// Rounds after round 0 whose leaders are charged a missed proposal (see
+/// [`crate::consensus::slashing`]); after a longer stall, only the first rounds count.
+pub const MAX_TRACKED_ROUNDS: u64 = 32;
+
+/// Round of a block with timestamp `block_time` on a parent from `parent_time`:
+/// each round lasts two slots of `slot_time` seconds, so the round-0 leader has a
+/// full slot of slack.
+///
+/// Round 0 belongs to the leader drawn from the parent's seed alone and every
+/// later round to a fresh draw, so an offline leader only delays the chain by a
+/// round. A proposer can claim a later round only by moving its timestamp
+/// forward, which the future-drift rule bounds; a proof-of-stake spec keeps
+/// `max_future_drift` below one block time (see
+/// [`ChainSpec::validate`](crate::blockchain::spec::ChainSpec::validate)).
+pub fn proposal_round(parent_time: i64, block_time: i64, slot_time: i64) -> u64 {
+    let elapsed = block_time.saturating_sub(parent_time).max(0) as u64;
+    elapsed / (slot_time.max(1) as u64).saturating_mul(2)
+}
+
+/// Seed of the leader draw on top of `parent`: the seed in its header, or its
+/// hash for a block without one (the genesis block).
+///
+/// A header's seed is its proposer's VRF output over the parent's seed (see
+/// [`crate::crypto::vrf`]). A key and an input have exactly one VRF output, so a
+/// proposer cannot grind the next seed; it can only withhold its block, which
+/// costs it the proposal.
+pub fn leader_seed(parent: &Block) -> Vec<u8> {
+    let seed = parent.header().seed;
+    if seed == [0u8; 32] {
+        parent.hash.as_bytes().to_vec()
+    } else {
+        seed.to_vec()
+    }
+}
 
 /// Represents a staking record for a given address
 #[derive(Debug, Clone)]
@@ -20,19 +80,35 @@ pub struct Stake {
 }
 
 /// Maintains all active stakes in the system
-#[derive(Default, Debug)]
+///
+/// The pool of a block is rebuilt from the on-chain stakes of the state its
+/// parent left ([`StakingPool::from_state`]), so every node derives the same
+/// validators from the same chain. Ordered maps keep iteration, and hence leader selection, identical on every node.
+#[derive(Default, Debug, Clone)]
 pub struct StakingPool {
-    stakes: HashMap<Address, u64>,
-    validators: HashSet<Address>,
+    stakes: BTreeMap<Address, u64>,
+    validators: BTreeSet<Address>,
 }
 
 impl StakingPool {
     /// Create a new staking pool
     pub fn new() -> Self {
         Self {
-            stakes: HashMap::new(),
-            validators: HashSet::new(),
+            stakes: BTreeMap::new(),
+            validators: BTreeSet::new(),
+        }
+    }
+
+    /// Rebuilds the pool from the stakes locked in `state`; accounts slashed for
+    /// equivocation are left out.
+    pub fn from_state(state: &WorldState) -> Self {
+        let mut pool = Self::new();
+        for (address, account) in state.accounts() {
+            if account.staked > 0 && !account.tombstoned {
+                pool.stake(address.clone(), account.staked);
+            }
         }
+        pool
     }
 
     /// Stake tokens to become eligible for validation
@@ -60,25 +136,210 @@ impl StakingPool {
         *self.stakes.get(address).unwrap_or(&0)
     }
 
+    /// Returns true if the address has at least [`MIN_STAKE`] staked
+    pub fn is_validator(&self, address: &str) -> bool {
+        self.validators.contains(address)
+    }
+
+    /// Total stake of the current validators
+    pub fn total_stake(&self) -> u64 {
+        self.validators
+            .iter()
+            .fold(0u64, |acc, address| acc.saturating_add(self.get_stake(address)))
+    }
+
     /// Get the list of current validators
     pub fn get_validators(&self) -> Vec<Address> {
         self.validators.iter().cloned().collect()
     }
 
-    /// Randomly select a validator based on stake-weighted lottery
-    pub fn select_leader(&self) -> Option<Address> {
-        let mut weighted_pool = vec![];
+    /// Selects a validator by a stake-weighted draw seeded with `seed` (see
+    /// [`leader_seed`]). Every node with the same pool and seed selects the
+    /// same validator; the draw takes one pass over the stakes.
+    pub fn select_leader(&self, seed: &[u8]) -> Option<Address> {
+        let total = self.total_stake();
+        if total == 0 {
+            return None;
+        }
+
+        let mut preimage = LEADER_DOMAIN.to_vec();
+        preimage.extend_from_slice(seed);
+        let digest = sha256_hash(&preimage);
+        let draw = u128::from_be_bytes(digest[..16].try_into().expect("16-byte slice")) % u128::from(total);
 
-        for (addr, amount) in &self.stakes {
-            if *amount >= MIN_STAKE {
-                // Add the address multiple times based on stake
-                for _ in 0..*amount {
-                    weighted_pool.push(addr.clone());
-                }
+        // Validators own consecutive ranges of [0, total) in address order.
+        let mut upper = 0u128;
+        for address in &self.validators {
+            upper += u128::from(self.get_stake(address));
+            if draw < upper {
+                return Some(address.clone());
             }
         }
+        None
+    }
+
+    /// Leader of `round` on a parent with leader seed `seed`: the draw seeded with
+    /// the seed alone for round 0, and with the seed and the round number after that.
+    pub fn round_leader(&self, seed: &[u8], round: u64) -> Option<Address> {
+        let mut seed = seed.to_vec();
+        if round > 0 {
+            seed.extend_from_slice(&round.to_le_bytes());
+        }
+        self.select_leader(&seed)
+    }
+}
+
+/// Proof-of-Stake consensus engine; the validators come from the chain state.
+///
+/// A block is sealed without any work by the leader of its round (see
+/// [`proposal_round`]), who receives the coinbase and signs the header, so any
+/// node can check a block against the pool of its parent state.
+#[derive(Debug, Clone, Copy)]
+pub struct ProofOfStake {
+    /// Target block time in seconds; a round lasts two of them.
+    slot_time: i64,
+
+    /// Network the proposer signatures are made for.
+    network_id: u32,
+}
+
+impl ProofOfStake {
+    /// Creates the engine for the network `network_id` targeting `slot_time`
+    /// seconds per block.
+    pub fn new(slot_time: i64, network_id: u32) -> Self {
+        Self { slot_time, network_id }
+    }
+
+    /// Round of `block` on top of `parent`.
+    pub fn round_of(&self, parent: &Block, block: &Block) -> u64 {
+        proposal_round(parent.timestamp, block.timestamp, self.slot_time)
+    }
+
+    /// VRF input the proposer of a block on `parent` derives the block's seed from.
+    fn seed_input(&self, parent: &Block) -> Vec<u8> {
+        let mut input = SEED_DOMAIN.to_vec();
+        input.extend_from_slice(&self.network_id.to_le_bytes());
+        input.extend_from_slice(&leader_seed(parent));
+        input
+    }
+
+    /// Checks that the seed of `block` is its signer's VRF output over the seed of `parent`.
+    fn check_seed(&self, block: &Block, parent: &Block) -> Result<(), String> {
+        let public_key = block
+            .signature
+            .as_ref()
+            .and_then(|signature| hex::decode(&signature.public_key).ok())
+            .and_then(|bytes| PublicKey::from_bytes(&bytes))
+            .ok_or_else(|| "block is not signed by its proposer".to_string())?;
+        let proof = block
+            .seed_proof
+            .as_deref()
+            .and_then(|proof| hex::decode(proof).ok())
+            .and_then(|bytes| VrfProof::from_bytes(&bytes))
+            .ok_or_else(|| "block carries no valid seed proof".to_string())?;
+        match vrf_verify(&public_key, &self.seed_input(parent), &proof) {
+            Some(output) if hex::encode(output) == block.seed => Ok(()),
+            Some(_) => Err("block seed is not the output of its seed proof".into()),
+            None => Err("block seed proof does not verify".into()),
+        }
+    }
+
+    /// The proposer of a block: the recipient of its coinbase.
+    pub fn proposer_of(block: &Block) -> Option<&str> {
+        block
+            .transactions
+            .first()
+            .filter(|tx| tx.is_coinbase())
+            .map(|tx| tx.to.as_str())
+    }
+}
+
+impl ConsensusEngine for ProofOfStake {
+    fn kind(&self) -> ConsensusKind {
+        ConsensusKind::Pos
+    }
+
+    fn name(&self) -> &'static str {
+        "ProofOfStake"
+    }
+
+    fn seal_block(&self, block: &mut Block, parent: &Block, signer: Option<&PrivateKey>, cancel: &CancelHandle) -> Result<(), String> {
+        let signer = signer.ok_or_else(|| "proof-of-stake blocks must be signed by their proposer".to_string())?;
+        let proof = vrf_prove(signer, &self.seed_input(parent));
+        block.seed = hex::encode(proof.output());
+        block.seed_proof = Some(hex::encode(proof.to_bytes()));
+        block.hash = block.calculate_hash();
+        block.signature = Some(BlockSignature::sign(self.network_id, &block.header(), signer));
+        if cancel.is_cancelled() {
+            return Err("sealing was cancelled".into());
+        }
+        Ok(())
+    }
+
+    fn validate_block(&self, block: &Block) -> ConsensusResult {
+        let proposer = match Self::proposer_of(block) {
+            Some(proposer) => proposer,
+            None => return ConsensusResult::Invalid("block has no proposer".into()),
+        };
+        let signer = match &block.signature {
+            Some(signature) => signature.verify(self.network_id, &block.header()),
+            None => return ConsensusResult::Invalid("block is not signed by its proposer".into()),
+        };
+        match signer {
+            Ok(signer) if signer == proposer => ConsensusResult::Valid,
+            Ok(signer) => ConsensusResult::Invalid(format!("block is signed by {}, not by its proposer {}", signer, proposer)),
+            Err(reason) => ConsensusResult::Invalid(reason),
+        }
+    }
+
+    fn validate_proposer(&self, block: &Block, parent: &Block, validators: &StakingPool) -> ConsensusResult {
+        if let Err(reason) = self.check_seed(block, parent) {
+            return ConsensusResult::Invalid(reason);
+        }
+        let round = self.round_of(parent, block);
+        let leader = match validators.round_leader(&leader_seed(parent), round) {
+            Some(leader) => leader,
+            None => return ConsensusResult::Invalid("there are no validators".into()),
+        };
+        match Self::proposer_of(block) {
+            Some(proposer) if proposer == leader => ConsensusResult::Valid,
+            Some(proposer) => ConsensusResult::Invalid(format!(
+                "proposer {} is not the leader {} of round {}",
+                proposer, leader, round
+            )),
+            None => ConsensusResult::Invalid("block has no proposer".into()),
+        }
+    }
+
+    fn missed_proposers(&self, block: &Block, parent: &Block, validators: &StakingPool) -> Vec<String> {
+        let round = self.round_of(parent, block).min(MAX_TRACKED_ROUNDS + 1);
+        let proposer = Self::proposer_of(block);
+        let seed = leader_seed(parent);
+        let mut missed: Vec<String> = (0..round)
+            .filter_map(|r| validators.round_leader(&seed, r))
+            .filter(|leader| Some(leader.as_str()) != proposer)
+            .collect();
+        missed.sort_unstable();
+        missed.dedup();
+        missed
+    }
+
+    fn validate_transactions(&self, _txs: &[Transaction]) -> ConsensusResult {
+        ConsensusResult::Valid
+    }
+
+    fn select_proposer(&self, previous_block: &Block, time: i64, validators: &StakingPool) -> Option<String> {
+        let round = proposal_round(previous_block.timestamp, time, self.slot_time);
+        validators.round_leader(&leader_seed(previous_block), round)
+    }
+
+    fn may_propose(&self, previous_block: &Block, time: i64, address: &str, validators: &StakingPool) -> bool {
+        self.select_proposer(previous_block, time, validators)
+            .is_some_and(|leader| leader == address)
+    }
 
-        let mut rng = thread_rng();
-        weighted_pool.into_iter().choose(&mut rng)
+    fn block_weight(&self, block: &Block, parent: &Block) -> u128 {
+        // Late blocks still outweigh nothing, so a branch never stops growing heavier.
+        (PROPOSAL_WEIGHT >> self.round_of(parent, block).min(127)).max(1)
     }
 }
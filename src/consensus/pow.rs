@@ -1,60 +1,84 @@
-use sha2::{Digest, Sha256};
-use hex::encode;
-use chrono::prelude::*;
-use crate::blockchain::block::Block;
-
-/// Trait for consensus mechanisms
-pub trait ConsensusEngine {
-    fn mine_block(&self, block: &mut Block);
-    fn is_valid_proof(&self, block: &Block) -> bool;
-}
+//! Proof-of-Work (PoW) consensus engine.
+//!
+//! Blocks are sealed by searching for a nonce whose header hash meets the
+//! block's `bits` target; the target itself comes from the chain's retargeting
+//! rules (see `consensus::difficulty`).
+
+use crate::blockchain::block::{Block, BlockHeader};
+use crate::consensus::difficulty::hash_meets_target;
+use crate::consensus::miner::{CancelHandle, Miner};
+use crate::consensus::pos::StakingPool;
+use crate::consensus::{ConsensusEngine, ConsensusKind, ConsensusResult};
+use crate::crypto::keypair::PrivateKey;
+use crate::transaction::tx::Transaction;
 
 /// Proof-of-Work implementation
+#[derive(Debug, Clone)]
 pub struct ProofOfWork {
-    pub difficulty: usize,
+    miner: Miner,
 }
 
 impl ProofOfWork {
-    pub fn new(difficulty: usize) -> Self {
-        ProofOfWork { difficulty }
-    }
-
-    fn calculate_hash(&self, block: &Block) -> String {
-        let input = format!(
-            "{}{}{}{}{}",
-            block.index,
-            block.timestamp,
-            block.previous_hash,
-            block.merkle_root,
-            block.nonce
-        );
-        let mut hasher = Sha256::new();
-        hasher.update(input);
-        encode(hasher.finalize())
+    /// Creates an engine mining with one worker per available CPU.
+    pub fn new() -> Self {
+        Self::with_miner(Miner::with_available_parallelism())
+    }
+
+    /// Creates an engine mining with the given miner.
+    pub fn with_miner(miner: Miner) -> Self {
+        ProofOfWork { miner }
+    }
+
+    /// Returns true if the block hash is its header hash and meets the block's own target.
+    pub fn is_valid_proof(&self, block: &Block) -> bool {
+        let hash = block.header().hash();
+        hash == block.hash && hash_meets_target(&hash, block.bits)
+    }
+}
+
+impl Default for ProofOfWork {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl ConsensusEngine for ProofOfWork {
-    fn mine_block(&self, block: &mut Block) {
-        println!(
-            "⛏️  Mining block #{} with difficulty {}...",
-            block.index, self.difficulty
-        );
-
-        loop {
-            let hash = self.calculate_hash(block);
-            if hash.starts_with(&"0".repeat(self.difficulty)) {
-                block.hash = hash;
-                println!("✅ Block mined: {}", block.hash);
-                break;
-            } else {
-                block.nonce += 1;
-            }
+    fn kind(&self) -> ConsensusKind {
+        ConsensusKind::Pow
+    }
+
+    fn name(&self) -> &'static str {
+        "ProofOfWork"
+    }
+
+    fn seal_block(&self, block: &mut Block, _parent: &Block, _signer: Option<&PrivateKey>, cancel: &CancelHandle) -> Result<(), String> {
+        if !self.miner.mine(block, cancel) {
+            return Err("mining was cancelled".into());
+        }
+        Ok(())
+    }
+
+    fn validate_block(&self, block: &Block) -> ConsensusResult {
+        if self.is_valid_proof(block) {
+            ConsensusResult::Valid
+        } else {
+            ConsensusResult::Invalid("hash does not meet the target".into())
         }
     }
 
-    fn is_valid_proof(&self, block: &Block) -> bool {
-        let hash = self.calculate_hash(block);
-        hash.starts_with(&"0".repeat(self.difficulty)) && hash == block.hash
+    fn validate_header(&self, header: &BlockHeader) -> ConsensusResult {
+        if hash_meets_target(&header.hash(), header.bits) {
+            ConsensusResult::Valid
+        } else {
+            ConsensusResult::Invalid(format!("header {} does not meet its target", header.index))
+        }
+    }
+
+    fn validate_transactions(&self, _txs: &[Transaction]) -> ConsensusResult {
+        ConsensusResult::Valid
+    }
+
+    fn select_proposer(&self, _previous_block: &Block, _time: i64, _validators: &StakingPool) -> Option<String> {
+        None
     }
 }
@@ -0,0 +1,183 @@
+//! # consensus::slashing
+//!
+//! Penalties for proof-of-stake validators that misbehave.
+//!
+//! **Equivocation.** A validator that signs two different headers on the same
+//! parent has tried to fork the chain. Anyone can submit the two signed headers
+//! ([`EquivocationEvidence`]) in an `evidence` transaction. Once it verifies, a
+//! share of the offender's stake and unbonding funds (`equivocation_slash_percent`)
+//! is burned and the offender is removed from the validator set for good.
+//! Evidence is accepted for as long as the offender's funds may still be
+//! unbonding (`unbonding_period` blocks), so unstaking right after
+//! double-signing does not escape the penalty.
+//!
+//! **Downtime.** A proposer that misses its round lets the leader of a later
+//! round propose instead (see [`crate::consensus::pos`]). Every block records
+//! the leaders of the rounds it skipped; a validator that misses
+//! `downtime_threshold` proposals in a row loses `downtime_slash_percent` of its
+//! stake but stays a validator. Proposing a block resets the count.
+//!
+//! Burned funds leave the supply for good; they are not paid to the reporter.
+
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::block::{Block, BlockHeader, BlockSignature};
+use crate::crypto::hash::sha256_hash;
+use crate::crypto::keypair::PublicKey;
+use crate::wallet::wallet::derive_address;
+
+/// Default share of stake and unbonding funds burned for equivocation, in percent.
+pub const EQUIVOCATION_SLASH_PERCENT: u64 = 5;
+
+/// Default number of proposals in a row a validator may miss before it is penalized.
+pub const DOWNTIME_THRESHOLD: u64 = 50;
+
+/// Default share of stake burned for downtime, in percent.
+pub const DOWNTIME_SLASH_PERCENT: u64 = 1;
+
+/// Share `percent` of `amount`, rounded down.
+pub fn slash_amount(amount: u64, percent: u64) -> u64 {
+    (u128::from(amount) * u128::from(percent.min(100)) / 100) as u64
+}
+
+/// A header with its proposer's signature, as broadcast with a block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedHeader {
+    pub header: BlockHeader,
+    pub signature: BlockSignature,
+}
+
+impl SignedHeader {
+    /// The signed header of a block, if the block carries a signature.
+    pub fn of(block: &Block) -> Option<Self> {
+        block.signature.as_ref().map(|signature| Self {
+            header: block.header(),
+            signature: signature.clone(),
+        })
+    }
+
+    /// Checks the signature for the network `network_id` and returns the
+    /// signer's address.
+    pub fn signer(&self, network_id: u32) -> Result<String, String> {
+        self.signature.verify(network_id, &self.header)
+    }
+}
+
+/// Two different headers on the same parent, signed by the same validator.
+///
+/// Headers at one height on different parents are not evidence: a leader may
+/// honestly extend both sides of a fork.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EquivocationEvidence {
+    pub first: SignedHeader,
+    pub second: SignedHeader,
+}
+
+impl EquivocationEvidence {
+    /// Pairs two signed headers as evidence.
+    pub fn new(first: SignedHeader, second: SignedHeader) -> Self {
+        Self { first, second }
+    }
+
+    /// Height at which the validator equivocated.
+    pub fn height(&self) -> u64 {
+        self.first.header.index
+    }
+
+    /// Address the evidence accuses, taken from the first header's public key
+    /// without checking the signatures (see [`EquivocationEvidence::verify`]).
+    pub fn accused(&self) -> Option<String> {
+        hex::decode(&self.first.signature.public_key)
+            .ok()
+            .and_then(|bytes| PublicKey::from_bytes(&bytes))
+            .map(|public_key| derive_address(&public_key))
+    }
+
+    /// Checks that both headers are at the same height on the same parent,
+    /// differ, and are signed by the same validator for the network
+    /// `network_id`, so evidence only verifies on its own network. Returns the
+    /// offender's address.
+    pub fn verify(&self, network_id: u32) -> Result<String, String> {
+        if self.first.header.index != self.second.header.index {
+            return Err("signed headers are at different heights".into());
+        }
+        if self.first.header.previous_hash != self.second.header.previous_hash {
+            return Err("signed headers build on different parents".into());
+        }
+        if self.first.header == self.second.header {
+            return Err("signed headers are the same header".into());
+        }
+        let first = self.first.signer(network_id)?;
+        let second = self.second.signer(network_id)?;
+        if first != second {
+            return Err(format!("headers are signed by {} and {}", first, second));
+        }
+        Ok(first)
+    }
+
+    /// Hex SHA-256 committing to both signed headers (part of the transaction hash).
+    pub fn digest(&self) -> String {
+        let mut preimage = Vec::new();
+        for signed in [&self.first, &self.second] {
+            preimage.extend_from_slice(&signed.header.to_bytes());
+            for field in [&signed.signature.public_key, &signed.signature.signature] {
+                preimage.extend_from_slice(&(field.len() as u32).to_le_bytes());
+                preimage.extend_from_slice(field.as_bytes());
+            }
+        }
+        hex::encode(sha256_hash(&preimage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::keypair::PrivateKey;
+
+    const NETWORK: u32 = 474747;
+
+    fn signed(key: &PrivateKey, parent: &str, nonce: u64) -> SignedHeader {
+        let mut block = Block::new(7, parent.to_string(), Vec::new(), 0x207fffff);
+        block.timestamp = 1_700_000_000;
+        block.nonce = nonce;
+        let header = block.header();
+        SignedHeader { signature: BlockSignature::sign(NETWORK, &header, key), header }
+    }
+
+    #[test]
+    fn verifies_two_headers_on_one_parent() {
+        let key = PrivateKey::generate();
+        let parent = "11".repeat(32);
+        let evidence = EquivocationEvidence::new(signed(&key, &parent, 1), signed(&key, &parent, 2));
+        let offender = derive_address(&key.public_key());
+        assert_eq!(evidence.verify(NETWORK), Ok(offender.clone()));
+        assert_eq!(evidence.accused(), Some(offender));
+        assert_eq!(evidence.height(), 7);
+        assert!(evidence.verify(NETWORK + 1).is_err());
+    }
+
+    #[test]
+    fn rejects_headers_that_are_not_equivocation() {
+        let key = PrivateKey::generate();
+        let parent = "11".repeat(32);
+
+        // Extending both sides of a fork is honest.
+        let forks = EquivocationEvidence::new(signed(&key, &parent, 1), signed(&key, &"22".repeat(32), 2));
+        assert!(forks.verify(NETWORK).is_err());
+
+        let same = EquivocationEvidence::new(signed(&key, &parent, 1), signed(&key, &parent, 1));
+        assert!(same.verify(NETWORK).is_err());
+
+        let other = PrivateKey::generate();
+        let signers = EquivocationEvidence::new(signed(&key, &parent, 1), signed(&other, &parent, 2));
+        assert!(signers.verify(NETWORK).is_err());
+    }
+
+    #[test]
+    fn slashes_a_share_rounded_down() {
+        assert_eq!(slash_amount(1_000, EQUIVOCATION_SLASH_PERCENT), 50);
+        assert_eq!(slash_amount(99, 1), 0);
+        assert_eq!(slash_amount(u64::MAX, 100), u64::MAX);
+        assert_eq!(slash_amount(10, 250), 10);
+    }
+}
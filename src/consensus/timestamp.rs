@@ -0,0 +1,74 @@
+//! # consensus::timestamp
+//!
+//! Block timestamp rules, enforced on every block the chain accepts:
+//!
+//! - **Median-time-past (MTP):** a block's timestamp must be strictly greater than
+//!   the median of the previous [`MEDIAN_TIME_SPAN`] block timestamps. This keeps
+//!   time moving forward without requiring miners' clocks to agree exactly.
+//! - **Future drift:** a block's timestamp may be at most [`MAX_FUTURE_DRIFT`]
+//!   seconds ahead of the validating node's clock.
+//!
+//! All times are integer seconds since the UNIX epoch. The constants are the
+//! mainnet values; each network sets its own in its `ChainSpec`.
+
+/// Number of ancestor blocks whose timestamps form the median-time-past.
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+/// Maximum number of seconds a block may be ahead of local time. Kept below one
+/// 47s block so a proof-of-stake proposer cannot stamp a block into a later round.
+pub const MAX_FUTURE_DRIFT: i64 = 30;
+
+/// Returns the median of the given ancestor timestamps (the caller picks the window).
+///
+/// An empty slice yields `i64::MIN` so that any timestamp passes the MTP rule.
+pub fn median_time_past(timestamps: &[i64]) -> i64 {
+    let mut window: Vec<i64> = timestamps.to_vec();
+    if window.is_empty() {
+        return i64::MIN;
+    }
+    window.sort_unstable();
+    window[window.len() / 2]
+}
+
+/// Checks a block timestamp against the median-time-past of its ancestors and the local clock.
+pub fn check_block_time(
+    timestamp: i64,
+    median_time_past: i64,
+    now: i64,
+    max_future_drift: i64,
+) -> Result<(), String> {
+    if timestamp <= median_time_past {
+        return Err(format!(
+            "Block timestamp {} is not after median-time-past {}.",
+            timestamp, median_time_past
+        ));
+    }
+    if timestamp > now.saturating_add(max_future_drift) {
+        return Err(format!(
+            "Block timestamp {} is more than {}s ahead of local time {}.",
+            timestamp, max_future_drift, now
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn median_of_the_window() {
+        assert_eq!(median_time_past(&[]), i64::MIN);
+        assert_eq!(median_time_past(&[5, 1, 3]), 3);
+        assert_eq!(median_time_past(&[4, 1, 3, 2]), 3);
+    }
+
+    #[test]
+    fn requires_time_after_the_median_and_before_the_drift_limit() {
+        let now = 1_700_000_000;
+        assert!(check_block_time(100, 100, now, MAX_FUTURE_DRIFT).is_err());
+        assert!(check_block_time(101, 100, now, MAX_FUTURE_DRIFT).is_ok());
+        assert!(check_block_time(now + MAX_FUTURE_DRIFT, 100, now, MAX_FUTURE_DRIFT).is_ok());
+        assert!(check_block_time(now + MAX_FUTURE_DRIFT + 1, 100, now, MAX_FUTURE_DRIFT).is_err());
+    }
+}
@@ -7,7 +7,9 @@
 //! - `double_sha256`: double SHA-256 (used in Bitcoin-like structures)
 //! - `blake2b_hash`: Blake2b-256, fast and secure alternative
 //! - `merkle_root`: calculate Merkle root from a list of data entries
+//! - `merkle_proof` / `verify_merkle_proof`: inclusion proofs for light clients
 
+use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
 use blake2::{Blake2b256, Digest as BlakeDigest};
 
@@ -33,36 +35,197 @@ pub fn blake2b_hash(data: &[u8]) -> Vec<u8> {
     hasher.finalize().to_vec()
 }
 
+/// Domain-separation prefix for hashing a Merkle leaf.
+const MERKLE_LEAF_PREFIX: u8 = 0x00;
+
+/// Domain-separation prefix for hashing two child nodes into a parent.
+const MERKLE_NODE_PREFIX: u8 = 0x01;
+
+/// Domain-separation prefix for committing the leaf count into the root.
+const MERKLE_ROOT_PREFIX: u8 = 0x02;
+
+/// Inclusion proof for one leaf of a Merkle tree built by [`merkle_root`].
+///
+/// `siblings` lists the sibling hash at every level where the path has one,
+/// from the leaf upwards. Levels where the node was the unpaired last node are
+/// skipped; the verifier recomputes them from `index` and `leaf_count`. Both are
+/// bound by the root, so a proof that verifies also proves the leaf's position.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub index: usize,
+    pub leaf_count: usize,
+    pub siblings: Vec<Vec<u8>>,
+}
+
+fn merkle_leaf_hash(leaf: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(leaf.len() + 1);
+    data.push(MERKLE_LEAF_PREFIX);
+    data.extend_from_slice(leaf);
+    sha256_hash(&data)
+}
+
+fn merkle_node_hash(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(left.len() + right.len() + 1);
+    data.push(MERKLE_NODE_PREFIX);
+    data.extend_from_slice(left);
+    data.extend_from_slice(right);
+    sha256_hash(&data)
+}
+
+/// Binds the tree's width into its root, which in turn fixes the tree's shape and
+/// with it the position a proof path leads to.
+fn merkle_commit_root(tree_root: &[u8], leaf_count: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(tree_root.len() + 9);
+    data.push(MERKLE_ROOT_PREFIX);
+    data.extend_from_slice(&(leaf_count as u64).to_le_bytes());
+    data.extend_from_slice(tree_root);
+    sha256_hash(&data)
+}
+
+/// Builds the next tree level; an unpaired last node is promoted unchanged.
+fn merkle_next_level(level: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => merkle_node_hash(left, right),
+            [single] => single.clone(),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
 /// Calculates a Merkle root from a list of byte-array leaves.
-/// Pads with last element if odd count. Returns 32-byte root hash.
+///
+/// Leaves and interior nodes are hashed with distinct prefixes, and an odd node at
+/// any level is promoted to the next level instead of being paired with itself.
+/// Unlike the Bitcoin construction, `[a, b, c]` and `[a, b, c, c]` therefore have
+/// different roots (no CVE-2012-2459 style mutation). The leaf count is hashed into
+/// the result as well, so proofs cannot claim another width or position. Returns a
+/// 32-byte root hash.
 pub fn merkle_root(leaves: &[Vec<u8>]) -> Vec<u8> {
     if leaves.is_empty() {
         return sha256_hash(b"");
     }
 
-    let mut hashes = leaves.to_vec();
+    let mut hashes: Vec<Vec<u8>> = leaves.iter().map(|l| merkle_leaf_hash(l)).collect();
 
     while hashes.len() > 1 {
-        let mut next_level = Vec::new();
+        hashes = merkle_next_level(&hashes);
+    }
+
+    merkle_commit_root(&hashes[0], leaves.len())
+}
 
-        for i in (0..hashes.len()).step_by(2) {
-            let left = &hashes[i];
-            let right = if i + 1 < hashes.len() {
-                &hashes[i + 1]
+/// Generates the inclusion proof for the leaf at `index`.
+pub fn merkle_proof(leaves: &[Vec<u8>], index: usize) -> Option<MerkleProof> {
+    if index >= leaves.len() {
+        return None;
+    }
+
+    let mut siblings = Vec::new();
+    let mut level: Vec<Vec<u8>> = leaves.iter().map(|l| merkle_leaf_hash(l)).collect();
+    let mut position = index;
+
+    while level.len() > 1 {
+        let sibling = position ^ 1;
+        if sibling < level.len() {
+            siblings.push(level[sibling].clone());
+        }
+        level = merkle_next_level(&level);
+        position /= 2;
+    }
+
+    Some(MerkleProof {
+        index,
+        leaf_count: leaves.len(),
+        siblings,
+    })
+}
+
+/// Verifies that `leaf` is included at `proof.index` in the tree with the given root.
+pub fn verify_merkle_proof(leaf: &[u8], proof: &MerkleProof, root: &[u8]) -> bool {
+    if proof.index >= proof.leaf_count {
+        return false;
+    }
+
+    let mut hash = merkle_leaf_hash(leaf);
+    let mut position = proof.index;
+    let mut width = proof.leaf_count;
+    let mut siblings = proof.siblings.iter();
+
+    while width > 1 {
+        let sibling = position ^ 1;
+        if sibling < width {
+            let node = match siblings.next() {
+                Some(node) => node,
+                None => return false,
+            };
+            hash = if position.is_multiple_of(2) {
+                merkle_node_hash(&hash, node)
             } else {
-                &hashes[i] // pad with last if odd number
+                merkle_node_hash(node, &hash)
             };
+        }
+        position /= 2;
+        width = width.div_ceil(2);
+    }
+
+    siblings.next().is_none() && merkle_commit_root(&hash, proof.leaf_count).as_slice() == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            let mut combined = Vec::with_capacity(left.len() + right.len());
-            combined.extend_from_slice(left);
-            combined.extend_from_slice(right);
+    fn leaves(count: usize) -> Vec<Vec<u8>> {
+        (0..count).map(|i| format!("tx-{}", i).into_bytes()).collect()
+    }
 
-            let new_hash = sha256_hash(&combined);
-            next_level.push(new_hash);
+    #[test]
+    fn proves_every_leaf_of_odd_and_even_trees() {
+        for count in 1..=9 {
+            let leaves = leaves(count);
+            let root = merkle_root(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = merkle_proof(&leaves, index).unwrap();
+                assert!(verify_merkle_proof(leaf, &proof, &root), "{} of {}", index, count);
+                assert!(!verify_merkle_proof(b"other", &proof, &root));
+            }
+            assert!(merkle_proof(&leaves, count).is_none());
         }
+    }
+
+    #[test]
+    fn rejects_tampered_proofs() {
+        let leaves = leaves(3);
+        let root = merkle_root(&leaves);
+        let proof = merkle_proof(&leaves, 2).unwrap();
 
-        hashes = next_level;
+        // [a, b, c] hashes c against H(a, b); the same sibling at index 1 of a
+        // two-leaf tree would rebuild the same tree root without the count.
+        let moved = MerkleProof { index: 1, leaf_count: 2, ..proof.clone() };
+        assert!(!verify_merkle_proof(&leaves[2], &moved, &root));
+
+        let reindexed = MerkleProof { index: 1, ..proof.clone() };
+        assert!(!verify_merkle_proof(&leaves[2], &reindexed, &root));
+
+        let widened = MerkleProof { leaf_count: 4, ..proof.clone() };
+        assert!(!verify_merkle_proof(&leaves[2], &widened, &root));
+
+        let mut forged = proof.clone();
+        forged.siblings[0][0] ^= 1;
+        assert!(!verify_merkle_proof(&leaves[2], &forged, &root));
+
+        let mut padded = proof;
+        padded.siblings.push(vec![0; 32]);
+        assert!(!verify_merkle_proof(&leaves[2], &padded, &root));
     }
 
-    hashes[0].clone()
+    #[test]
+    fn root_commits_to_leaf_count() {
+        let mut leaves = leaves(3);
+        let root = merkle_root(&leaves);
+        leaves.push(leaves[2].clone());
+        assert_ne!(merkle_root(&leaves), root);
+    }
 }
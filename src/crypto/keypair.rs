@@ -7,7 +7,7 @@
 
 use k256::{
     ecdsa::SigningKey,
-    elliptic_curve::sec1::{ToEncodedPoint, FromEncodedPoint},
+    elliptic_curve::sec1::FromEncodedPoint,
     PublicKey as K256PublicKey,
     SecretKey,
 };
@@ -28,7 +28,7 @@ impl PrivateKey {
 
     /// Returns the raw 32-byte representation of the private key.
     pub fn to_bytes(&self) -> [u8; 32] {
-        self.inner.to_bytes()
+        self.inner.to_bytes().into()
     }
 
     /// Derives the associated public key.
@@ -5,21 +5,28 @@
 //! address derivation, and cryptographic utilities.
 //!
 //! ## Submodules:
-//! - `hash`: SHA-256, Blake2b, Merkle Root, etc.
+//! - `hash`: SHA-256, Blake2b, Merkle Root and inclusion proofs, etc.
 //! - `keypair`: ECDSA key generation and management.
 //! - `signature`: Message signing and verification.
+//! - `smt`: Sparse Merkle tree with membership and non-membership proofs.
+//! - `vrf`: Verifiable random function (unique, publicly checkable outputs).
 //!
 //! This module ensures all cryptographic operations are deterministic, secure, and auditable.
 
 pub mod hash;
 pub mod keypair;
 pub mod signature;
+pub mod smt;
+pub mod vrf;
 
 pub use hash::{
     sha256_hash,
     double_sha256,
     blake2b_hash,
     merkle_root,
+    merkle_proof,
+    verify_merkle_proof,
+    MerkleProof,
 };
 
 pub use keypair::{
@@ -35,6 +42,18 @@ pub use signature::{
     Signature,
 };
 
+pub use smt::{
+    SparseMerkleTree,
+    SparseMerkleProof,
+    verify_smt_proof,
+};
+
+pub use vrf::{
+    vrf_prove,
+    vrf_verify,
+    VrfProof,
+};
+
 /// Re-exports for simplified usage from external modules.
 pub mod prelude {
     pub use super::{
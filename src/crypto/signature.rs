@@ -10,10 +10,7 @@
 //!
 //! ✅ Secure, deterministic, and battle-tested.
 
-use k256::{
-    ecdsa::{Signature as K256Signature, SigningKey, VerifyingKey, signature::{Signer, Verifier}},
-    elliptic_curve::sec1::ToEncodedPoint,
-};
+use k256::ecdsa::{Signature as K256Signature, SigningKey, VerifyingKey, signature::{Signer, Verifier}};
 use crate::crypto::keypair::{PrivateKey, PublicKey};
 
 /// A digital signature (64 bytes, secp256k1).
@@ -36,7 +33,8 @@ impl Signature {
 
 /// Signs arbitrary message data using a given private key.
 pub fn sign_message(message: &[u8], private_key: &PrivateKey) -> Signature {
-    let signing_key = SigningKey::from(private_key.to_bytes());
+    let signing_key = SigningKey::from_bytes(&private_key.to_bytes().into())
+        .expect("A private key is always a valid signing key");
     let signature = signing_key.sign(message);
     Signature { inner: signature }
 }
@@ -0,0 +1,407 @@
+//! # crypto::smt
+//!
+//! A sparse Merkle tree over 256-bit keys, with membership and non-membership
+//! proofs.
+//!
+//! Conceptually every one of the 2^256 key positions is a leaf and almost all
+//! of them are empty. The tree is stored compactly:
+//! - an empty subtree hashes to [`EMPTY_HASH`] (all zeros);
+//! - a subtree holding exactly one entry is represented by that entry's leaf
+//!   hash, wherever the subtree starts;
+//! - any other subtree hashes its two halves, split on the next key bit
+//!   (most significant bit first).
+//!
+//! Leaves and interior nodes use distinct domain-separation prefixes (as in
+//! [`merkle_root`](crate::crypto::hash::merkle_root)), so a leaf can never be
+//! passed off as a node. The root depends only on the set of entries, not on
+//! the order they were inserted in.
+//!
+//! Every node caches its hash, so an insert or removal rehashes only the path
+//! down to its key and reading the root is free. Clones share their nodes and
+//! copy only the paths they later change.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::hash::sha256_hash;
+
+/// A 256-bit key or hash.
+pub type SmtHash = [u8; 32];
+
+/// Hash of an empty subtree, and the root of an empty tree.
+pub const EMPTY_HASH: SmtHash = [0u8; 32];
+
+/// Domain-separation prefix for hashing a leaf.
+const SMT_LEAF_PREFIX: u8 = 0x00;
+
+/// Domain-separation prefix for hashing two child nodes into a parent.
+const SMT_NODE_PREFIX: u8 = 0x01;
+
+/// Proof that a key is (or is not) in a tree with a given root.
+///
+/// `siblings` lists the sibling hash at every level from the root down to
+/// where the key's path ends. `leaf` is the entry found there: the key's own
+/// entry for a membership proof, and for a non-membership proof either `None`
+/// (the path ends in an empty subtree) or a different key sharing the path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SparseMerkleProof {
+    pub siblings: Vec<SmtHash>,
+    pub leaf: Option<(SmtHash, SmtHash)>,
+}
+
+/// Key-value entries (both 256-bit), committed to by a single root hash.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SparseMerkleTree {
+    root: Arc<Node>,
+    len: usize,
+}
+
+/// A subtree with its cached hash.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+enum Node {
+    /// A subtree without entries.
+    #[default]
+    Empty,
+
+    /// A subtree holding exactly one entry.
+    Leaf { key: SmtHash, value_hash: SmtHash, hash: SmtHash },
+
+    /// A subtree holding two or more entries, split on the key bit at its depth.
+    Branch { left: Arc<Node>, right: Arc<Node>, hash: SmtHash },
+}
+
+impl Node {
+    fn leaf(key: SmtHash, value_hash: SmtHash) -> Self {
+        Node::Leaf {
+            key,
+            value_hash,
+            hash: smt_leaf_hash(&key, &value_hash),
+        }
+    }
+
+    fn branch(left: Arc<Node>, right: Arc<Node>) -> Self {
+        let hash = smt_node_hash(&left.hash(), &right.hash());
+        Node::Branch { left, right, hash }
+    }
+
+    fn hash(&self) -> SmtHash {
+        match self {
+            Node::Empty => EMPTY_HASH,
+            Node::Leaf { hash, .. } | Node::Branch { hash, .. } => *hash,
+        }
+    }
+}
+
+/// Hashes arbitrary bytes into a tree key.
+pub fn smt_key(data: &[u8]) -> SmtHash {
+    to_hash(sha256_hash(data))
+}
+
+/// Hash of the leaf holding `value_hash` under `key`.
+pub fn smt_leaf_hash(key: &SmtHash, value_hash: &SmtHash) -> SmtHash {
+    let mut data = Vec::with_capacity(65);
+    data.push(SMT_LEAF_PREFIX);
+    data.extend_from_slice(key);
+    data.extend_from_slice(value_hash);
+    to_hash(sha256_hash(&data))
+}
+
+fn smt_node_hash(left: &SmtHash, right: &SmtHash) -> SmtHash {
+    let mut data = Vec::with_capacity(65);
+    data.push(SMT_NODE_PREFIX);
+    data.extend_from_slice(left);
+    data.extend_from_slice(right);
+    to_hash(sha256_hash(&data))
+}
+
+fn to_hash(bytes: Vec<u8>) -> SmtHash {
+    bytes.try_into().expect("SHA-256 digests are 32 bytes")
+}
+
+/// The bit of `key` at `depth`, most significant bit first.
+fn bit(key: &SmtHash, depth: usize) -> bool {
+    key[depth / 8] & (0x80 >> (depth % 8)) != 0
+}
+
+/// Number of leading bits `a` and `b` have in common.
+fn common_prefix_bits(a: &SmtHash, b: &SmtHash) -> usize {
+    (0..256).find(|&depth| bit(a, depth) != bit(b, depth)).unwrap_or(256)
+}
+
+/// Takes a node out of its `Arc`, copying it only if a clone still shares it.
+fn take(node: Arc<Node>) -> Node {
+    Arc::try_unwrap(node).unwrap_or_else(|shared| (*shared).clone())
+}
+
+/// Inserts or replaces an entry in the subtree `node` at `depth`.
+fn insert_at(node: Node, key: SmtHash, value_hash: SmtHash, depth: usize) -> Node {
+    match node {
+        Node::Empty => Node::leaf(key, value_hash),
+        Node::Leaf { key: existing, .. } if existing == key => Node::leaf(key, value_hash),
+        Node::Leaf { key: existing, .. } => {
+            // Two entries: push the existing one a level down and insert beside it.
+            let (left, right) = if bit(&existing, depth) {
+                (Node::Empty, node)
+            } else {
+                (node, Node::Empty)
+            };
+            insert_at(Node::branch(Arc::new(left), Arc::new(right)), key, value_hash, depth)
+        }
+        Node::Branch { left, right, .. } => {
+            if bit(&key, depth) {
+                let right = insert_at(take(right), key, value_hash, depth + 1);
+                Node::branch(left, Arc::new(right))
+            } else {
+                let left = insert_at(take(left), key, value_hash, depth + 1);
+                Node::branch(Arc::new(left), right)
+            }
+        }
+    }
+}
+
+/// Removes an entry known to be in the subtree `node` at `depth`. A branch left
+/// with a single entry collapses into that entry's leaf.
+fn remove_at(node: Node, key: &SmtHash, depth: usize) -> Node {
+    match node {
+        Node::Branch { left, right, .. } => {
+            let (left, right) = if bit(key, depth) {
+                (left, Arc::new(remove_at(take(right), key, depth + 1)))
+            } else {
+                (Arc::new(remove_at(take(left), key, depth + 1)), right)
+            };
+            match (&*left, &*right) {
+                (Node::Empty, Node::Empty) => Node::Empty,
+                (Node::Empty, Node::Leaf { .. }) => take(right),
+                (Node::Leaf { .. }, Node::Empty) => take(left),
+                _ => Node::branch(left, right),
+            }
+        }
+        _ => Node::Empty,
+    }
+}
+
+impl SparseMerkleTree {
+    /// Creates an empty tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the value hash stored under `key`.
+    pub fn insert(&mut self, key: SmtHash, value_hash: SmtHash) {
+        match self.get(&key) {
+            Some(existing) if *existing == value_hash => return,
+            Some(_) => {}
+            None => self.len += 1,
+        }
+        let root = take(std::mem::take(&mut self.root));
+        self.root = Arc::new(insert_at(root, key, value_hash, 0));
+    }
+
+    /// Removes `key`; removing a missing key is a no-op.
+    pub fn remove(&mut self, key: &SmtHash) {
+        if self.get(key).is_none() {
+            return;
+        }
+        let root = take(std::mem::take(&mut self.root));
+        self.root = Arc::new(remove_at(root, key, 0));
+        self.len -= 1;
+    }
+
+    /// Returns the value hash stored under `key`.
+    pub fn get(&self, key: &SmtHash) -> Option<&SmtHash> {
+        let mut node = &*self.root;
+        let mut depth = 0;
+        loop {
+            match node {
+                Node::Empty => return None,
+                Node::Leaf { key: leaf_key, value_hash, .. } => {
+                    return (leaf_key == key).then_some(value_hash);
+                }
+                Node::Branch { left, right, .. } => {
+                    node = if bit(key, depth) { right } else { left };
+                    depth += 1;
+                }
+            }
+        }
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the tree has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the root hash.
+    pub fn root(&self) -> SmtHash {
+        self.root.hash()
+    }
+
+    /// Builds a proof for `key`: membership if it is in the tree, non-membership otherwise.
+    pub fn prove(&self, key: &SmtHash) -> SparseMerkleProof {
+        let mut node = &*self.root;
+        let mut siblings = Vec::new();
+        let mut depth = 0;
+
+        while let Node::Branch { left, right, .. } = node {
+            if bit(key, depth) {
+                siblings.push(left.hash());
+                node = right;
+            } else {
+                siblings.push(right.hash());
+                node = left;
+            }
+            depth += 1;
+        }
+
+        SparseMerkleProof {
+            siblings,
+            leaf: match node {
+                Node::Leaf { key, value_hash, .. } => Some((*key, *value_hash)),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Verifies a proof for `key` against `root`.
+///
+/// With `Some(value_hash)` this checks that `key` holds exactly that value; with
+/// `None` it checks that `key` is absent.
+pub fn verify_smt_proof(
+    root: &SmtHash,
+    key: &SmtHash,
+    value_hash: Option<&SmtHash>,
+    proof: &SparseMerkleProof,
+) -> bool {
+    let depth = proof.siblings.len();
+    if depth > 256 {
+        return false;
+    }
+
+    let mut hash = match (value_hash, &proof.leaf) {
+        (Some(value_hash), Some((leaf_key, leaf_value))) if leaf_key == key && leaf_value == value_hash => {
+            smt_leaf_hash(key, value_hash)
+        }
+        (None, None) => EMPTY_HASH,
+        // Another key ends on this path: it must share the path and differ from `key`.
+        (None, Some((leaf_key, leaf_value))) if leaf_key != key && common_prefix_bits(leaf_key, key) >= depth => {
+            smt_leaf_hash(leaf_key, leaf_value)
+        }
+        _ => return false,
+    };
+
+    for level in (0..depth).rev() {
+        let sibling = &proof.siblings[level];
+        hash = if bit(key, level) {
+            smt_node_hash(sibling, &hash)
+        } else {
+            smt_node_hash(&hash, sibling)
+        };
+    }
+    hash == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(n: u8) -> Vec<(SmtHash, SmtHash)> {
+        (0..n).map(|i| (smt_key(&[i]), smt_key(&[i, i]))).collect()
+    }
+
+    #[test]
+    fn root_depends_only_on_the_entries() {
+        let mut forward = SparseMerkleTree::new();
+        let mut backward = SparseMerkleTree::new();
+        for (key, value) in entries(16) {
+            forward.insert(key, value);
+        }
+        for (key, value) in entries(16).into_iter().rev() {
+            backward.insert(key, value);
+        }
+        assert_eq!(forward.root(), backward.root());
+        assert_eq!(forward.len(), 16);
+
+        // Removing entries collapses the tree back to what inserting the rest gives.
+        let mut partial = SparseMerkleTree::new();
+        for (key, value) in entries(16).into_iter().take(5) {
+            partial.insert(key, value);
+        }
+        for (key, _) in entries(16).into_iter().skip(5) {
+            forward.remove(&key);
+        }
+        assert_eq!(forward.root(), partial.root());
+
+        for (key, _) in entries(5) {
+            forward.remove(&key);
+        }
+        assert!(forward.is_empty());
+        assert_eq!(forward.root(), EMPTY_HASH);
+    }
+
+    #[test]
+    fn clones_do_not_share_changes() {
+        let mut tree = SparseMerkleTree::new();
+        for (key, value) in entries(4) {
+            tree.insert(key, value);
+        }
+        let before = tree.clone();
+        tree.insert(smt_key(b"new"), smt_key(b"value"));
+        tree.remove(&entries(1)[0].0);
+        assert_ne!(tree.root(), before.root());
+        assert_eq!(before.len(), 4);
+        assert_eq!(before.get(&entries(1)[0].0), Some(&entries(1)[0].1));
+    }
+
+    #[test]
+    fn proves_membership() {
+        let mut tree = SparseMerkleTree::new();
+        for (key, value) in entries(8) {
+            tree.insert(key, value);
+        }
+        let root = tree.root();
+        for (key, value) in entries(8) {
+            let proof = tree.prove(&key);
+            assert!(verify_smt_proof(&root, &key, Some(&value), &proof));
+            assert!(!verify_smt_proof(&root, &key, Some(&smt_key(b"other")), &proof));
+            assert!(!verify_smt_proof(&root, &key, None, &proof));
+        }
+
+        let (key, value) = entries(1)[0];
+        let mut proof = tree.prove(&key);
+        proof.siblings[0][0] ^= 1;
+        assert!(!verify_smt_proof(&root, &key, Some(&value), &proof));
+    }
+
+    #[test]
+    fn proves_non_membership() {
+        let empty = SparseMerkleTree::new();
+        let missing = smt_key(b"missing");
+        assert!(verify_smt_proof(&EMPTY_HASH, &missing, None, &empty.prove(&missing)));
+
+        let mut tree = SparseMerkleTree::new();
+        for (key, value) in entries(8) {
+            tree.insert(key, value);
+        }
+        let root = tree.root();
+        let proof = tree.prove(&missing);
+        assert!(verify_smt_proof(&root, &missing, None, &proof));
+        assert!(!verify_smt_proof(&root, &missing, Some(&smt_key(b"value")), &proof));
+
+        // A neighbour that shares all but the last bit ends on the same path.
+        let mut neighbour = entries(1)[0].0;
+        neighbour[31] ^= 1;
+        let proof = tree.prove(&neighbour);
+        assert!(proof.leaf.is_some());
+        assert!(verify_smt_proof(&root, &neighbour, None, &proof));
+
+        // A present key cannot be proven absent with another key's proof.
+        let (key, _) = entries(2)[1];
+        assert!(!verify_smt_proof(&root, &key, None, &tree.prove(&missing)));
+    }
+}
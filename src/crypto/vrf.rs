@@ -0,0 +1,187 @@
+//! # crypto::vrf
+//!
+//! A verifiable random function over secp256k1, following the ECVRF
+//! construction of RFC 9381 (try-and-increment hash to curve, SHA-256
+//! challenges).
+//!
+//! Only the holder of a private key can compute the VRF output for an input,
+//! but the output is unique: unlike an ECDSA signature, whose nonce the signer
+//! picks freely, a key and an input have exactly one output, and the attached
+//! [`VrfProof`] lets anyone check it against the public key. The
+//! proof-of-stake leader draw is seeded with VRF outputs (see
+//! [`crate::consensus::pos`]) so that a proposer cannot grind the seed.
+
+use k256::elliptic_curve::ops::Reduce;
+use k256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use k256::elliptic_curve::PrimeField;
+use k256::{AffinePoint, EncodedPoint, FieldBytes, ProjectivePoint, Scalar, SecretKey, U256};
+
+use crate::crypto::hash::sha256_hash;
+use crate::crypto::keypair::{PrivateKey, PublicKey};
+
+/// Domain separator of every hash the VRF computes.
+const VRF_DOMAIN: &[u8] = b"OCOS-VRF";
+
+/// Size in bytes of an encoded [`VrfProof`]: a compressed point and two scalars.
+pub const VRF_PROOF_SIZE: usize = 33 + 32 + 32;
+
+/// Proof that a VRF output was computed with a given key from a given input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VrfProof {
+    gamma: AffinePoint,
+    c: Scalar,
+    s: Scalar,
+}
+
+impl VrfProof {
+    /// Returns the byte representation of the proof.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.gamma.to_encoded_point(true).as_bytes().to_vec();
+        bytes.extend_from_slice(&self.c.to_bytes());
+        bytes.extend_from_slice(&self.s.to_bytes());
+        bytes
+    }
+
+    /// Restores a proof from its bytes; non-canonical encodings are rejected.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != VRF_PROOF_SIZE {
+            return None;
+        }
+        Some(Self {
+            gamma: decode_point(&bytes[..33])?,
+            c: decode_scalar(&bytes[33..65])?,
+            s: decode_scalar(&bytes[65..])?,
+        })
+    }
+
+    /// The output the proof commits to. Only meaningful once [`vrf_verify`]
+    /// accepted the proof.
+    pub fn output(&self) -> [u8; 32] {
+        let mut preimage = VRF_DOMAIN.to_vec();
+        preimage.push(0x03);
+        preimage.extend_from_slice(self.gamma.to_encoded_point(true).as_bytes());
+        digest(&preimage)
+    }
+}
+
+/// Computes the VRF proof of `alpha` with `private_key`; its output is
+/// [`VrfProof::output`].
+pub fn vrf_prove(private_key: &PrivateKey, alpha: &[u8]) -> VrfProof {
+    let secret = SecretKey::from_bytes(&private_key.to_bytes().into())
+        .expect("A private key is always a valid secret scalar");
+    let x = *secret.to_nonzero_scalar();
+    let y = (ProjectivePoint::GENERATOR * x).to_affine();
+    let h = hash_to_curve(&y, alpha);
+    let gamma = (ProjectivePoint::from(h) * x).to_affine();
+
+    // Deterministic nonce from the key and the hashed input, as in RFC 6979.
+    let mut nonce = VRF_DOMAIN.to_vec();
+    nonce.push(0x04);
+    nonce.extend_from_slice(&private_key.to_bytes());
+    nonce.extend_from_slice(h.to_encoded_point(true).as_bytes());
+    let k = hash_to_scalar(&nonce);
+
+    let u = (ProjectivePoint::GENERATOR * k).to_affine();
+    let v = (ProjectivePoint::from(h) * k).to_affine();
+    let c = challenge([&y, &h, &gamma, &u, &v]);
+    VrfProof { gamma, c, s: k + c * x }
+}
+
+/// Checks `proof` for the input `alpha` under `public_key` and returns the
+/// VRF output, or `None` if the proof does not hold.
+pub fn vrf_verify(public_key: &PublicKey, alpha: &[u8], proof: &VrfProof) -> Option<[u8; 32]> {
+    let y = decode_point(&public_key.to_compressed_bytes())?;
+    let h = hash_to_curve(&y, alpha);
+    let u = ProjectivePoint::GENERATOR * proof.s - ProjectivePoint::from(y) * proof.c;
+    let v = ProjectivePoint::from(h) * proof.s - ProjectivePoint::from(proof.gamma) * proof.c;
+    let c = challenge([&y, &h, &proof.gamma, &u.to_affine(), &v.to_affine()]);
+    (c == proof.c).then(|| proof.output())
+}
+
+/// Maps `alpha` to a curve point: the first counter whose hash is the x
+/// coordinate of a point (taken with even y) wins.
+fn hash_to_curve(public_key: &AffinePoint, alpha: &[u8]) -> AffinePoint {
+    let mut preimage = VRF_DOMAIN.to_vec();
+    preimage.push(0x01);
+    preimage.extend_from_slice(public_key.to_encoded_point(true).as_bytes());
+    preimage.extend_from_slice(alpha);
+    (0..=u8::MAX)
+        .find_map(|counter| {
+            let mut candidate = vec![0x02];
+            candidate.extend_from_slice(&sha256_hash(&[preimage.as_slice(), &[counter]].concat()));
+            decode_point(&candidate)
+        })
+        .expect("Half of all x coordinates are on the curve")
+}
+
+/// Challenge scalar binding the public key, the hashed input, the output point
+/// and the two commitments.
+fn challenge(points: [&AffinePoint; 5]) -> Scalar {
+    let mut preimage = VRF_DOMAIN.to_vec();
+    preimage.push(0x02);
+    for point in points {
+        preimage.extend_from_slice(point.to_encoded_point(true).as_bytes());
+    }
+    hash_to_scalar(&preimage)
+}
+
+fn digest(preimage: &[u8]) -> [u8; 32] {
+    sha256_hash(preimage).try_into().expect("SHA-256 digests are 32 bytes")
+}
+
+fn hash_to_scalar(preimage: &[u8]) -> Scalar {
+    <Scalar as Reduce<U256>>::reduce_bytes(&FieldBytes::from(digest(preimage)))
+}
+
+fn decode_point(bytes: &[u8]) -> Option<AffinePoint> {
+    let encoded = EncodedPoint::from_bytes(bytes).ok()?;
+    Option::from(AffinePoint::from_encoded_point(&encoded))
+}
+
+fn decode_scalar(bytes: &[u8]) -> Option<Scalar> {
+    let repr: [u8; 32] = bytes.try_into().ok()?;
+    Option::from(Scalar::from_repr(repr.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proves_and_verifies_deterministically() {
+        let key = PrivateKey::generate();
+        let proof = vrf_prove(&key, b"seed");
+        assert_eq!(vrf_verify(&key.public_key(), b"seed", &proof), Some(proof.output()));
+        assert_eq!(vrf_prove(&key, b"seed"), proof);
+        assert_ne!(vrf_prove(&key, b"other seed").output(), proof.output());
+
+        let decoded = VrfProof::from_bytes(&proof.to_bytes()).unwrap();
+        assert_eq!(decoded, proof);
+        assert!(VrfProof::from_bytes(&proof.to_bytes()[1..]).is_none());
+    }
+
+    #[test]
+    fn rejects_wrong_key_and_input() {
+        let key = PrivateKey::generate();
+        let proof = vrf_prove(&key, b"seed");
+        assert_eq!(vrf_verify(&PrivateKey::generate().public_key(), b"seed", &proof), None);
+        assert_eq!(vrf_verify(&key.public_key(), b"seed!", &proof), None);
+    }
+
+    #[test]
+    fn rejects_mutated_proofs() {
+        let key = PrivateKey::generate();
+        let proof = vrf_prove(&key, b"seed");
+
+        let mut gamma = proof.clone();
+        gamma.gamma = (ProjectivePoint::from(proof.gamma) + ProjectivePoint::GENERATOR).to_affine();
+        let mut c = proof.clone();
+        c.c += Scalar::ONE;
+        let mut s = proof.clone();
+        s.s += Scalar::ONE;
+
+        for mutated in [gamma, c, s] {
+            assert_eq!(vrf_verify(&key.public_key(), b"seed", &mutated), None);
+        }
+    }
+}
@@ -2,7 +2,8 @@
 //!
 //! Provides Prometheus-compatible metrics collection for OCOS P2P network.
 //!
-//! Tracks peer count, message throughput, connection events, and block propagation latency.
+//! Tracks peer count, message throughput, connection events, block propagation latency
+//! and the local miner's hash rate.
 
 use std::sync::atomic::{AtomicUsize, AtomicU64, Ordering};
 use std::time::{Duration, Instant};
@@ -50,6 +51,17 @@ lazy_static! {
         )
         .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0])
     );
+
+    // ⛏ Mining
+    pub static ref MINER_HASH_RATE: IntGauge = register_gauge(
+        "ocos_miner_hash_rate",
+        "Hashes per second of the local miner over its current search round"
+    );
+
+    pub static ref MINER_HASHES_TOTAL: IntCounter = register_counter(
+        "ocos_miner_hashes_total",
+        "Total block header hashes computed by the local miner"
+    );
 }
 
 /// Increments peer connection count.
@@ -80,6 +92,16 @@ pub fn record_block_propagation(start_time: Instant) {
     BLOCK_PROPAGATION_TIME.observe(elapsed);
 }
 
+/// Records `hashes` new hashes of a running mining round and updates the hash
+/// rate from the `round_hashes` computed in the `elapsed` time since it started.
+pub fn record_mining_progress(hashes: u64, round_hashes: u64, elapsed: Duration) {
+    MINER_HASHES_TOTAL.inc_by(hashes);
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0.0 {
+        MINER_HASH_RATE.set((round_hashes as f64 / seconds) as i64);
+    }
+}
+
 /// Exposes metrics as a Prometheus-formatted string.
 pub fn export_prometheus_metrics() -> String {
     let mut buffer = Vec::new();
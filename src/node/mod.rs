@@ -7,6 +7,7 @@ pub mod runner;
 
 use crate::node::node::Node;
 use crate::node::runner::run_node;
+use crate::transaction::mempool::Mempool;
 
 use crate::config::config::NodeConfig;
 
@@ -19,12 +20,33 @@ use crate::config::config::NodeConfig;
 /// # Example
 /// ```
 /// let config = NodeConfig::load_from_file("config/devnet.toml");
-/// start_node(config);
+/// start_node(config).expect("node failed to start");
 /// ```
-
-pub fn start_node(config: NodeConfig) {
+///
+/// Returns an error if the chain spec, database or stored chain cannot be loaded.
+pub fn start_node(config: NodeConfig) -> Result<(), String> {
     println!("🚀 Starting OCOS Node [mode: {}]...", config.mode);
     
-    let node = Node::new(config);
+    let node = Node::new(config)?;
     run_node(node);
+    Ok(())
+}
+
+/// Rolls the node's stored chain back until the block at `height` is the tip.
+/// This backs the node binary's `rollback <height>` subcommand; it is not exposed
+/// over the API.
+///
+/// Run it while the node is stopped: it opens the chain database itself. The
+/// disconnected blocks stay stored as a side branch, and their transactions are
+/// dropped with the in-memory mempool.
+pub fn rollback_node(config: NodeConfig, height: u64) -> Result<(), String> {
+    let node = Node::new(config)?;
+    let mut blockchain = node.blockchain.lock().unwrap();
+    let disconnected = blockchain
+        .rollback_to_height(height, &mut Mempool::default())
+        .map_err(|e| format!("Rollback failed: {}", e))?;
+
+    let tip = blockchain.latest_block();
+    println!("⏪ Disconnected {} block(s); tip is now block {} ({}).", disconnected.len(), tip.index, tip.hash);
+    Ok(())
 }
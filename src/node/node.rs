@@ -1,7 +1,14 @@
 //! OCOS Node Structure
 //! Defines the internal state and components of a single node instance.
 
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
 use crate::blockchain::chain::Blockchain;
+use crate::blockchain::spec::ChainSpec;
+use crate::consensus::finality::CheckpointVote;
+use crate::consensus::miner::CancelHandle;
+use crate::storage::{ChainStore, RocksStore};
 use crate::transaction::mempool::Mempool;
 use crate::config::config::NodeConfig;
 use crate::network::peer::PeerManager;
@@ -10,7 +17,8 @@ use crate::wallet::wallet::Wallet;
 #[derive(Debug)]
 pub struct Node {
     pub config: NodeConfig,
-    pub blockchain: Blockchain,
+    /// The chain, shared with the API and sync; block production seals outside the lock.
+    pub blockchain: Arc<Mutex<Blockchain>>,
     pub mempool: Mempool,
     pub peer_manager: PeerManager,
     pub wallet: Wallet,
@@ -18,20 +26,121 @@ pub struct Node {
 
 impl Node {
     /// Creates a new OCOS Node instance from the given configuration
-    pub fn new(config: NodeConfig) -> Self {
-        let blockchain = Blockchain::new_genesis(&config);
+    pub fn new(config: NodeConfig) -> Result<Self, String> {
+        let spec = ChainSpec::load(&config.network_name)
+            .map_err(|e| format!("Cannot load chain spec '{}': {}", config.network_name, e))?;
+        let db_path = std::path::Path::new(&config.data_dir).join(&spec.name);
+        let db = RocksStore::open(&db_path).map_err(|e| format!("Cannot open chain database: {}", e))?;
+        let blockchain = Blockchain::open(spec, ChainStore::new(Arc::new(db)))
+            .map_err(|e| format!("Cannot load chain from {}: {}", db_path.display(), e))?;
+        if let Some(height) = blockchain.repaired_tip() {
+            println!("🩹 Repaired a half-applied chain tip; resumed at block {} ({}).", height, blockchain.latest_block().hash);
+        }
         let mempool = Mempool::default();
         let peer_manager = PeerManager::from_config(&config);
         let wallet = Wallet::load_or_generate(&config);
 
         println!("🧠 OCOS Node initialized: network = {}", config.network_name);
 
-        Node {
+        Ok(Node {
             config,
-            blockchain,
+            blockchain: Arc::new(Mutex::new(blockchain)),
             mempool,
             peer_manager,
             wallet,
+        })
+    }
+
+    /// Produces the next block from the mempool, if the consensus engine lets this
+    /// node's wallet propose it. Returns true if a block was added.
+    ///
+    /// The block is built from a template and sealed (mined, or signed with the
+    /// wallet key under proof of stake) without holding the chain lock. The seal is
+    /// cancelled when another block changes the tip, or after one block time so the
+    /// timestamp does not go stale; either way a fresh template is built and sealing
+    /// starts over.
+    pub fn produce_block(&mut self) -> bool {
+        let address = self.wallet.address();
+        loop {
+            let (template, parent, engine, cancel) = {
+                let mut blockchain = self.blockchain.lock().unwrap();
+                if !blockchain.may_propose(&address) {
+                    return false;
+                }
+                // The template keeps only the pending transactions that apply on the tip.
+                let template = match blockchain.block_template(&self.mempool.all(), &address) {
+                    Ok(template) => template,
+                    Err(e) => {
+                        println!("⚠️  Block production failed: {}", e);
+                        return false;
+                    }
+                };
+                let refresh = Duration::from_secs(blockchain.spec.consensus.target_block_time.max(1) as u64);
+                let cancel = CancelHandle::with_deadline(Instant::now() + refresh);
+                blockchain.cancel_on_tip_change(cancel.clone());
+                (template, blockchain.latest_block().clone(), blockchain.shared_engine(), cancel)
+            };
+
+            let mut block = template.to_block(template.coinbase.nonce, template.timestamp, 0);
+            if let Err(e) = engine.seal_block(&mut block, &parent, Some(&self.wallet.private_key), &cancel) {
+                if cancel.is_cancelled() {
+                    continue;
+                }
+                println!("⚠️  Block production failed: {}", e);
+                return false;
+            }
+
+            let mut blockchain = self.blockchain.lock().unwrap();
+            return match blockchain.accept_block(block, &mut self.mempool) {
+                Ok(connected) => {
+                    if connected {
+                        println!("📦 Produced block #{}", blockchain.latest_block().index);
+                    }
+                    connected
+                }
+                Err(e) => {
+                    println!("⚠️  Block production failed: {}", e);
+                    false
+                }
+            };
+        }
+    }
+
+    /// Signs the latest finality checkpoint block with the node's wallet, if the
+    /// network has finality and the wallet is one of its validators.
+    pub fn vote_finality(&mut self) {
+        let address = self.wallet.address();
+        let mut blockchain = self.blockchain.lock().unwrap();
+        let is_validator =
+            blockchain.engine().has_finality() && blockchain.staking_pool().is_validator(&address);
+        let interval = blockchain.spec.consensus.finality_interval;
+        if !is_validator || interval == 0 {
+            return;
+        }
+
+        let tip = blockchain.latest_block().index;
+        let height = tip - tip % interval;
+        if height == 0 || blockchain.finalized_height().is_some_and(|finalized| finalized >= height) {
+            return;
+        }
+        let block_hash = match blockchain.block_at(height) {
+            Some(block) => block.hash.clone(),
+            None => return,
+        };
+        let vote = CheckpointVote::sign(blockchain.spec.network_id, height, &block_hash, &self.wallet.private_key);
+        match blockchain.submit_finality_vote(vote) {
+            Ok(true) => println!("🔒 Block #{} finalized", height),
+            Ok(false) => {}
+            Err(e) => println!("⚠️  Finality vote rejected: {}", e),
+        }
+        // TODO: broadcast the vote to peers
+    }
+
+    /// Prunes old block bodies if the chain runs pruned. A failure is reported and
+    /// retried on the next call; the connected blocks stay in either way.
+    pub fn prune_chain(&mut self) {
+        if let Err(e) = self.blockchain.lock().unwrap().prune() {
+            println!("⚠️ Pruning failed: {}", e);
         }
     }
 
@@ -49,6 +158,6 @@ impl Node {
 
     /// Validates current state of the node
     pub fn validate(&self) -> bool {
-        self.blockchain.validate_chain()
+        self.blockchain.lock().unwrap().validate_chain().is_ok()
     }
 }
@@ -8,8 +8,9 @@ use std::time::Duration;
 
 /// Starts the OCOS node runtime:
 /// - Launches REST API server
-/// - Begins mining loop (if enabled)
-/// - Periodically syncs blockchain from peers
+/// - Begins block production (if enabled) with the network's consensus engine
+/// - Periodically syncs blockchain from peers, pruning old block bodies on a pruned node
+/// - Signs finality checkpoints when the wallet is a validator (hybrid networks)
 pub fn run_node(mut node: Node) {
     println!("🎛️  OCOS node runtime started...");
 
@@ -19,16 +20,21 @@ pub fn run_node(mut node: Node) {
         start_api_server(api_config);
     });
 
-    // ⛏ Start mining loop in main thread (basic PoW simulator)
+    // ⛏ Start block production loop in main thread, sealed by the network's consensus engine
     if node.config.enable_mining {
-        println!("⛏️  Mining enabled. Starting mining loop...");
+        let (engine, block_time) = {
+            let blockchain = node.blockchain.lock().unwrap();
+            let block_time = Duration::from_secs(blockchain.spec.consensus.target_block_time.max(1) as u64);
+            (blockchain.engine().name(), block_time)
+        };
+        println!("⛏️  Block production enabled ({}). Starting loop...", engine);
         loop {
-            thread::sleep(Duration::from_secs(10)); // simulate block time
+            thread::sleep(block_time);
+            node.produce_block();
+            node.prune_chain();
+            node.vote_finality();
 
-            // TODO: generate block from mempool and add to chain
-            println!("🔨 Mining simulated block... (placeholder)");
-
-            // broadcast mined block to peers (future)
+            // broadcast produced block to peers (future)
         }
     }
 
@@ -37,6 +43,8 @@ pub fn run_node(mut node: Node) {
         loop {
             thread::sleep(Duration::from_secs(30));
             node.sync_chain();
+            node.prune_chain();
+            node.vote_finality();
         }
     });
 
@@ -0,0 +1,478 @@
+//! # storage::chain_store
+//!
+//! Typed chain data on top of any [`KeyValueStore`].
+//!
+//! | Column     | Key                               | Value                          |
+//! |------------|-----------------------------------|--------------------------------|
+//! | `Meta`     | `genesis`, `tip`, `state_tip`, `base` | block hash                 |
+//! | `Meta`     | `prune_depth`                     | big-endian `u64`               |
+//! | `Meta`     | `address_index`                   | `1` if the index is kept       |
+//! | `Meta`     | `finalized`                       | bincode [`FinalityCheckpoint`] |
+//! | `Blocks`   | block hash                        | bincode-encoded [`Block`]      |
+//! | `Headers`  | block hash                        | 160-byte [`BlockHeader`]       |
+//! | `Heights`  | big-endian `u64` height           | active-chain block hash        |
+//! | `State`    | address                           | bincode-encoded [`Account`]    |
+//! | `TxIndex`  | transaction hash                  | bincode-encoded [`TxLocation`] |
+//! | `Snapshot` | `manifest`, big-endian `u32` index | bincode manifest or chunk     |
+//! | `Undo`     | block hash                        | bincode-encoded [`BlockUndo`]  |
+//! | `BlockHeights` | block hash                    | big-endian `u64` active height |
+//! | `AddressIndex` | address key, height, position | transaction hash               |
+//!
+//! Block hashes are stored as their hex strings, matching [`BlockHash`].
+//!
+//! `TxIndex` and `BlockHeights` cover the active chain and are always kept.
+//! `AddressIndex` is optional: an address key is the big-endian `u32` length of
+//! the address followed by its bytes, then the big-endian height and `u32`
+//! position of the transaction, so one address's history is a contiguous,
+//! height-ordered key range.
+//!
+//! Write methods only queue operations in a [`WriteBatch`]; nothing reaches the
+//! backend until [`ChainStore::commit`]. A block connection (body, heights, tx
+//! index, touched accounts and tip pointer) is one batch, so a crash can never
+//! leave a tip whose state or index was only partly written. `state_tip` records
+//! which block the stored state belongs to; it is written in the same batch as
+//! `tip`, and a mismatch between the two marks a store written non-atomically.
+//! A block's undo journal is written in the same batch that connects it.
+
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::block::{Block, BlockHash, BlockHeader};
+use crate::blockchain::snapshot::{SnapshotManifest, StateSnapshot};
+use crate::blockchain::state::{Account, WorldState};
+use crate::blockchain::undo::BlockUndo;
+use crate::consensus::finality::FinalityCheckpoint;
+use crate::storage::kv::{Column, KeyValueStore, StorageResult, WriteBatch};
+use crate::storage::memory::MemoryStore;
+
+const GENESIS_KEY: &[u8] = b"genesis";
+const TIP_KEY: &[u8] = b"tip";
+const STATE_TIP_KEY: &[u8] = b"state_tip";
+const BASE_KEY: &[u8] = b"base";
+const MANIFEST_KEY: &[u8] = b"manifest";
+const PRUNE_DEPTH_KEY: &[u8] = b"prune_depth";
+const ADDRESS_INDEX_KEY: &[u8] = b"address_index";
+const FINALIZED_KEY: &[u8] = b"finalized";
+
+/// Where an active-chain transaction was included.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxLocation {
+    pub block_hash: BlockHash,
+    pub height: u64,
+
+    /// Position of the transaction in the block (the coinbase is 0).
+    pub position: u32,
+}
+
+/// A transaction in an address's history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressTx {
+    pub tx_hash: String,
+    pub height: u64,
+    pub position: u32,
+}
+
+/// Chain-level view of a key-value store. Cloning shares the underlying store.
+#[derive(Debug, Clone)]
+pub struct ChainStore {
+    kv: Arc<dyn KeyValueStore>,
+}
+
+impl ChainStore {
+    /// Wraps a backend.
+    pub fn new(kv: Arc<dyn KeyValueStore>) -> Self {
+        Self { kv }
+    }
+
+    /// A fresh in-memory store.
+    pub fn memory() -> Self {
+        Self::new(Arc::new(MemoryStore::new()))
+    }
+
+    /// Returns the raw backend.
+    pub fn backend(&self) -> &Arc<dyn KeyValueStore> {
+        &self.kv
+    }
+
+    /// Hash of the genesis block the store was initialized with, if any.
+    pub fn genesis_hash(&self) -> StorageResult<Option<BlockHash>> {
+        self.get_string(Column::Meta, GENESIS_KEY)
+    }
+
+    /// Records the genesis block hash (done once, when the store is initialized).
+    pub fn set_genesis_hash(&self, batch: &mut WriteBatch, hash: &str) {
+        batch.put(Column::Meta, GENESIS_KEY, hash.as_bytes());
+    }
+
+    /// Hash of the active tip, if any.
+    pub fn tip_hash(&self) -> StorageResult<Option<BlockHash>> {
+        self.get_string(Column::Meta, TIP_KEY)
+    }
+
+    /// Hash of the block the stored world state corresponds to, if any.
+    pub fn state_tip_hash(&self) -> StorageResult<Option<BlockHash>> {
+        self.get_string(Column::Meta, STATE_TIP_KEY)
+    }
+
+    /// Moves the tip pointer and marks the state written in the same batch as belonging to it.
+    pub fn set_tip(&self, batch: &mut WriteBatch, hash: &str) {
+        batch.put(Column::Meta, TIP_KEY, hash.as_bytes());
+        batch.put(Column::Meta, STATE_TIP_KEY, hash.as_bytes());
+    }
+
+    /// Hash of the snapshot anchor the active chain starts from; `None` if it starts at genesis.
+    pub fn base_hash(&self) -> StorageResult<Option<BlockHash>> {
+        self.get_string(Column::Meta, BASE_KEY)
+    }
+
+    /// Configured prune depth, if the node runs pruned.
+    pub fn prune_depth(&self) -> StorageResult<Option<u64>> {
+        match self.kv.get(Column::Meta, PRUNE_DEPTH_KEY)? {
+            Some(bytes) => <[u8; 8]>::try_from(bytes.as_slice())
+                .map(|b| Some(u64::from_be_bytes(b)))
+                .map_err(|_| "Invalid prune depth in store.".to_string()),
+            None => Ok(None),
+        }
+    }
+
+    /// Records the prune depth (`None` keeps every block body).
+    pub fn set_prune_depth(&self, batch: &mut WriteBatch, depth: Option<u64>) {
+        match depth {
+            Some(depth) => batch.put(Column::Meta, PRUNE_DEPTH_KEY, &depth.to_be_bytes()),
+            None => batch.delete(Column::Meta, PRUNE_DEPTH_KEY),
+        }
+    }
+
+    /// Loads the latest finalized checkpoint, with the votes that finalized it.
+    pub fn finalized(&self) -> StorageResult<Option<FinalityCheckpoint>> {
+        match self.kv.get(Column::Meta, FINALIZED_KEY)? {
+            Some(bytes) => decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Records `checkpoint` as the latest finalized checkpoint.
+    pub fn set_finalized(&self, batch: &mut WriteBatch, checkpoint: &FinalityCheckpoint) -> StorageResult<()> {
+        batch.put(Column::Meta, FINALIZED_KEY, &encode(checkpoint)?);
+        Ok(())
+    }
+
+    /// Records the snapshot the chain starts from, and its anchor as the chain base,
+    /// replacing any previous one.
+    pub fn put_snapshot(&self, batch: &mut WriteBatch, snapshot: &StateSnapshot) -> StorageResult<()> {
+        for (key, _) in self.kv.scan(Column::Snapshot)? {
+            batch.delete(Column::Snapshot, &key);
+        }
+        batch.put(Column::Meta, BASE_KEY, snapshot.manifest.block_hash.as_bytes());
+        batch.put(Column::Snapshot, MANIFEST_KEY, &encode(&snapshot.manifest)?);
+        for chunk in &snapshot.chunks {
+            batch.put(Column::Snapshot, &chunk.index.to_be_bytes(), &encode(chunk)?);
+        }
+        Ok(())
+    }
+
+    /// Loads the snapshot the chain starts from, if any.
+    pub fn snapshot(&self) -> StorageResult<Option<StateSnapshot>> {
+        let manifest: SnapshotManifest = match self.kv.get(Column::Snapshot, MANIFEST_KEY)? {
+            Some(bytes) => decode(&bytes)?,
+            None => return Ok(None),
+        };
+        let mut chunks = Vec::with_capacity(manifest.chunk_hashes.len());
+        for index in 0..manifest.chunk_hashes.len() as u32 {
+            let bytes = self
+                .kv
+                .get(Column::Snapshot, &index.to_be_bytes())?
+                .ok_or_else(|| format!("Missing snapshot chunk {}.", index))?;
+            chunks.push(decode(&bytes)?);
+        }
+        Ok(Some(StateSnapshot { manifest, chunks }))
+    }
+
+    /// Stores a block body and its header.
+    pub fn put_block(&self, batch: &mut WriteBatch, block: &Block) -> StorageResult<()> {
+        batch.put(Column::Blocks, block.hash.as_bytes(), &encode(block)?);
+        batch.put(Column::Headers, block.hash.as_bytes(), &block.header().to_bytes());
+        Ok(())
+    }
+
+    /// Removes a block body, its header and its undo journal.
+    pub fn delete_block(&self, batch: &mut WriteBatch, hash: &str) {
+        batch.delete(Column::Blocks, hash.as_bytes());
+        batch.delete(Column::Headers, hash.as_bytes());
+        batch.delete(Column::Undo, hash.as_bytes());
+    }
+
+    /// Removes a block body and its undo journal but keeps its header (pruning).
+    pub fn delete_body(&self, batch: &mut WriteBatch, hash: &str) {
+        batch.delete(Column::Blocks, hash.as_bytes());
+        batch.delete(Column::Undo, hash.as_bytes());
+    }
+
+    /// Stores the undo journal of a block.
+    pub fn put_undo(&self, batch: &mut WriteBatch, undo: &BlockUndo) -> StorageResult<()> {
+        batch.put(Column::Undo, undo.block_hash.as_bytes(), &encode(undo)?);
+        Ok(())
+    }
+
+    /// Loads the undo journal of a block, if it was ever connected.
+    pub fn get_undo(&self, hash: &str) -> StorageResult<Option<BlockUndo>> {
+        match self.kv.get(Column::Undo, hash.as_bytes())? {
+            Some(bytes) => decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Loads a block by hash.
+    pub fn get_block(&self, hash: &str) -> StorageResult<Option<Block>> {
+        match self.kv.get(Column::Blocks, hash.as_bytes())? {
+            Some(bytes) => decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Loads a block header by hash.
+    pub fn get_header(&self, hash: &str) -> StorageResult<Option<BlockHeader>> {
+        match self.kv.get(Column::Headers, hash.as_bytes())? {
+            Some(bytes) => BlockHeader::from_bytes(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Loads every stored block, active or not, in no particular order.
+    pub fn blocks(&self) -> StorageResult<Vec<Block>> {
+        self.kv
+            .scan(Column::Blocks)?
+            .iter()
+            .map(|(_, bytes)| decode(bytes))
+            .collect()
+    }
+
+    /// Hash of the active-chain block at `height`.
+    pub fn hash_at(&self, height: u64) -> StorageResult<Option<BlockHash>> {
+        self.get_string(Column::Heights, &height.to_be_bytes())
+    }
+
+    /// Active-chain height of a block, if it is on the active chain.
+    pub fn height_of(&self, hash: &str) -> StorageResult<Option<u64>> {
+        match self.kv.get(Column::BlockHeights, hash.as_bytes())? {
+            Some(bytes) => <[u8; 8]>::try_from(bytes.as_slice())
+                .map(|b| Some(u64::from_be_bytes(b)))
+                .map_err(|_| "Invalid block height in store.".to_string()),
+            None => Ok(None),
+        }
+    }
+
+    /// Marks a stored block as part of the active chain and indexes its transactions.
+    pub fn connect(&self, batch: &mut WriteBatch, block: &Block) -> StorageResult<()> {
+        batch.put(Column::Heights, &block.index.to_be_bytes(), block.hash.as_bytes());
+        batch.put(Column::BlockHeights, block.hash.as_bytes(), &block.index.to_be_bytes());
+        for (position, tx) in block.transactions.iter().enumerate() {
+            let location = TxLocation {
+                block_hash: block.hash.clone(),
+                height: block.index,
+                position: position as u32,
+            };
+            batch.put(Column::TxIndex, tx.hash.as_bytes(), &encode(&location)?);
+        }
+        Ok(())
+    }
+
+    /// Removes a block from the active chain and drops its transaction index entries.
+    ///
+    /// Checks run against the committed store, so disconnects must be queued
+    /// before the connects of the same batch.
+    pub fn disconnect(&self, batch: &mut WriteBatch, block: &Block) -> StorageResult<()> {
+        if self.hash_at(block.index)?.as_deref() == Some(block.hash.as_str()) {
+            batch.delete(Column::Heights, &block.index.to_be_bytes());
+        }
+        batch.delete(Column::BlockHeights, block.hash.as_bytes());
+        for tx in &block.transactions {
+            if self.tx_block(&tx.hash)?.as_deref() == Some(block.hash.as_str()) {
+                batch.delete(Column::TxIndex, tx.hash.as_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the optional address index is kept.
+    pub fn address_index(&self) -> StorageResult<bool> {
+        Ok(self.kv.get(Column::Meta, ADDRESS_INDEX_KEY)?.is_some())
+    }
+
+    /// Turns the address index on or off; turning it off drops every entry.
+    pub fn set_address_index(&self, batch: &mut WriteBatch, enabled: bool) -> StorageResult<()> {
+        if enabled {
+            batch.put(Column::Meta, ADDRESS_INDEX_KEY, &[1]);
+        } else {
+            batch.delete(Column::Meta, ADDRESS_INDEX_KEY);
+            for (key, _) in self.kv.scan(Column::AddressIndex)? {
+                batch.delete(Column::AddressIndex, &key);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds every transaction of an active block to the history of its sender and recipient.
+    pub fn index_addresses(&self, batch: &mut WriteBatch, block: &Block) {
+        for (key, tx_hash) in address_entries(block) {
+            batch.put(Column::AddressIndex, &key, tx_hash.as_bytes());
+        }
+    }
+
+    /// Removes a disconnected block's transactions from address histories.
+    pub fn unindex_addresses(&self, batch: &mut WriteBatch, block: &Block) {
+        for (key, _) in address_entries(block) {
+            batch.delete(Column::AddressIndex, &key);
+        }
+    }
+
+    /// History of an address, oldest first.
+    pub fn address_txs(&self, address: &str) -> StorageResult<Vec<AddressTx>> {
+        let prefix = address_prefix(address);
+        self.kv
+            .scan_prefix(Column::AddressIndex, &prefix)?
+            .into_iter()
+            .map(|(key, value)| {
+                let suffix = &key[prefix.len()..];
+                let height = <[u8; 8]>::try_from(suffix.get(..8).unwrap_or_default());
+                let position = <[u8; 4]>::try_from(suffix.get(8..).unwrap_or_default());
+                match (height, position, String::from_utf8(value)) {
+                    (Ok(height), Ok(position), Ok(tx_hash)) => Ok(AddressTx {
+                        tx_hash,
+                        height: u64::from_be_bytes(height),
+                        position: u32::from_be_bytes(position),
+                    }),
+                    _ => Err("Invalid address index entry in store.".to_string()),
+                }
+            })
+            .collect()
+    }
+
+    /// Drops the height entry of a block that is no longer (or never was) fully connected.
+    pub fn clear_height(&self, batch: &mut WriteBatch, height: u64) {
+        batch.delete(Column::Heights, &height.to_be_bytes());
+    }
+
+    /// Highest height with an active-chain entry, if any.
+    pub fn max_height(&self) -> StorageResult<Option<u64>> {
+        Ok(self
+            .kv
+            .scan(Column::Heights)?
+            .last()
+            .and_then(|(key, _)| <[u8; 8]>::try_from(key.as_slice()).ok())
+            .map(u64::from_be_bytes))
+    }
+
+    /// Hash of the active-chain block containing a transaction.
+    pub fn tx_block(&self, tx_hash: &str) -> StorageResult<Option<BlockHash>> {
+        Ok(self.tx_location(tx_hash)?.map(|location| location.block_hash))
+    }
+
+    /// Active-chain block and position of a transaction.
+    pub fn tx_location(&self, tx_hash: &str) -> StorageResult<Option<TxLocation>> {
+        match self.kv.get(Column::TxIndex, tx_hash.as_bytes())? {
+            Some(bytes) => decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes the current account of each address, deleting accounts that are now empty.
+    pub fn write_accounts<'a, I>(&self, batch: &mut WriteBatch, state: &WorldState, addresses: I) -> StorageResult<()>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        for address in addresses {
+            let account = state.account(address);
+            if account == Account::default() {
+                batch.delete(Column::State, address.as_bytes());
+            } else {
+                batch.put(Column::State, address.as_bytes(), &encode(&account)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the whole stored world state with `state`.
+    pub fn replace_state(&self, batch: &mut WriteBatch, state: &WorldState) -> StorageResult<()> {
+        for (key, _) in self.kv.scan(Column::State)? {
+            batch.delete(Column::State, &key);
+        }
+        for (address, account) in state.accounts() {
+            if *account != Account::default() {
+                batch.put(Column::State, address.as_bytes(), &encode(account)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads the stored world state.
+    pub fn load_state(&self) -> StorageResult<WorldState> {
+        let mut state = WorldState::new();
+        for (key, bytes) in self.kv.scan(Column::State)? {
+            let address = String::from_utf8(key).map_err(|_| "Invalid address key in state.".to_string())?;
+            state.set_account(&address, decode(&bytes)?);
+        }
+        Ok(state)
+    }
+
+    /// Applies a batch atomically.
+    pub fn commit(&self, batch: WriteBatch) -> StorageResult<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        self.kv.write(batch)
+    }
+
+    /// Forces buffered writes to disk.
+    pub fn flush(&self) -> StorageResult<()> {
+        self.kv.flush()
+    }
+
+    fn get_string(&self, column: Column, key: &[u8]) -> StorageResult<Option<String>> {
+        match self.kv.get(column, key)? {
+            Some(bytes) => String::from_utf8(bytes)
+                .map(Some)
+                .map_err(|_| format!("Invalid UTF-8 value in column '{}'.", column.name())),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Key prefix of an address's history in the address index.
+fn address_prefix(address: &str) -> Vec<u8> {
+    let mut prefix = Vec::with_capacity(4 + address.len());
+    prefix.extend_from_slice(&(address.len() as u32).to_be_bytes());
+    prefix.extend_from_slice(address.as_bytes());
+    prefix
+}
+
+/// Address index keys of a block's transactions (one per distinct address of
+/// each transaction; the coinbase sender is not an address), with the
+/// transaction hashes.
+fn address_entries(block: &Block) -> Vec<(Vec<u8>, &str)> {
+    let mut entries = Vec::new();
+    for (position, tx) in block.transactions.iter().enumerate() {
+        let addresses = if tx.is_coinbase() || tx.from == tx.to {
+            vec![&tx.to]
+        } else {
+            vec![&tx.from, &tx.to]
+        };
+        for address in addresses {
+            let mut key = address_prefix(address);
+            key.extend_from_slice(&block.index.to_be_bytes());
+            key.extend_from_slice(&(position as u32).to_be_bytes());
+            entries.push((key, tx.hash.as_str()));
+        }
+    }
+    entries
+}
+
+fn encode<T: Serialize>(value: &T) -> StorageResult<Vec<u8>> {
+    bincode::serialize(value).map_err(|e| format!("Serialization error: {}", e))
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> StorageResult<T> {
+    bincode::deserialize(bytes).map_err(|e| format!("Deserialization error: {}", e))
+}
@@ -0,0 +1,201 @@
+//! # storage::kv
+//!
+//! The key-value abstraction every storage backend implements.
+//!
+//! Data is split into [`Column`]s (separate key namespaces, mapped to RocksDB
+//! column families on disk). Keys and values are raw bytes; the typed layout
+//! lives in [`ChainStore`](crate::storage::chain_store::ChainStore).
+//!
+//! Related changes are grouped in a [`WriteBatch`] and applied with
+//! [`KeyValueStore::write`], which every backend must apply atomically: after a
+//! crash either all of the batch is visible or none of it is.
+
+use std::fmt;
+
+/// Result type of storage operations.
+pub type StorageResult<T> = Result<T, String>;
+
+/// A key namespace in the store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Column {
+    /// Chain metadata: genesis hash, tip pointer, snapshot base.
+    Meta,
+    /// Full blocks by block hash (active chain and side branches).
+    Blocks,
+    /// Encoded block headers by block hash.
+    Headers,
+    /// Active-chain block hash by big-endian height.
+    Heights,
+    /// Accounts by address.
+    State,
+    /// Block hash containing each transaction, by transaction hash.
+    TxIndex,
+    /// The state snapshot a fast-synced chain started from.
+    Snapshot,
+    /// Undo journal of each connected block, by block hash.
+    Undo,
+    /// Active-chain height of each block, by block hash.
+    BlockHeights,
+    /// Transactions touching each address (optional explorer index).
+    AddressIndex,
+}
+
+impl Column {
+    /// Every column, in a fixed order.
+    pub const ALL: [Column; 10] = [
+        Column::Meta,
+        Column::Blocks,
+        Column::Headers,
+        Column::Heights,
+        Column::State,
+        Column::TxIndex,
+        Column::Snapshot,
+        Column::Undo,
+        Column::BlockHeights,
+        Column::AddressIndex,
+    ];
+
+    /// Stable on-disk name of the column.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Meta => "meta",
+            Column::Blocks => "blocks",
+            Column::Headers => "headers",
+            Column::Heights => "heights",
+            Column::State => "state",
+            Column::TxIndex => "tx_index",
+            Column::Snapshot => "snapshot",
+            Column::Undo => "undo",
+            Column::BlockHeights => "block_heights",
+            Column::AddressIndex => "address_index",
+        }
+    }
+}
+
+/// A single write inside a [`WriteBatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOp {
+    Put { column: Column, key: Vec<u8>, value: Vec<u8> },
+    Delete { column: Column, key: Vec<u8> },
+}
+
+/// An ordered list of writes applied atomically; later operations win.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteBatch {
+    ops: Vec<BatchOp>,
+}
+
+impl WriteBatch {
+    /// Creates an empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a write of `value` under `key`.
+    pub fn put(&mut self, column: Column, key: &[u8], value: &[u8]) {
+        self.ops.push(BatchOp::Put {
+            column,
+            key: key.to_vec(),
+            value: value.to_vec(),
+        });
+    }
+
+    /// Queues the removal of `key`.
+    pub fn delete(&mut self, column: Column, key: &[u8]) {
+        self.ops.push(BatchOp::Delete {
+            column,
+            key: key.to_vec(),
+        });
+    }
+
+    /// Returns the queued operations, in order.
+    pub fn ops(&self) -> &[BatchOp] {
+        &self.ops
+    }
+
+    /// Returns the number of queued operations.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns true if nothing is queued.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+/// A persistent (or in-memory) byte-oriented key-value store.
+pub trait KeyValueStore: Send + Sync + fmt::Debug {
+    /// Reads the value stored under `key`, if any.
+    fn get(&self, column: Column, key: &[u8]) -> StorageResult<Option<Vec<u8>>>;
+
+    /// Stores `value` under `key`, replacing any previous value.
+    fn put(&self, column: Column, key: &[u8], value: &[u8]) -> StorageResult<()>;
+
+    /// Removes `key`; removing a missing key is not an error.
+    fn delete(&self, column: Column, key: &[u8]) -> StorageResult<()>;
+
+    /// Applies every operation of the batch atomically and in order.
+    fn write(&self, batch: WriteBatch) -> StorageResult<()>;
+
+    /// Returns every entry of a column in ascending key order.
+    fn scan(&self, column: Column) -> StorageResult<Vec<(Vec<u8>, Vec<u8>)>>;
+
+    /// Returns the entries of a column whose key starts with `prefix`, in ascending key order.
+    fn scan_prefix(&self, column: Column, prefix: &[u8]) -> StorageResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        Ok(self
+            .scan(column)?
+            .into_iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .collect())
+    }
+
+    /// Forces buffered writes to durable storage.
+    fn flush(&self) -> StorageResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{MemoryStore, RocksStore};
+
+    fn exercise(store: &dyn KeyValueStore) {
+        store.put(Column::Blocks, b"b", b"2").unwrap();
+        store.put(Column::Blocks, b"a", b"1").unwrap();
+        store.put(Column::State, b"a", b"other column").unwrap();
+        assert_eq!(store.get(Column::Blocks, b"a").unwrap(), Some(b"1".to_vec()));
+
+        store.put(Column::Blocks, b"a", b"3").unwrap();
+        assert_eq!(
+            store.scan(Column::Blocks).unwrap(),
+            vec![(b"a".to_vec(), b"3".to_vec()), (b"b".to_vec(), b"2".to_vec())]
+        );
+
+        store.delete(Column::Blocks, b"a").unwrap();
+        store.delete(Column::Blocks, b"missing").unwrap();
+        assert_eq!(store.get(Column::Blocks, b"a").unwrap(), None);
+        assert_eq!(store.get(Column::State, b"a").unwrap(), Some(b"other column".to_vec()));
+        assert!(store.scan(Column::TxIndex).unwrap().is_empty());
+        store.flush().unwrap();
+    }
+
+    #[test]
+    fn memory_backend() {
+        exercise(&MemoryStore::new());
+    }
+
+    #[test]
+    fn rocks_backend_persists_across_reopen() {
+        let path = std::env::temp_dir().join(format!("ocos-kv-test-{}", std::process::id()));
+        {
+            let store = RocksStore::open(&path).unwrap();
+            exercise(&store);
+        }
+        let reopened = RocksStore::open(&path).unwrap();
+        assert_eq!(reopened.get(Column::Blocks, b"b").unwrap(), Some(b"2".to_vec()));
+        drop(reopened);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
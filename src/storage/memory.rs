@@ -0,0 +1,84 @@
+//! # storage::memory
+//!
+//! A [`KeyValueStore`] kept entirely in memory, for tests, devnets and
+//! throwaway nodes. Nothing survives a restart.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+
+use crate::storage::kv::{BatchOp, Column, KeyValueStore, StorageResult, WriteBatch};
+
+type ColumnData = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// In-memory backend; keys are kept sorted so scans match the on-disk backend.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    columns: RwLock<HashMap<Column, ColumnData>>,
+}
+
+impl MemoryStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KeyValueStore for MemoryStore {
+    fn get(&self, column: Column, key: &[u8]) -> StorageResult<Option<Vec<u8>>> {
+        let columns = self.columns.read().map_err(|_| "Memory store lock poisoned.".to_string())?;
+        Ok(columns.get(&column).and_then(|data| data.get(key).cloned()))
+    }
+
+    fn put(&self, column: Column, key: &[u8], value: &[u8]) -> StorageResult<()> {
+        let mut columns = self.columns.write().map_err(|_| "Memory store lock poisoned.".to_string())?;
+        columns.entry(column).or_default().insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, column: Column, key: &[u8]) -> StorageResult<()> {
+        let mut columns = self.columns.write().map_err(|_| "Memory store lock poisoned.".to_string())?;
+        if let Some(data) = columns.get_mut(&column) {
+            data.remove(key);
+        }
+        Ok(())
+    }
+
+    fn write(&self, batch: WriteBatch) -> StorageResult<()> {
+        // One write lock for the whole batch: readers see all of it or none of it.
+        let mut columns = self.columns.write().map_err(|_| "Memory store lock poisoned.".to_string())?;
+        for op in batch.ops() {
+            match op {
+                BatchOp::Put { column, key, value } => {
+                    columns.entry(*column).or_default().insert(key.clone(), value.clone());
+                }
+                BatchOp::Delete { column, key } => {
+                    if let Some(data) = columns.get_mut(column) {
+                        data.remove(key);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn scan(&self, column: Column) -> StorageResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let columns = self.columns.read().map_err(|_| "Memory store lock poisoned.".to_string())?;
+        Ok(columns
+            .get(&column)
+            .map(|data| data.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+            .unwrap_or_default())
+    }
+
+    fn scan_prefix(&self, column: Column, prefix: &[u8]) -> StorageResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let columns = self.columns.read().map_err(|_| "Memory store lock poisoned.".to_string())?;
+        Ok(columns
+            .get(&column)
+            .map(|data| {
+                data.range(prefix.to_vec()..)
+                    .take_while(|(k, _)| k.starts_with(prefix))
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
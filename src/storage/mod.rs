@@ -0,0 +1,25 @@
+//! # Storage Module
+//!
+//! Persistent storage for the OCOS chain: blocks, headers, the active-chain
+//! height index, account state, the transaction index and the optional
+//! address index used by block explorers.
+//!
+//! ## Submodules
+//! - `kv`: The [`KeyValueStore`] trait, its [`Column`] namespaces and atomic [`WriteBatch`]es
+//! - `memory`: In-memory backend for tests and throwaway nodes
+//! - `rocks`: Embedded on-disk backend (RocksDB)
+//! - `chain_store`: Typed chain data layout on top of any backend
+//!
+//! A [`Blockchain`](crate::blockchain::Blockchain) opened on a [`ChainStore`]
+//! commits every accepted block and state change to it in atomic batches and reloads the
+//! active chain, side branches and state from it at startup.
+
+pub mod chain_store;
+pub mod kv;
+pub mod memory;
+pub mod rocks;
+
+pub use chain_store::{AddressTx, ChainStore, TxLocation};
+pub use kv::{BatchOp, Column, KeyValueStore, StorageResult, WriteBatch};
+pub use memory::MemoryStore;
+pub use rocks::RocksStore;
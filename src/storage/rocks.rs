@@ -0,0 +1,106 @@
+//! # storage::rocks
+//!
+//! The embedded on-disk backend, built on RocksDB. Each [`Column`] maps to a
+//! RocksDB column family of the same name; missing families are created when
+//! the database is opened. Batches map onto RocksDB write batches, which are
+//! atomic and recorded in the write-ahead log.
+
+use std::fmt;
+use std::path::Path;
+
+use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, Direction, IteratorMode, Options, DB};
+
+use crate::storage::kv::{BatchOp, Column, KeyValueStore, StorageResult, WriteBatch};
+
+/// RocksDB-backed store.
+pub struct RocksStore {
+    db: DB,
+}
+
+impl RocksStore {
+    /// Opens (or creates) the database at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> StorageResult<Self> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+
+        let families = Column::ALL
+            .iter()
+            .map(|column| ColumnFamilyDescriptor::new(column.name(), Options::default()));
+        let db = DB::open_cf_descriptors(&options, path.as_ref(), families).map_err(|e| {
+            format!("Cannot open database at {}: {}", path.as_ref().display(), e)
+        })?;
+        Ok(Self { db })
+    }
+
+    fn family(&self, column: Column) -> StorageResult<&ColumnFamily> {
+        self.db
+            .cf_handle(column.name())
+            .ok_or_else(|| format!("Missing column family '{}'.", column.name()))
+    }
+}
+
+impl KeyValueStore for RocksStore {
+    fn get(&self, column: Column, key: &[u8]) -> StorageResult<Option<Vec<u8>>> {
+        self.db
+            .get_cf(self.family(column)?, key)
+            .map_err(|e| format!("Database read error: {}", e))
+    }
+
+    fn put(&self, column: Column, key: &[u8], value: &[u8]) -> StorageResult<()> {
+        self.db
+            .put_cf(self.family(column)?, key, value)
+            .map_err(|e| format!("Database write error: {}", e))
+    }
+
+    fn delete(&self, column: Column, key: &[u8]) -> StorageResult<()> {
+        self.db
+            .delete_cf(self.family(column)?, key)
+            .map_err(|e| format!("Database write error: {}", e))
+    }
+
+    fn write(&self, batch: WriteBatch) -> StorageResult<()> {
+        let mut rocks_batch = rocksdb::WriteBatch::default();
+        for op in batch.ops() {
+            match op {
+                BatchOp::Put { column, key, value } => rocks_batch.put_cf(self.family(*column)?, key, value),
+                BatchOp::Delete { column, key } => rocks_batch.delete_cf(self.family(*column)?, key),
+            }
+        }
+        self.db
+            .write(rocks_batch)
+            .map_err(|e| format!("Database write error: {}", e))
+    }
+
+    fn scan(&self, column: Column) -> StorageResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut entries = Vec::new();
+        for item in self.db.iterator_cf(self.family(column)?, IteratorMode::Start) {
+            let (key, value) = item.map_err(|e| format!("Database read error: {}", e))?;
+            entries.push((key.to_vec(), value.to_vec()));
+        }
+        Ok(entries)
+    }
+
+    fn scan_prefix(&self, column: Column, prefix: &[u8]) -> StorageResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut entries = Vec::new();
+        let mode = IteratorMode::From(prefix, Direction::Forward);
+        for item in self.db.iterator_cf(self.family(column)?, mode) {
+            let (key, value) = item.map_err(|e| format!("Database read error: {}", e))?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            entries.push((key.to_vec(), value.to_vec()));
+        }
+        Ok(entries)
+    }
+
+    fn flush(&self) -> StorageResult<()> {
+        self.db.flush().map_err(|e| format!("Database flush error: {}", e))
+    }
+}
+
+impl fmt::Debug for RocksStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RocksStore").field("path", &self.db.path()).finish()
+    }
+}
@@ -67,4 +67,45 @@ impl Mempool {
     pub fn contains(&self, tx_hash: &str) -> bool {
         self.index.contains_key(tx_hash)
     }
+
+    /// Removes transactions that were included in a connected block
+    pub fn remove_included(&mut self, included: &[Transaction]) {
+        if included.iter().all(|tx| !self.index.contains_key(&tx.hash)) {
+            return;
+        }
+        let hashes: std::collections::HashSet<&str> =
+            included.iter().map(|tx| tx.hash.as_str()).collect();
+        self.pool.retain(|tx| !hashes.contains(tx.hash.as_str()));
+        self.rebuild_index();
+    }
+
+    /// Puts transactions from disconnected (orphaned) blocks back at the front of the queue.
+    ///
+    /// Transactions already pending are skipped; original block order is preserved.
+    pub fn reinsert(&mut self, orphaned: Vec<Transaction>) {
+        for tx in orphaned.into_iter().rev() {
+            if self.index.contains_key(&tx.hash) || self.pool.len() >= MAX_MEMPOOL_SIZE {
+                continue;
+            }
+            self.index.insert(tx.hash.clone(), 0);
+            self.pool.push_front(tx);
+        }
+        self.rebuild_index();
+    }
+
+    /// Recomputes hash -> position after the queue was reordered
+    fn rebuild_index(&mut self) {
+        self.index = self
+            .pool
+            .iter()
+            .enumerate()
+            .map(|(i, tx)| (tx.hash.clone(), i))
+            .collect();
+    }
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -20,7 +20,7 @@ pub mod mempool;
 pub mod validator;
 pub mod receipt;
 
-pub use tx::Transaction;
+pub use tx::{Transaction, TxKind};
 pub use mempool::Mempool;
 pub use validator::TransactionValidator;
 pub use receipt::TransactionReceipt;
@@ -3,15 +3,53 @@ use sha2::{Sha256, Digest};
 use hex::encode;
 use chrono::{Utc, DateTime};
 
+use crate::consensus::slashing::EquivocationEvidence;
+use crate::crypto::keypair::PrivateKey;
+use crate::crypto::signature::sign_message;
+
+/// Sender used by coinbase transactions; it never holds a balance.
+pub const COINBASE_SENDER: &str = "0x0";
+
+/// Domain separator of the signed transaction message.
+const TX_DOMAIN: &[u8] = b"OCOS-TX";
+
+/// What a transaction does when applied to the world state.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TxKind {
+    /// Moves `amount` (plus `fee`) from `from` to `to`.
+    #[default]
+    Transfer,
+    /// Creates new coins for the block producer; must be the first transaction of a block.
+    Coinbase,
+    /// Locks `amount` of the sender's balance as validator stake (`to` is the sender).
+    Stake,
+    /// Starts unbonding `amount` of the sender's stake (`to` is the sender); it
+    /// becomes spendable again after the chain spec's unbonding period.
+    Unstake,
+    /// Submits proof that `to` signed two blocks on the same parent; `amount` is zero
+    /// and the offender's stake is slashed.
+    Evidence,
+}
+
 /// The core transaction structure used in the OCOS blockchain.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Transaction {
     pub from: String,           // Sender public key or address
     pub to: String,             // Recipient public key or address
     pub amount: u64,            // Token or coin amount to transfer
+    #[serde(default)]
+    pub fee: u64,               // Fee paid to the block producer
+    #[serde(default)]
+    pub nonce: u64,             // Sender's account nonce (replay protection); extranonce for coinbase
+    #[serde(default)]
+    pub kind: TxKind,           // Transfer, coinbase, stake, unstake or evidence
+    #[serde(default)]
+    pub evidence: Option<EquivocationEvidence>, // Double-signing proof (evidence transactions only)
     pub message: String,        // Optional message or metadata
     pub timestamp: String,      // RFC3339 timestamp
-    pub signature: Option<String>, // Digital signature (optional)
+    #[serde(default)]
+    pub public_key: Option<String>, // Hex uncompressed public key of the sender
+    pub signature: Option<String>, // Hex DER signature over the signing bytes
     pub hash: String,           // Unique hash of the transaction
 }
 
@@ -21,18 +59,112 @@ impl Transaction {
         let now: DateTime<Utc> = Utc::now();
         let timestamp = now.to_rfc3339();
 
-        let raw = format!("{}{}{}{}{}", from, to, amount, message, &timestamp);
-        let hash = Self::calculate_hash(&raw);
-
-        Self {
+        let mut tx = Self {
             from: from.to_string(),
             to: to.to_string(),
             amount,
+            fee: 0,
+            nonce: 0,
+            kind: TxKind::Transfer,
+            evidence: None,
             message: message.to_string(),
             timestamp,
+            public_key: None,
+            signature: None,
+            hash: String::new(),
+        };
+        tx.hash = tx.compute_hash();
+        tx
+    }
+
+    /// Creates the coinbase transaction paying `amount` to the producer of block `height`.
+    ///
+    /// The height is part of the message so every coinbase has a unique hash.
+    pub fn coinbase(height: u64, to: &str, amount: u64, timestamp: &str) -> Self {
+        let mut tx = Self {
+            from: COINBASE_SENDER.to_string(),
+            to: to.to_string(),
+            amount,
+            fee: 0,
+            nonce: 0,
+            kind: TxKind::Coinbase,
+            evidence: None,
+            message: format!("coinbase:{}", height),
+            timestamp: timestamp.to_string(),
+            public_key: None,
             signature: None,
-            hash,
+            hash: String::new(),
+        };
+        tx.hash = tx.compute_hash();
+        tx
+    }
+
+    /// Creates an unsigned transaction locking `amount` of `staker`'s balance as stake.
+    pub fn stake(staker: &str, amount: u64) -> Self {
+        Self::new(staker, staker, amount, "stake").with_kind(TxKind::Stake)
+    }
+
+    /// Creates an unsigned transaction unbonding `amount` of `staker`'s stake.
+    pub fn unstake(staker: &str, amount: u64) -> Self {
+        Self::new(staker, staker, amount, "unstake").with_kind(TxKind::Unstake)
+    }
+
+    /// Creates an unsigned transaction in which `reporter` submits equivocation
+    /// evidence against the validator that signed it.
+    pub fn evidence(reporter: &str, evidence: EquivocationEvidence) -> Self {
+        let offender = evidence.accused().unwrap_or_default();
+        let mut tx = Self::new(reporter, &offender, 0, "evidence");
+        tx.kind = TxKind::Evidence;
+        tx.evidence = Some(evidence);
+        tx.hash = tx.compute_hash();
+        tx
+    }
+
+    /// Sets the kind of the transaction and refreshes the transaction hash
+    pub fn with_kind(mut self, kind: TxKind) -> Self {
+        self.kind = kind;
+        self.hash = self.compute_hash();
+        self
+    }
+
+    /// Sets the fee offered to the block producer and refreshes the transaction hash
+    pub fn with_fee(mut self, fee: u64) -> Self {
+        self.fee = fee;
+        self.hash = self.compute_hash();
+        self
+    }
+
+    /// Sets the sender nonce and refreshes the transaction hash
+    pub fn with_nonce(mut self, nonce: u64) -> Self {
+        self.nonce = nonce;
+        self.hash = self.compute_hash();
+        self
+    }
+
+    /// Computes the hash over all signed fields of the transaction
+    ///
+    /// Every field is length-prefixed, so no two transactions share a preimage.
+    /// Evidence, when present, is committed to by its digest.
+    pub fn compute_hash(&self) -> String {
+        let kind = format!("{:?}", self.kind);
+        let evidence = self.evidence.as_ref().map(|evidence| evidence.digest()).unwrap_or_default();
+        let fields: [&[u8]; 9] = [
+            kind.as_bytes(),
+            self.from.as_bytes(),
+            self.to.as_bytes(),
+            &self.amount.to_le_bytes(),
+            &self.fee.to_le_bytes(),
+            &self.nonce.to_le_bytes(),
+            self.message.as_bytes(),
+            self.timestamp.as_bytes(),
+            evidence.as_bytes(),
+        ];
+        let mut hasher = Sha256::new();
+        for field in fields {
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field);
         }
+        encode(hasher.finalize())
     }
 
     /// Hash function using SHA256
@@ -42,13 +174,57 @@ impl Transaction {
         encode(hasher.finalize())
     }
 
-    /// Signs the transaction with a given signature
-    pub fn sign(&mut self, signature: &str) {
-        self.signature = Some(signature.to_string());
+    /// The bytes the sender signs: a domain tag, the network id and the hash over
+    /// every signed field, so a signature cannot be replayed on another network
+    pub fn signing_bytes(&self, network_id: u32) -> Vec<u8> {
+        let mut bytes = TX_DOMAIN.to_vec();
+        bytes.extend_from_slice(&network_id.to_le_bytes());
+        bytes.extend_from_slice(self.compute_hash().as_bytes());
+        bytes
+    }
+
+    /// Signs the transaction for network `network_id` with the sender's private key
+    pub fn sign(&mut self, network_id: u32, private_key: &PrivateKey) {
+        self.public_key = Some(encode(private_key.public_key().to_bytes()));
+        self.signature = Some(encode(sign_message(&self.signing_bytes(network_id), private_key).to_bytes()));
+    }
+
+    /// Size the transaction adds to an encoded block, in bytes.
+    pub fn encoded_size(&self) -> u64 {
+        bincode::serialized_size(self).unwrap_or(u64::MAX)
     }
 
     /// Checks if transaction has a signature
     pub fn is_signed(&self) -> bool {
         self.signature.is_some()
     }
+
+    /// Checks if this is a coinbase (block reward) transaction
+    pub fn is_coinbase(&self) -> bool {
+        self.kind == TxKind::Coinbase
+    }
+
+    /// Checks if this transaction locks or unbonds stake
+    pub fn is_staking(&self) -> bool {
+        matches!(self.kind, TxKind::Stake | TxKind::Unstake)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_field_boundaries() {
+        let tx = Transaction::new("alice|bob", "carol", 1, "");
+        let mut shifted = tx.clone();
+        shifted.from = "alice".to_string();
+        shifted.to = "bob|carol".to_string();
+        assert_ne!(shifted.compute_hash(), tx.hash);
+
+        let mut moved = tx.clone();
+        moved.message = tx.timestamp.clone();
+        moved.timestamp = String::new();
+        assert_ne!(moved.compute_hash(), tx.hash);
+    }
 }
@@ -1,4 +1,8 @@
-use crate::transaction::Transaction;
+use crate::blockchain::state::WorldState;
+use crate::crypto::keypair::PublicKey;
+use crate::crypto::signature::{verify_signature, Signature};
+use crate::transaction::{Transaction, TxKind};
+use crate::wallet::wallet::derive_address;
 
 /// The TransactionValidator struct is responsible for validating
 /// pending transactions before they are accepted into the mempool
@@ -6,7 +10,7 @@ use crate::transaction::Transaction;
 ///
 /// This includes:
 /// - Structural validation (fields not empty, amount > 0)
-/// - Signature presence (and later, signature verification)
+/// - Signature verification against the sender's address
 /// - Optional: balance check, nonce validation, fee policy
 pub struct TransactionValidator;
 
@@ -19,12 +23,35 @@ impl TransactionValidator {
         if tx.to.trim().is_empty() {
             return Err("Recipient address is empty.".into());
         }
-        if tx.amount == 0 {
-            return Err("Transaction amount must be greater than zero.".into());
+        if tx.kind == TxKind::Evidence {
+            if tx.amount != 0 {
+                return Err("Evidence transactions must not move an amount.".into());
+            }
+            if tx.evidence.is_none() {
+                return Err("Evidence transaction carries no evidence.".into());
+            }
+        } else {
+            if tx.amount == 0 {
+                return Err("Transaction amount must be greater than zero.".into());
+            }
+            if tx.evidence.is_some() {
+                return Err("Only evidence transactions may carry evidence.".into());
+            }
         }
         if tx.message.len() > 512 {
             return Err("Transaction message is too long.".into());
         }
+        if tx.is_staking() && tx.to != tx.from {
+            return Err("Stake and unstake transactions must name the sender as recipient.".into());
+        }
+        Ok(())
+    }
+
+    /// Rejects coinbase transactions, which are created by block producers and never relayed
+    pub fn is_not_coinbase(tx: &Transaction) -> Result<(), String> {
+        if tx.is_coinbase() {
+            return Err("Coinbase transactions cannot be submitted to the mempool.".into());
+        }
         Ok(())
     }
 
@@ -36,12 +63,94 @@ impl TransactionValidator {
         Ok(())
     }
 
-    /// Overall validator entrypoint
-    pub fn validate(tx: &Transaction) -> Result<(), String> {
+    /// Checks that the transaction is signed for `network_id` by the key the sender
+    /// address is derived from
+    pub fn verify_signature(tx: &Transaction, network_id: u32) -> Result<(), String> {
+        let public_key = tx
+            .public_key
+            .as_deref()
+            .and_then(|key| hex::decode(key).ok())
+            .and_then(|bytes| PublicKey::from_bytes(&bytes))
+            .ok_or_else(|| "Transaction carries no valid public key.".to_string())?;
+        if derive_address(&public_key) != tx.from {
+            return Err(format!("Public key does not belong to the sender {}.", tx.from));
+        }
+        let signature = tx
+            .signature
+            .as_deref()
+            .and_then(|signature| hex::decode(signature).ok())
+            .and_then(|bytes| Signature::from_bytes(&bytes))
+            .ok_or_else(|| "Transaction signature is malformed.".to_string())?;
+        if !verify_signature(&tx.signing_bytes(network_id), &signature, &public_key) {
+            return Err("Transaction signature does not match its contents.".into());
+        }
+        Ok(())
+    }
+
+    /// Checks that the sender can cover the amount plus fee and uses the next expected nonce
+    pub fn check_balance(tx: &Transaction, state: &WorldState) -> Result<(), String> {
+        state.check_transaction(tx)
+    }
+
+    /// Every check except the signature (for ancestors of the assume-valid block)
+    pub fn validate_unsigned(tx: &Transaction) -> Result<(), String> {
         Self::is_structurally_valid(tx)?;
+        Self::is_not_coinbase(tx)
+    }
+
+    /// Overall validator entrypoint, for a transaction on network `network_id`
+    pub fn validate(tx: &Transaction, network_id: u32) -> Result<(), String> {
+        Self::validate_unsigned(tx)?;
         Self::has_signature(tx)?;
-        // Future: Self::verify_signature(tx)?;
-        // Future: Self::check_balance(tx)?;
-        Ok(())
+        Self::verify_signature(tx, network_id)
+    }
+
+    /// Validates a transaction against the current world state (e.g. before mempool admission)
+    pub fn validate_with_state(tx: &Transaction, network_id: u32, state: &WorldState) -> Result<(), String> {
+        Self::validate(tx, network_id)?;
+        Self::check_balance(tx, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::keypair::PrivateKey;
+
+    const NETWORK: u32 = 47;
+
+    #[test]
+    fn rejects_forged_sender() {
+        let owner = PrivateKey::generate();
+        let thief = PrivateKey::generate();
+        let owner_address = derive_address(&owner.public_key());
+
+        let mut genuine = Transaction::new(&owner_address, "bob", 10, "");
+        genuine.sign(NETWORK, &owner);
+        assert!(TransactionValidator::validate(&genuine, NETWORK).is_ok());
+
+        // Signed by another key while claiming the owner's address
+        let mut forged = Transaction::new(&owner_address, "bob", 10, "");
+        forged.sign(NETWORK, &thief);
+        assert!(TransactionValidator::validate(&forged, NETWORK).is_err());
+
+        // The thief's signature presented under the owner's public key
+        forged.public_key = genuine.public_key.clone();
+        assert!(TransactionValidator::validate(&forged, NETWORK).is_err());
+
+        // The owner's signature replayed over altered contents
+        let mut tampered = genuine.clone();
+        tampered.amount = 1_000;
+        tampered.hash = tampered.compute_hash();
+        assert!(TransactionValidator::validate(&tampered, NETWORK).is_err());
+    }
+
+    #[test]
+    fn signatures_only_verify_on_their_network() {
+        let owner = PrivateKey::generate();
+        let mut tx = Transaction::new(&derive_address(&owner.public_key()), "bob", 10, "");
+        tx.sign(NETWORK, &owner);
+        assert!(TransactionValidator::validate(&tx, NETWORK).is_ok());
+        assert!(TransactionValidator::validate(&tx, NETWORK + 1).is_err());
     }
 }